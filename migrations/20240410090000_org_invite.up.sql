@@ -0,0 +1,18 @@
+CREATE TABLE org_invite (
+  id uuid NOT NULL,
+  org_id uuid NOT NULL,
+  email TEXT NOT NULL,
+  permission BIGINT NOT NULL,
+  token_hash TEXT NOT NULL,
+  invited_by TEXT NOT NULL,
+  expires_at timestamp with time zone NOT NULL,
+  accepted_by TEXT,
+  accepted_at timestamp with time zone,
+  declined_at timestamp with time zone,
+  created_at timestamp with time zone NOT NULL DEFAULT now(),
+  updated_at timestamp with time zone,
+  PRIMARY KEY (id),
+  CONSTRAINT fk_org FOREIGN KEY (org_id) REFERENCES org(id) ON DELETE CASCADE
+);
+
+CREATE INDEX org_invite_org_id ON org_invite (org_id);
@@ -0,0 +1,18 @@
+CREATE TABLE org_api_key (
+  id uuid NOT NULL,
+  org_id uuid NOT NULL,
+  name TEXT NOT NULL,
+  prefix TEXT NOT NULL,
+  key_hash TEXT NOT NULL,
+  scopes JSONB DEFAULT '[]' :: jsonb NOT NULL,
+  created_by TEXT NOT NULL,
+  expires_at timestamp with time zone,
+  last_used_at timestamp with time zone,
+  revoked_at timestamp with time zone,
+  created_at timestamp with time zone NOT NULL DEFAULT now(),
+  updated_at timestamp with time zone,
+  PRIMARY KEY (id),
+  CONSTRAINT fk_org FOREIGN KEY (org_id) REFERENCES org(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX org_api_key_key_hash ON org_api_key (key_hash);
@@ -0,0 +1,5 @@
+CREATE TABLE auth_revocation (
+  user_id TEXT NOT NULL,
+  revoked_at timestamp with time zone NOT NULL DEFAULT now(),
+  PRIMARY KEY (user_id)
+);
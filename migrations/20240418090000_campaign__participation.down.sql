@@ -0,0 +1,3 @@
+ALTER TABLE campaign DROP COLUMN blocked_countries;
+ALTER TABLE campaign DROP COLUMN allowed_countries;
+ALTER TABLE campaign DROP COLUMN max_participants;
@@ -0,0 +1,3 @@
+ALTER TABLE campaign ADD COLUMN max_participants BIGINT;
+ALTER TABLE campaign ADD COLUMN allowed_countries SMALLINT[];
+ALTER TABLE campaign ADD COLUMN blocked_countries SMALLINT[];
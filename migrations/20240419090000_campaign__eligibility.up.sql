@@ -0,0 +1 @@
+ALTER TABLE campaign ADD COLUMN eligibility JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1 @@
+ALTER TABLE engage DROP COLUMN rejections;
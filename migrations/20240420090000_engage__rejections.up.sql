@@ -0,0 +1 @@
+ALTER TABLE engage ADD COLUMN rejections JSONB DEFAULT '{}' :: jsonb NOT NULL;
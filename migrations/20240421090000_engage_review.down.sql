@@ -0,0 +1,6 @@
+DROP TABLE engage_review CASCADE;
+
+ALTER TABLE engage
+  DROP COLUMN assigned_to,
+  DROP COLUMN claimed_by,
+  DROP COLUMN claimed_until;
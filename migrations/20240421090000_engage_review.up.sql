@@ -0,0 +1,20 @@
+ALTER TABLE engage
+  ADD COLUMN assigned_to TEXT,
+  ADD COLUMN claimed_by TEXT,
+  ADD COLUMN claimed_until timestamp with time zone;
+
+CREATE TABLE engage_review (
+  id BIGSERIAL NOT NULL,
+  org_id uuid NOT NULL,
+  campaign_id uuid NOT NULL,
+  chain_id BIGINT NOT NULL,
+  signer_address TEXT NOT NULL,
+  task_id TEXT NOT NULL,
+  reviewer_id TEXT,
+  accepted BOOLEAN NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT now(),
+  PRIMARY KEY (id),
+  CONSTRAINT fk_org FOREIGN KEY (org_id) REFERENCES org(id) ON DELETE CASCADE
+);
+
+CREATE INDEX engage_review_org_id_created_at ON engage_review (org_id, created_at);
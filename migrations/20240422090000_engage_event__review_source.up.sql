@@ -0,0 +1,56 @@
+ALTER TABLE engage_event
+  ADD COLUMN review_source TEXT,
+  ADD COLUMN reviewer_id TEXT;
+
+ALTER TABLE engage_review ADD COLUMN source TEXT NOT NULL DEFAULT 'member';
+
+--
+-- Record who, or what, changed accepted tasks, as set for the transaction by the API
+--
+CREATE OR REPLACE FUNCTION log_engage_update() RETURNS TRIGGER LANGUAGE PLPGSQL AS $$ BEGIN
+INSERT INTO
+  engage_event (
+    org_id,
+    project_id,
+    campaign_id,
+    chain_id,
+    signer_address,
+    user_id,
+    old_submissions,
+    old_accepted,
+    old_coupon_serial,
+    old_coupon_url,
+    new_submissions,
+    new_accepted,
+    new_coupon_serial,
+    new_coupon_url,
+    review_source,
+    reviewer_id,
+    created_at
+  )
+VALUES
+(
+    NEW.org_id,
+    NEW.project_id,
+    NEW.campaign_id,
+    NEW.chain_id,
+    NEW.signer_address,
+    NEW.user_id,
+    OLD.submissions,
+    OLD.accepted,
+    OLD.coupon_serial,
+    OLD.coupon_url,
+    NEW.submissions,
+    NEW.accepted,
+    NEW.coupon_serial,
+    NEW.coupon_url,
+    CASE WHEN OLD.accepted IS DISTINCT FROM NEW.accepted
+      THEN NULLIF(current_setting('rs.review_source', true), '') END,
+    CASE WHEN OLD.accepted IS DISTINCT FROM NEW.accepted
+      THEN NULLIF(current_setting('rs.reviewer_id', true), '') END,
+    now()
+  );
+
+RETURN NEW;
+END;
+$$;
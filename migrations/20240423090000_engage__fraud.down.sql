@@ -0,0 +1,24 @@
+CREATE OR REPLACE FUNCTION subscribe_project() RETURNS trigger AS $$
+
+BEGIN
+  INSERT INTO project__user(
+    project_id,
+    user_id,
+    subscribed
+  ) VALUES (
+    NEW.project_id,
+    NEW.user_id,
+    true
+  );
+
+  RETURN NEW;
+END;
+
+$$ LANGUAGE plpgsql;
+
+DROP FUNCTION engage_proof_urls;
+
+DROP INDEX engage_event_user_id_created_at;
+DROP INDEX engage_campaign_id_user_id;
+
+ALTER TABLE campaign DROP COLUMN block_fraud;
@@ -0,0 +1,43 @@
+--
+-- Campaigns refuse a user's other wallets, which the trigger below used to do, unless they
+-- opt out
+--
+ALTER TABLE campaign ADD COLUMN block_fraud BOOLEAN NOT NULL DEFAULT true;
+
+CREATE INDEX engage_campaign_id_user_id ON engage (campaign_id, user_id);
+CREATE INDEX engage_event_user_id_created_at ON engage_event (user_id, created_at);
+
+--
+-- Links and images submitted as proof in engage submissions
+--
+CREATE FUNCTION engage_proof_urls(submissions JSONB) RETURNS SETOF TEXT LANGUAGE SQL IMMUTABLE AS $$
+SELECT s.value ->> 'link'
+FROM jsonb_each(submissions) s
+WHERE jsonb_typeof(s.value -> 'link') = 'string'
+UNION
+SELECT jsonb_array_elements_text(s.value -> 'images')
+FROM jsonb_each(submissions) s
+WHERE jsonb_typeof(s.value -> 'images') = 'array'
+$$;
+
+--
+-- Let users engage a project more than once, with other wallets or in other campaigns
+--
+CREATE OR REPLACE FUNCTION subscribe_project() RETURNS trigger AS $$
+
+BEGIN
+  INSERT INTO project__user(
+    project_id,
+    user_id,
+    subscribed
+  ) VALUES (
+    NEW.project_id,
+    NEW.user_id,
+    true
+  )
+  ON CONFLICT (project_id, user_id) DO NOTHING;
+
+  RETURN NEW;
+END;
+
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,3 @@
+DROP INDEX voucher_project_id_user_id;
+
+ALTER TABLE voucher DROP COLUMN voided_at;
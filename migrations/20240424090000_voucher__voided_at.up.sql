@@ -0,0 +1,7 @@
+--
+-- Vouchers of revoked tasks are voided, if their points were already spent the voucher keeps
+-- a negative balance, the debt the user has to pay off before minting again
+--
+ALTER TABLE voucher ADD COLUMN voided_at timestamp with time zone;
+
+CREATE INDEX voucher_project_id_user_id ON voucher (project_id, user_id);
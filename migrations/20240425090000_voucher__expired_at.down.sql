@@ -0,0 +1,5 @@
+DROP TABLE voucher_event;
+
+DROP INDEX voucher_valid_until;
+
+ALTER TABLE voucher DROP COLUMN expired_at;
@@ -0,0 +1,22 @@
+ALTER TABLE voucher ADD COLUMN expired_at timestamp with time zone;
+
+CREATE INDEX voucher_valid_until ON voucher (valid_until) WHERE expired_at IS NULL;
+
+--
+-- Changes to voucher balances that no one asked for, like points expiring
+--
+CREATE TABLE voucher_event (
+  id BIGSERIAL PRIMARY KEY,
+  org_id uuid NOT NULL,
+  project_id uuid NOT NULL,
+  campaign_id uuid NOT NULL,
+  chain_id BIGINT NOT NULL,
+  signer_address TEXT NOT NULL,
+  user_id TEXT NOT NULL,
+  task_id TEXT NOT NULL,
+  kind TEXT NOT NULL,
+  amount BIGINT NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT now()
+);
+
+CREATE INDEX voucher_event_user_id_created_at ON voucher_event (user_id, created_at);
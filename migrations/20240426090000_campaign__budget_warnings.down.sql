@@ -0,0 +1,3 @@
+DROP TABLE campaign_budget_warning;
+
+ALTER TABLE campaign DROP COLUMN budget_warnings;
@@ -0,0 +1,17 @@
+--
+-- Percentages of the budget at which to warn the org, e.g. {50,80,100}
+--
+ALTER TABLE campaign ADD COLUMN budget_warnings SMALLINT[];
+
+CREATE TABLE campaign_budget_warning (
+  id BIGSERIAL PRIMARY KEY,
+  org_id uuid NOT NULL,
+  campaign_id uuid NOT NULL,
+  threshold SMALLINT NOT NULL,
+  budget numeric NOT NULL,
+  committed numeric NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT now(),
+  CONSTRAINT fk_campaign FOREIGN KEY (campaign_id) REFERENCES campaign(id) ON DELETE CASCADE
+);
+
+CREATE INDEX campaign_budget_warning_campaign_id ON campaign_budget_warning (campaign_id, created_at);
@@ -0,0 +1,5 @@
+ALTER TABLE voucher DROP COLUMN multiplier_id;
+ALTER TABLE voucher DROP COLUMN bonus;
+ALTER TABLE voucher DROP COLUMN base;
+
+DROP TABLE point_multiplier;
@@ -0,0 +1,29 @@
+--
+-- Promotions that multiply the points of approved tasks, e.g. double points weekends
+--
+CREATE TABLE point_multiplier (
+  id uuid PRIMARY KEY,
+  org_id uuid NOT NULL,
+  project_id uuid NOT NULL,
+  campaign_id uuid,
+  name TEXT NOT NULL,
+  factor numeric NOT NULL,
+  conditions JSONB NOT NULL DEFAULT '[]',
+  starts_at timestamp with time zone NOT NULL,
+  ends_at timestamp with time zone NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT now(),
+  CONSTRAINT fk_org FOREIGN KEY (org_id) REFERENCES org(id) ON DELETE CASCADE,
+  CONSTRAINT fk_project FOREIGN KEY (project_id) REFERENCES project(id) ON DELETE CASCADE,
+  CONSTRAINT fk_campaign FOREIGN KEY (campaign_id) REFERENCES campaign(id) ON DELETE CASCADE
+);
+
+CREATE INDEX point_multiplier_project_id_ends_at ON point_multiplier (project_id, ends_at);
+
+--
+-- Voucher values are the task's points, plus the bonus of the multiplier applied to them
+--
+ALTER TABLE voucher ADD COLUMN base BIGINT;
+UPDATE voucher SET base = value;
+ALTER TABLE voucher ALTER COLUMN base SET NOT NULL;
+ALTER TABLE voucher ADD COLUMN bonus BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE voucher ADD COLUMN multiplier_id uuid;
@@ -0,0 +1,3 @@
+DROP INDEX voucher_org_id_user_id;
+
+DROP TABLE org__reward;
@@ -0,0 +1,19 @@
+--
+-- Rewards priced in points pooled across all of an org's projects
+--
+CREATE TABLE org__reward (
+  org_id uuid NOT NULL,
+  reward_id uuid NOT NULL,
+  point BIGINT NOT NULL,
+  approved BOOLEAN NOT NULL DEFAULT false,
+  active BOOLEAN NOT NULL DEFAULT true,
+  max_mint BIGINT,
+  user_mint BIGINT,
+  created_at timestamp with time zone NOT NULL DEFAULT now(),
+  updated_at timestamp with time zone,
+  PRIMARY KEY (org_id, reward_id),
+  CONSTRAINT fk_org FOREIGN KEY (org_id) REFERENCES org(id) ON DELETE CASCADE,
+  CONSTRAINT fk_reward FOREIGN KEY (reward_id) REFERENCES reward(id)
+);
+
+CREATE INDEX voucher_org_id_user_id ON voucher (org_id, user_id);
@@ -0,0 +1,6 @@
+ALTER TABLE voucher DROP COLUMN transfer_id;
+
+DROP TABLE point_transfer;
+
+ALTER TABLE project DROP COLUMN point_transfer_daily_limit;
+ALTER TABLE project DROP COLUMN point_transfer;
@@ -0,0 +1,27 @@
+--
+-- Projects opt in to let their users gift points to each other, up to a daily limit per sender
+--
+ALTER TABLE project ADD COLUMN point_transfer BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE project ADD COLUMN point_transfer_daily_limit BIGINT;
+
+CREATE TABLE point_transfer (
+  id uuid PRIMARY KEY,
+  org_id uuid NOT NULL,
+  project_id uuid NOT NULL,
+  sender_id TEXT NOT NULL,
+  recipient_id TEXT NOT NULL,
+  chain_id BIGINT NOT NULL,
+  signer_address TEXT NOT NULL,
+  amount BIGINT NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT now(),
+  CONSTRAINT fk_org FOREIGN KEY (org_id) REFERENCES org(id) ON DELETE CASCADE,
+  CONSTRAINT fk_project FOREIGN KEY (project_id) REFERENCES project(id) ON DELETE CASCADE
+);
+
+CREATE INDEX point_transfer_project_id_sender_id ON point_transfer (project_id, sender_id, created_at);
+CREATE INDEX point_transfer_project_id_recipient_id ON point_transfer (project_id, recipient_id, created_at);
+
+--
+-- Received points are vouchers of the recipient's wallet, one per voucher spent by the sender
+--
+ALTER TABLE voucher ADD COLUMN transfer_id uuid;
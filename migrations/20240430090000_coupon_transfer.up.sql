@@ -0,0 +1,21 @@
+--
+-- Minted coupons handed over to another user, directly or through a one-time claim link
+--
+CREATE TABLE coupon_transfer (
+  id uuid PRIMARY KEY,
+  reward_id uuid NOT NULL,
+  number BIGINT NOT NULL,
+  sender_id TEXT NOT NULL,
+  recipient_id TEXT,
+  token_hash TEXT,
+  expires_at timestamp with time zone,
+  claimed_by TEXT,
+  claimed_at timestamp with time zone,
+  cancelled_at timestamp with time zone,
+  created_at timestamp with time zone NOT NULL DEFAULT now(),
+  updated_at timestamp with time zone,
+  CONSTRAINT fk_coupon FOREIGN KEY (reward_id, number) REFERENCES coupon(reward_id, number) ON DELETE CASCADE
+);
+
+CREATE INDEX coupon_transfer_reward_id_number ON coupon_transfer (reward_id, number);
+CREATE INDEX coupon_transfer_claimed_by ON coupon_transfer (claimed_by);
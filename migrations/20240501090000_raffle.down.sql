@@ -0,0 +1,5 @@
+DROP TABLE raffle_winner;
+
+DROP TABLE raffle_entry;
+
+DROP TABLE raffle;
@@ -0,0 +1,56 @@
+--
+-- Rewards given away by draw to users who spent points on tickets. The seed picking the
+-- winners is committed to with its hash when the raffle is created and revealed by the draw
+--
+CREATE TABLE raffle (
+  id uuid PRIMARY KEY,
+  org_id uuid NOT NULL,
+  project_id uuid NOT NULL,
+  reward_id uuid NOT NULL,
+  name TEXT NOT NULL,
+  ticket_point BIGINT NOT NULL,
+  user_tickets BIGINT,
+  winners BIGINT NOT NULL,
+  refund BOOLEAN NOT NULL DEFAULT false,
+  seed TEXT NOT NULL,
+  seed_hash TEXT NOT NULL,
+  draw_at timestamp with time zone NOT NULL,
+  drawn_at timestamp with time zone,
+  created_at timestamp with time zone NOT NULL DEFAULT now(),
+  updated_at timestamp with time zone,
+  CONSTRAINT fk_org FOREIGN KEY (org_id) REFERENCES org(id) ON DELETE CASCADE,
+  CONSTRAINT fk_project FOREIGN KEY (project_id) REFERENCES project(id) ON DELETE CASCADE,
+  CONSTRAINT fk_reward FOREIGN KEY (reward_id) REFERENCES reward(id)
+);
+
+CREATE INDEX raffle_project_id ON raffle (project_id);
+CREATE INDEX raffle_draw_at ON raffle (draw_at) WHERE drawn_at IS NULL;
+
+--
+-- Tickets bought by a user, with the vouchers spent on them to refund losers
+--
+CREATE TABLE raffle_entry (
+  id BIGSERIAL PRIMARY KEY,
+  raffle_id uuid NOT NULL,
+  user_id TEXT NOT NULL,
+  tickets BIGINT NOT NULL,
+  point BIGINT NOT NULL,
+  spent JSONB NOT NULL DEFAULT '[]',
+  won BOOLEAN,
+  refunded_at timestamp with time zone,
+  created_at timestamp with time zone NOT NULL DEFAULT now(),
+  CONSTRAINT fk_raffle FOREIGN KEY (raffle_id) REFERENCES raffle(id) ON DELETE CASCADE
+);
+
+CREATE INDEX raffle_entry_raffle_id_user_id ON raffle_entry (raffle_id, user_id);
+
+CREATE TABLE raffle_winner (
+  raffle_id uuid NOT NULL,
+  rank BIGINT NOT NULL,
+  user_id TEXT NOT NULL,
+  reward_id uuid NOT NULL,
+  number BIGINT NOT NULL,
+  PRIMARY KEY (raffle_id, rank),
+  CONSTRAINT fk_raffle FOREIGN KEY (raffle_id) REFERENCES raffle(id) ON DELETE CASCADE,
+  CONSTRAINT fk_coupon FOREIGN KEY (reward_id, number) REFERENCES coupon(reward_id, number)
+);
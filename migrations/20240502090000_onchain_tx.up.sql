@@ -0,0 +1,13 @@
+--
+-- Transactions accepted for on-chain tasks, each counts for a single task of a single participant
+--
+CREATE TABLE onchain_tx (
+  chain_id BIGINT NOT NULL,
+  tx_hash TEXT NOT NULL,
+  campaign_id uuid NOT NULL,
+  task_id TEXT NOT NULL,
+  user_id TEXT NOT NULL,
+  created_at timestamp with time zone NOT NULL DEFAULT now(),
+  PRIMARY KEY (chain_id, tx_hash),
+  CONSTRAINT fk_campaign FOREIGN KEY (campaign_id) REFERENCES campaign(id) ON DELETE CASCADE
+);
@@ -0,0 +1,13 @@
+UPDATE org
+SET admins = (
+  SELECT COALESCE(jsonb_object_agg(
+    key,
+    CASE
+      WHEN value::BIGINT = 9007199254740991 THEN value
+      WHEN value::BIGINT & 2 != 0 THEN '2'::JSONB
+      WHEN value::BIGINT = 0 THEN '0'::JSONB
+      ELSE '1'::JSONB
+    END
+  ), '{}'::JSONB)
+  FROM jsonb_each(admins)
+);
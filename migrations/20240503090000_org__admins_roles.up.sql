@@ -0,0 +1,17 @@
+--
+-- Admin permissions used to be levels, editors from 2 up. Map them to the role bits, leaving
+-- owners as they are and making every other editor an editor role, with view and review
+--
+UPDATE org
+SET admins = (
+  SELECT COALESCE(jsonb_object_agg(
+    key,
+    CASE
+      WHEN value::BIGINT = 9007199254740991 THEN value
+      WHEN value::BIGINT >= 2 THEN '7'::JSONB
+      WHEN value::BIGINT = 1 THEN '1'::JSONB
+      ELSE '0'::JSONB
+    END
+  ), '{}'::JSONB)
+  FROM jsonb_each(admins)
+);
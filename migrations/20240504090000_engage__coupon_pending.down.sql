@@ -0,0 +1 @@
+ALTER TABLE engage DROP COLUMN coupon_pending_at;
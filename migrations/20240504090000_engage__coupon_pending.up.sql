@@ -0,0 +1,5 @@
+--
+-- Coupons being issued by Mezzofy, they count toward the campaign budget until they're
+-- recorded or released
+--
+ALTER TABLE engage ADD COLUMN coupon_pending_at timestamp with time zone;
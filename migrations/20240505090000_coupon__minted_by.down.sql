@@ -0,0 +1,2 @@
+DROP INDEX coupon_reward_id_minted_by;
+ALTER TABLE coupon DROP COLUMN minted_by;
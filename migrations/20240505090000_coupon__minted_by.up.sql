@@ -0,0 +1,21 @@
+--
+-- Who minted a coupon, which mint limits count since coupons can be handed over afterwards
+--
+ALTER TABLE coupon ADD COLUMN minted_by TEXT;
+
+UPDATE coupon c
+SET minted_by = COALESCE(
+  (
+    SELECT t.sender_id
+    FROM coupon_transfer t
+    WHERE t.reward_id = c.reward_id
+      AND t.number = c.number
+      AND t.claimed_at IS NOT NULL
+    ORDER BY t.claimed_at ASC
+    LIMIT 1
+  ),
+  c.user_id
+)
+WHERE minted_at IS NOT NULL;
+
+CREATE INDEX coupon_reward_id_minted_by ON coupon (reward_id, minted_by);
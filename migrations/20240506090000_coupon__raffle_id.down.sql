@@ -0,0 +1,3 @@
+DROP INDEX coupon_raffle_id;
+ALTER TABLE coupon DROP CONSTRAINT fk_raffle;
+ALTER TABLE coupon DROP COLUMN raffle_id;
@@ -0,0 +1,40 @@
+--
+-- Coupons set aside for the winners of a raffle, the mints leave them alone
+--
+ALTER TABLE coupon ADD COLUMN raffle_id uuid;
+ALTER TABLE coupon ADD CONSTRAINT fk_raffle FOREIGN KEY (raffle_id) REFERENCES raffle(id) ON DELETE SET NULL;
+
+CREATE INDEX coupon_raffle_id ON coupon (raffle_id) WHERE raffle_id IS NOT NULL;
+
+-- raffles not drawn yet reserve what is left of their reward, oldest first
+WITH free AS (
+  SELECT
+    reward_id,
+    number,
+    ROW_NUMBER() OVER (PARTITION BY reward_id ORDER BY number) AS n
+  FROM coupon
+  WHERE user_id IS NULL
+    AND minted_at IS NULL
+),
+wanted AS (
+  SELECT
+    id,
+    reward_id,
+    winners,
+    COALESCE(SUM(winners) OVER (
+      PARTITION BY reward_id
+      ORDER BY created_at, id
+      ROWS BETWEEN UNBOUNDED PRECEDING AND 1 PRECEDING
+    ), 0) AS before
+  FROM raffle
+  WHERE drawn_at IS NULL
+)
+UPDATE coupon c
+SET raffle_id = w.id
+FROM free f
+JOIN wanted w
+ON w.reward_id = f.reward_id
+  AND f.n > w.before
+  AND f.n <= w.before + w.winners
+WHERE c.reward_id = f.reward_id
+  AND c.number = f.number;
@@ -3,36 +3,42 @@ use axum::{
   extract::{FromRef, FromRequestParts, State},
   http::{request::Parts, StatusCode},
   response::{IntoResponse, Response},
-  routing::{delete, get, patch, post},
+  routing::{delete, get, patch, post, put},
   Router,
 };
 use axum_extra::{
   headers::{authorization::Bearer, Authorization},
   TypedHeader,
 };
-use firebase_auth::FirebaseAuth;
 use serde::Serialize;
+use uuid::Uuid;
 
 use crate::{
-  auth::{user::UserService, MyFirebaseUser},
+  auth::{
+    api_key::{OrgApiKey, API_KEY_HEADER},
+    provider::{AuthProvider, ClaimsStore},
+    secret, MyFirebaseUser,
+  },
   db::{self},
-  mezzofy, subscan,
+  evm, mezzofy, subscan,
 };
 
 use self::rs::engage::EngageEventStream;
 
 pub mod cm;
+pub mod ext;
 pub mod rs;
 pub mod su;
 
 #[derive(Clone)]
 pub struct AppState {
   pub pool: sqlx::PgPool,
-  pub firebase_auth: FirebaseAuth,
-  pub claims_service: UserService,
+  pub auth_provider: AuthProvider,
+  pub claims_service: ClaimsStore,
   pub engage_event_stream: EngageEventStream,
   pub mezzofy_client: mezzofy::Client,
   pub subscan_client: subscan::Client,
+  pub evm_client: evm::Client,
 }
 
 impl FromRef<AppState> for sqlx::PgPool {
@@ -48,19 +54,21 @@ pub struct Server {
 impl Server {
   pub fn new(
     pool: sqlx::PgPool,
-    firebase_auth: FirebaseAuth,
-    claims_service: UserService,
+    auth_provider: AuthProvider,
+    claims_service: ClaimsStore,
     engage_event_stream: EngageEventStream,
     mezzofy_client: mezzofy::Client,
     subscan_client: subscan::Client,
+    evm_client: evm::Client,
   ) -> Self {
     let app_state = AppState {
       pool,
-      firebase_auth,
+      auth_provider,
       claims_service,
       engage_event_stream,
       mezzofy_client,
       subscan_client,
+      evm_client,
     };
 
     let router = axum::Router::new()
@@ -68,6 +76,7 @@ impl Server {
       .route("/health", get(health))
       // .route("/su/init", post(su::auth::init))
       .route("/su/grant", post(su::auth::grant))
+      .route("/su/revoke", post(su::auth::revoke))
       .route(
         "/su/project-reward/:org_id/:project_id/:reward_id",
         patch(su::project_reward::update),
@@ -93,6 +102,27 @@ impl Server {
           .put(cm::org::replace)
           .delete(cm::org::delete),
       )
+      .route("/cm/org/:org_id/members", get(cm::org_member::list))
+      .route(
+        "/cm/org/:org_id/members/:user_id",
+        patch(cm::org_member::update).delete(cm::org_member::delete),
+      )
+      .route(
+        "/cm/org/:org_id/invites",
+        get(cm::org_invite::list).post(cm::org_invite::create),
+      )
+      .route(
+        "/cm/org/:org_id/invites/:invite_id",
+        delete(cm::org_invite::delete),
+      )
+      .route(
+        "/cm/org/:org_id/invites/:invite_id/accept",
+        post(cm::org_invite::accept),
+      )
+      .route(
+        "/cm/org/:org_id/invites/:invite_id/decline",
+        post(cm::org_invite::decline),
+      )
       .route(
         "/cm/project/:org_id",
         get(cm::project::list).post(cm::project::create),
@@ -122,6 +152,21 @@ impl Server {
           .patch(cm::project_reward::update)
           .delete(cm::project_reward::unlink),
       )
+      .route(
+        "/cm/raffle/:org_id",
+        get(cm::raffle::list).post(cm::raffle::create),
+      )
+      .route("/cm/raffle/:org_id/:raffle_id", get(cm::raffle::get))
+      .route(
+        "/cm/org-reward/:org_id",
+        get(cm::org_reward::list).post(cm::org_reward::create),
+      )
+      .route(
+        "/cm/org-reward/:org_id/:reward_id",
+        get(cm::org_reward::get)
+          .patch(cm::org_reward::update)
+          .delete(cm::org_reward::unlink),
+      )
       .route(
         "/cm/campaign/:org_id",
         get(cm::campaign::list).post(cm::campaign::create),
@@ -133,6 +178,10 @@ impl Server {
           .put(cm::campaign::replace)
           .delete(cm::campaign::delete),
       )
+      .route(
+        "/cm/campaign/:org_id/:campaign_id/budget-warnings",
+        get(cm::campaign::list_budget_warnings),
+      )
       .route(
         "/cm/campaign-reward/:org_id/:campaign_id",
         get(cm::campaign_reward::list).post(cm::campaign_reward::create),
@@ -144,12 +193,29 @@ impl Server {
           .delete(cm::campaign_reward::unlink),
       )
       .route("/cm/engage/:org_id", get(cm::engage::list))
+      .route(
+        "/cm/review/:org_id",
+        get(cm::engage::list_pending).post(cm::engage::review),
+      )
+      .route("/cm/review/:org_id/stats", get(cm::engage::review_stats))
+      .route(
+        "/cm/review/:org_id/:campaign_id/:chain_id/:signer_address/claim",
+        post(cm::engage::claim).delete(cm::engage::release),
+      )
+      .route(
+        "/cm/review/:org_id/:campaign_id/:chain_id/:signer_address/assign",
+        put(cm::engage::assign),
+      )
       .route(
         "/cm/engage/:org_id/:campaign_id/:chain_id/:signer_address",
         get(cm::engage::get)
           .patch(cm::engage::approve)
           .delete(cm::engage::delete),
       )
+      .route(
+        "/cm/engage/:org_id/:campaign_id/:chain_id/:signer_address/reject",
+        post(cm::engage::reject),
+      )
       .route(
         "/cm/engage/:org_id/:campaign_id/:chain_id/:signer_address/coupon",
         patch(cm::engage::update_coupon),
@@ -166,6 +232,30 @@ impl Server {
         "/cm/engage/:org_id/:campaign_id/:chain_id/:signer_address/commit",
         post(cm::engage::commit_coupon),
       )
+      .route(
+        "/cm/api-key/:org_id",
+        get(cm::api_key::list).post(cm::api_key::create),
+      )
+      .route("/cm/api-key/:org_id/:key_id", delete(cm::api_key::revoke))
+      .route(
+        "/cm/multiplier/:org_id",
+        get(cm::multiplier::list).post(cm::multiplier::create),
+      )
+      .route(
+        "/cm/multiplier/:org_id/:multiplier_id",
+        delete(cm::multiplier::delete),
+      )
+      .route("/ext/campaign/:org_id", get(ext::campaign::list))
+      .route("/ext/campaign/:org_id/:campaign_id", get(ext::campaign::get))
+      .route("/ext/engage/:org_id", get(ext::engage::list))
+      .route(
+        "/ext/engage/:org_id/:campaign_id/:chain_id/:signer_address",
+        get(ext::engage::get).patch(ext::engage::approve),
+      )
+      .route(
+        "/ext/engage/:org_id/:campaign_id/:chain_id/:signer_address/reject",
+        post(ext::engage::reject),
+      )
       .route("/countries", get(rs::country::list))
       .route("/auth/fix", get(rs::auth::fix_claims))
       .route("/auth/link", post(rs::auth::link))
@@ -176,6 +266,10 @@ impl Server {
       .route("/engage", get(rs::campaign::list))
       .route("/engage/:campaign_id", get(rs::campaign::get))
       .route("/engage/:campaign_id/org_id", get(rs::campaign::get_org_id))
+      .route(
+        "/engage/:campaign_id/eligibility",
+        get(rs::engage::eligibility),
+      )
       .route(
         "/tasks/:chain_id/:signer_address",
         get(rs::engage::get_tasks),
@@ -189,6 +283,18 @@ impl Server {
       )
       .route("/coupons", get(rs::coupon::list))
       .route("/coupons/:reward_id/:number", get(rs::coupon::get))
+      .route(
+        "/coupons/:reward_id/:number/transfers",
+        get(rs::coupon_transfer::list).post(rs::coupon_transfer::create),
+      )
+      .route(
+        "/coupons/:reward_id/:number/transfers/:transfer_id",
+        delete(rs::coupon_transfer::cancel),
+      )
+      .route(
+        "/coupon-transfers/:transfer_id/claim",
+        post(rs::coupon_transfer::claim),
+      )
       // .route(
       //   "/coupons/:chain_id/:signer_address/:campaign_id",
       //   get(rs::engage::retrieve_coupon),
@@ -206,6 +312,21 @@ impl Server {
         "/project-rewards/:project_id/:reward_id",
         get(rs::project_reward::get).post(rs::reward::mint_from_project),
       )
+      .route(
+        "/point-transfers/:project_id",
+        get(rs::point_transfer::list).post(rs::point_transfer::create),
+      )
+      .route("/org-rewards/:org_id", get(rs::org_reward::list))
+      .route("/raffles/:project_id", get(rs::raffle::list))
+      .route("/raffles/:project_id/:raffle_id", get(rs::raffle::get))
+      .route(
+        "/raffles/:project_id/:raffle_id/entries",
+        get(rs::raffle::list_entrants).post(rs::raffle::buy),
+      )
+      .route(
+        "/org-rewards/:org_id/:reward_id",
+        get(rs::org_reward::get).post(rs::reward::mint_from_org),
+      )
       .route(
         "/campaign-participations/:campaign_id",
         get(rs::campaign_participation::list),
@@ -224,6 +345,7 @@ impl Server {
         get(rs::voucher::get),
       )
       .route("/me", get(rs::me::get))
+      .route("/me/points/expiring", get(rs::me::get_expiring_points))
       .with_state(app_state);
 
     Self { router }
@@ -249,6 +371,14 @@ pub fn handle_db_error(err: db::Error) -> Response {
       (StatusCode::BAD_REQUEST, err.to_string()).into_response()
     }
     db::Error::NotFound => StatusCode::NOT_FOUND.into_response(),
+    db::Error::Claimed
+    | db::Error::Debt
+    | db::Error::OverBudget
+    | db::Error::Redeemed
+    | db::Error::Issued
+    | db::Error::Full
+    | db::Error::LastOwner
+    | db::Error::Member => (StatusCode::CONFLICT, err.to_string()).into_response(),
     _ => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
   }
 }
@@ -278,10 +408,31 @@ where
         .await
         .map_err(http_error_handler(StatusCode::BAD_REQUEST))?;
 
-    AppState::from_ref(state)
-      .firebase_auth
-      .verify(bearer.token())
-      .map_err(|err| (StatusCode::UNAUTHORIZED, err.to_string()))
+    authenticate(&AppState::from_ref(state), bearer.token()).await
+  }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for OrgApiKey
+where
+  S: Send + Sync,
+  AppState: FromRef<S>,
+{
+  type Rejection = (StatusCode, String);
+
+  async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+    let key = parts
+      .headers
+      .get(API_KEY_HEADER)
+      .and_then(|v| v.to_str().ok())
+      .ok_or((StatusCode::UNAUTHORIZED, String::from("API key missing")))?;
+
+    db::org_api_key::authenticate(&AppState::from_ref(state).pool, &secret::hash(key))
+      .await
+      .map_err(|err| match err {
+        db::Error::NotFound => (StatusCode::UNAUTHORIZED, String::from("Invalid API key")),
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
+      })
   }
 }
 
@@ -303,16 +454,61 @@ where
         Err(_) => return Ok(Self(None)),
       };
 
-    match AppState::from_ref(state)
-      .firebase_auth
-      .verify(bearer.token())
-    {
-      Ok(user) => Ok(Self(Some(user))),
-      Err(err) => Err((StatusCode::UNAUTHORIZED, err.to_string())),
-    }
+    authenticate(&AppState::from_ref(state), bearer.token())
+      .await
+      .map(|user| Self(Some(user)))
   }
 }
 
+/// Verify a bearer token and reject it if it was issued before the user's tokens were revoked.
+async fn authenticate(state: &AppState, token: &str) -> Result<MyFirebaseUser, (StatusCode, String)> {
+  let user = state
+    .auth_provider
+    .verify(token)
+    .map_err(|err| (StatusCode::UNAUTHORIZED, err.to_string()))?;
+
+  match db::auth_revocation::get(&state.pool, &user.sub).await {
+    Ok(Some(revoked_at)) if (user.iat as i64) < revoked_at.timestamp() => Err((
+      StatusCode::UNAUTHORIZED,
+      String::from("Token revoked"),
+    )),
+    Ok(_) => Ok(user),
+    Err(err) => Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+  }
+}
+
+/// Replace the org permission carried by the token with the one stored in the DB, so that
+/// sensitive operations are not authorised by stale claims.
+pub async fn refresh_org_claim(
+  db: &sqlx::PgPool,
+  user: &mut MyFirebaseUser,
+  org_id: Uuid,
+) -> Result<(), Response> {
+  let permission = db::org::get_permission(db, org_id, &user.sub)
+    .await
+    .map_err(handle_db_error)?;
+
+  match permission {
+    Some(p) => user.claims.orgs.insert(org_id.to_string(), p),
+    None => user.claims.orgs.remove(&org_id.to_string()),
+  };
+  Ok(())
+}
+
+/// Replace the admin level carried by the token with the one currently in the claims store.
+pub async fn refresh_admin_claim(
+  claims_service: &mut ClaimsStore,
+  user: &mut MyFirebaseUser,
+) -> Result<(), Response> {
+  let u = claims_service
+    .lookup(&user.sub)
+    .await
+    .map_err(|err| (StatusCode::BAD_GATEWAY, err.to_string()).into_response())?;
+
+  user.claims.admin = u.customAttributes.admin;
+  Ok(())
+}
+
 fn http_error_handler<E>(status: StatusCode) -> impl Fn(E) -> (StatusCode, String)
 where
   E: std::error::Error,
@@ -320,9 +516,9 @@ where
   move |err: E| -> (StatusCode, String) { (status, err.to_string()) }
 }
 
-impl FromRef<AppState> for FirebaseAuth {
+impl FromRef<AppState> for AuthProvider {
   fn from_ref(state: &AppState) -> Self {
-    state.firebase_auth.clone()
+    state.auth_provider.clone()
   }
 }
 
@@ -338,6 +534,12 @@ impl FromRef<AppState> for subscan::Client {
   }
 }
 
+impl FromRef<AppState> for evm::Client {
+  fn from_ref(state: &AppState) -> Self {
+    state.evm_client.clone()
+  }
+}
+
 impl FromRef<AppState> for EngageEventStream {
   fn from_ref(state: &AppState) -> Self {
     state.engage_event_stream.clone()
@@ -354,7 +556,7 @@ impl IntoResponse for UnauthorizedResponse {
   }
 }
 
-impl FromRef<AppState> for UserService {
+impl FromRef<AppState> for ClaimsStore {
   fn from_ref(state: &AppState) -> Self {
     state.claims_service.clone()
   }
@@ -1,8 +1,14 @@
+pub mod api_key;
 pub mod auth;
 pub mod org;
+pub mod org_invite;
+pub mod org_member;
+pub mod org_reward;
 pub mod project;
 pub mod project_reward;
+pub mod raffle;
 pub mod campaign;
 pub mod campaign_reward;
 pub mod engage;
+pub mod multiplier;
 pub mod reward;
@@ -0,0 +1,104 @@
+use axum::{
+  extract::{Path, State},
+  http::StatusCode,
+  response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+  api::{handle_db_error, handle_result, into_json_response, refresh_org_claim},
+  auth::{
+    api_key::{self, ApiScope},
+    secret, MyFirebaseUser,
+  },
+  db::{self, new_uuid, IdPrefix},
+};
+
+pub async fn list(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path(org_id): Path<Uuid>,
+) -> Response {
+  if !user.is_owner(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let res = db::org_api_key::list(&db, org_id);
+
+  handle_result(res.await)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateForm {
+  pub name: String,
+  pub scopes: Vec<ApiScope>,
+  pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// The key itself is only returned once, on creation.
+#[derive(Serialize)]
+pub struct ApiKeyCreated {
+  id: Uuid,
+  key: String,
+  prefix: String,
+  created_at: DateTime<Utc>,
+}
+
+pub async fn create(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  Path(org_id): Path<Uuid>,
+  Json(form): Json<CreateForm>,
+) -> Result<Response, Response> {
+  refresh_org_claim(&db, &mut user, org_id).await?;
+  if !user.is_owner(org_id) {
+    return Err(StatusCode::FORBIDDEN.into_response());
+  }
+  if form.scopes.is_empty() {
+    return Err((StatusCode::BAD_REQUEST, String::from("scopes missing")).into_response());
+  }
+
+  let id = new_uuid(IdPrefix::ApiKey);
+  let (key, prefix) = api_key::generate();
+  let res = db::org_api_key::create(
+    &db,
+    db::org_api_key::CreateParam {
+      id,
+      org_id,
+      name: &form.name,
+      prefix: &prefix,
+      key_hash: &secret::hash(&key),
+      scopes: &form.scopes,
+      created_by: &user.sub,
+      expires_at: form.expires_at,
+    },
+  )
+  .await
+  .map_err(handle_db_error)?;
+
+  Ok(into_json_response(&ApiKeyCreated {
+    id,
+    key,
+    prefix,
+    created_at: res.created_at,
+  }))
+}
+
+pub async fn revoke(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  Path((org_id, key_id)): Path<(Uuid, Uuid)>,
+) -> Response {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.is_owner(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let res = db::org_api_key::revoke(&db, org_id, key_id);
+
+  handle_result(res.await)
+}
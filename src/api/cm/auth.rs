@@ -6,14 +6,14 @@ use axum::{
 use uuid::Uuid;
 
 use crate::{
-  auth::{user::UserService, CustomClaims, MyFirebaseUser},
+  auth::{provider::ClaimsStore, CustomClaims, MyFirebaseUser},
   db,
 };
 
 pub async fn grant(
   State(db): State<sqlx::PgPool>,
   user: MyFirebaseUser,
-  State(mut claims_service): State<UserService>,
+  State(mut claims_service): State<ClaimsStore>,
   Path(org_id): Path<Uuid>,
 ) -> Response {
   let res = db::org::get(&db, org_id).await;
@@ -5,17 +5,19 @@ use axum::{
 };
 use chrono::{DateTime, NaiveDate, Utc};
 use is_empty::IsEmpty;
+use iso3166::Country;
 use rust_decimal::Decimal;
 use serde::Deserialize;
 use serde_with::{serde_as, NoneAsEmptyString};
 use uuid::Uuid;
 
 use crate::{
-  api::handle_result,
+  api::{handle_db_error, handle_result, into_json_response, refresh_org_claim},
   auth::MyFirebaseUser,
   db::{
     self,
-    campaign::{CampaignFilter, CreateParam, ReplaceParams, Task, UpdateParams},
+    campaign::{CampaignFilter, CreateParam, ReplaceParams, Rule, Task, UpdateParams},
+    campaign_budget::BudgetedCampaign,
     new_uuid, IdCreateResult, IdPrefix,
   },
 };
@@ -24,11 +26,32 @@ pub async fn get(
   State(db): State<sqlx::PgPool>,
   user: MyFirebaseUser,
   Path((org_id, campaign_id)): Path<(Uuid, Uuid)>,
+) -> Result<Response, Response> {
+  if !user.can_view(org_id) {
+    return Err(StatusCode::FORBIDDEN.into_response());
+  }
+  let campaign = db::campaign::get(&db, org_id, campaign_id)
+    .await
+    .map_err(handle_db_error)?;
+  let budget_usage = db::campaign_budget::get_usage(&db, campaign_id)
+    .await
+    .map_err(handle_db_error)?;
+
+  Ok(into_json_response(&BudgetedCampaign {
+    campaign,
+    budget_usage,
+  }))
+}
+
+pub async fn list_budget_warnings(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path((org_id, campaign_id)): Path<(Uuid, Uuid)>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if !user.can_view(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
-  let res = db::campaign::get(&db, org_id, campaign_id);
+  let res = db::campaign_budget::list_warnings(&db, org_id, campaign_id);
 
   handle_result(res.await)
 }
@@ -48,7 +71,7 @@ pub async fn list(
   Query(p): Query<db::ListParams<ListFilter>>,
   Path(org_id): Path<Uuid>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if !user.can_view(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
   let res = db::campaign::list(
@@ -94,17 +117,39 @@ pub struct CreateForm {
   pub voucher_policy: Option<i16>,
   #[serde_as(as = "NoneAsEmptyString")]
   pub voucher_expire_at: Option<NaiveDate>,
+  pub max_participants: Option<i64>,
+  pub allowed_countries: Option<Vec<i16>>,
+  pub blocked_countries: Option<Vec<i16>>,
+  pub eligibility: Option<Vec<Rule>>,
+  pub block_fraud: Option<bool>,
+  pub budget_warnings: Option<Vec<i16>>,
 }
 
 pub async fn create(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
   Path(org_id): Path<Uuid>,
-  Json(p): Json<CreateForm>,
+  Json(mut p): Json<CreateForm>,
 ) -> Response {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
   if !user.can_edit(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
+  if let Err(msg) = prepare_tasks(&mut p.tasks)
+    .and_then(|_| {
+      check_participation(
+        p.max_participants,
+        p.allowed_countries.as_deref(),
+        p.blocked_countries.as_deref(),
+        p.eligibility.as_deref(),
+      )
+    })
+    .and_then(|_| check_budget_warnings(p.budget_warnings.as_deref()))
+  {
+    return (StatusCode::BAD_REQUEST, msg).into_response();
+  }
 
   let id = new_uuid(IdPrefix::Campaign);
   let res = db::campaign::create(
@@ -129,6 +174,12 @@ pub async fn create(
       end_at: p.end_at,
       voucher_policy: p.voucher_policy.unwrap_or(1),
       voucher_expire_at: p.voucher_expire_at,
+      max_participants: p.max_participants,
+      allowed_countries: p.allowed_countries,
+      blocked_countries: p.blocked_countries,
+      eligibility: p.eligibility.unwrap_or_default(),
+      block_fraud: p.block_fraud.unwrap_or(true),
+      budget_warnings: p.budget_warnings,
     },
   );
 
@@ -140,13 +191,29 @@ pub async fn create(
 
 pub async fn update(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
   Path((org_id, campaign_id)): Path<(Uuid, Uuid)>,
-  Json(p): Json<UpdateParams>,
+  Json(mut p): Json<UpdateParams>,
 ) -> Response {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
   if !user.can_edit(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
+  if let Err(msg) = prepare_tasks(p.tasks.as_deref_mut().unwrap_or_default())
+    .and_then(|_| {
+      check_participation(
+        p.max_participants,
+        p.allowed_countries.as_deref(),
+        p.blocked_countries.as_deref(),
+        p.eligibility.as_deref(),
+      )
+    })
+    .and_then(|_| check_budget_warnings(p.budget_warnings.as_deref()))
+  {
+    return (StatusCode::BAD_REQUEST, msg).into_response();
+  }
 
   let res = db::campaign::update(&db, org_id, campaign_id, p);
 
@@ -155,13 +222,29 @@ pub async fn update(
 
 pub async fn replace(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
   Path((org_id, campaign_id)): Path<(Uuid, Uuid)>,
-  Json(p): Json<ReplaceParams>,
+  Json(mut p): Json<ReplaceParams>,
 ) -> Response {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
   if !user.can_edit(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
+  if let Err(msg) = prepare_tasks(&mut p.tasks)
+    .and_then(|_| {
+      check_participation(
+        p.max_participants,
+        p.allowed_countries.as_deref(),
+        p.blocked_countries.as_deref(),
+        Some(&p.eligibility),
+      )
+    })
+    .and_then(|_| check_budget_warnings(p.budget_warnings.as_deref()))
+  {
+    return (StatusCode::BAD_REQUEST, msg).into_response();
+  }
 
   let res = db::campaign::replace(&db, org_id, campaign_id, p);
 
@@ -170,9 +253,12 @@ pub async fn replace(
 
 pub async fn delete(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
   Path((org_id, campaign_id)): Path<(Uuid, Uuid)>,
 ) -> Response {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
   if !user.can_edit(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
@@ -182,3 +268,114 @@ pub async fn delete(
     _ => StatusCode::ACCEPTED.into_response(),
   }
 }
+
+/// Hash quiz answers, then reject task lists with duplicate ids or unusable task configs.
+fn prepare_tasks(tasks: &mut [Task]) -> Result<(), String> {
+  tasks.iter_mut().for_each(|t| t.kind.seal());
+  for (i, task) in tasks.iter().enumerate() {
+    if tasks[..i].iter().any(|t| t.id == task.id) {
+      return Err(format!("duplicate task {}", task.id));
+    }
+    task
+      .kind
+      .validate()
+      .map_err(|msg| format!("task {}: {}", task.id, msg))?;
+    if let Some(id) = task
+      .requires
+      .iter()
+      .flatten()
+      .find(|id| !tasks.iter().any(|t| &&t.id == id))
+    {
+      return Err(format!("task {}: unknown prerequisite {}", task.id, id));
+    }
+    if requires_itself(tasks, task) {
+      return Err(format!("task {}: circular prerequisites", task.id));
+    }
+    if matches!((task.open_at, task.close_at), (Some(o), Some(c)) if c <= o) {
+      return Err(format!(
+        "task {}: close_at should be after open_at",
+        task.id
+      ));
+    }
+    if task.max_completions.is_some_and(|m| m < 1) {
+      return Err(format!(
+        "task {}: max_completions should be at least 1",
+        task.id
+      ));
+    }
+    if task.auto_approve.as_ref().is_some_and(|r| r.is_empty()) {
+      return Err(format!("task {}: auto_approve has no rules", task.id));
+    }
+    for (i, rule) in task.auto_approve.iter().flatten().enumerate() {
+      rule
+        .validate()
+        .map_err(|msg| format!("task {}: auto_approve rule {}: {}", task.id, i, msg))?;
+    }
+  }
+  Ok(())
+}
+
+/// Reject participant caps below one, unusable eligibility rules and unknown country ids.
+fn check_participation(
+  max_participants: Option<i64>,
+  allowed_countries: Option<&[i16]>,
+  blocked_countries: Option<&[i16]>,
+  eligibility: Option<&[Rule]>,
+) -> Result<(), String> {
+  if max_participants.is_some_and(|m| m < 1) {
+    return Err(String::from("max_participants should be at least 1"));
+  }
+  let rules = eligibility.unwrap_or_default();
+  for (i, rule) in rules.iter().enumerate() {
+    rule
+      .validate()
+      .map_err(|msg| format!("eligibility rule {}: {}", i, msg))?;
+  }
+
+  let rule_countries = rules.iter().filter_map(|r| match r {
+    Rule::Country { country_ids } => Some(country_ids.as_slice()),
+    _ => None,
+  });
+  match allowed_countries
+    .into_iter()
+    .chain(blocked_countries)
+    .chain(rule_countries)
+    .flatten()
+    .find(|id| **id < 0 || Country::from_id(**id as u16).is_none())
+  {
+    Some(id) => Err(format!("unknown country {}", id)),
+    None => Ok(()),
+  }
+}
+
+fn check_budget_warnings(thresholds: Option<&[i16]>) -> Result<(), String> {
+  match thresholds
+    .unwrap_or_default()
+    .iter()
+    .find(|t| !(1..=100).contains(*t))
+  {
+    Some(t) => Err(format!(
+      "budget warning {} should be a percentage from 1 to 100",
+      t
+    )),
+    None => Ok(()),
+  }
+}
+
+fn requires_itself(tasks: &[Task], task: &Task) -> bool {
+  let mut seen = Vec::<&str>::new();
+  let mut pending = task.requires.iter().flatten().collect::<Vec<&String>>();
+  while let Some(id) = pending.pop() {
+    if id == &task.id {
+      return true;
+    }
+    if seen.contains(&id.as_str()) {
+      continue;
+    }
+    seen.push(id);
+    if let Some(t) = tasks.iter().find(|t| &t.id == id) {
+      pending.extend(t.requires.iter().flatten());
+    }
+  }
+  false
+}
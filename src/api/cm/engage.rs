@@ -4,16 +4,16 @@ use axum::{
   response::{IntoResponse, Json, Response},
 };
 use axum_extra::extract::Query;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use iso3166::Country;
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, skip_serializing_none};
 use uuid::Uuid;
 
 use crate::{
-  api::{handle_db_error, handle_result, into_json_response},
-  auth::{user::UserService, MyFirebaseUser},
-  db::{self, engage::UpdateCouponSet},
+  api::{handle_db_error, handle_result, into_json_response, refresh_org_claim},
+  auth::{provider::ClaimsStore, MyFirebaseUser, EDITOR_PERMISSION, REVIEWER_PERMISSION},
+  db::{self, engage::UpdateCouponSet, engage_review::Reviewer},
   mezzofy,
 };
 
@@ -22,7 +22,7 @@ pub async fn get(
   user: MyFirebaseUser,
   Path((org_id, campaign_id, chain_id, signer_address)): Path<(Uuid, Uuid, i64, String)>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if !user.can_view(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
 
@@ -52,7 +52,7 @@ pub async fn list(
   Query(p): Query<ListParams>,
   Path(org_id): Path<Uuid>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if !user.can_view(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
 
@@ -83,7 +83,7 @@ pub struct UpdateForm {
 
 pub async fn approve(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
   Path((org_id,  campaign_id, chain_id, signer_address)): Path<(
     Uuid,
     Uuid,
@@ -92,7 +92,10 @@ pub async fn approve(
   )>,
   Json(form): Json<UpdateForm>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.can_review(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
 
@@ -103,16 +106,220 @@ pub async fn approve(
     chain_id,
     signer_address,
     form.accepted,
+    Reviewer::Member(&user.sub),
   );
 
   handle_result(res.await)
 }
 
-pub async fn delete(
+#[derive(Deserialize, Default)]
+pub struct RejectForm {
+  /// Why each task was rejected, shown to the participant.
+  pub reasons: db::engage::Messages,
+}
+
+pub async fn reject(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  Path((org_id, campaign_id, chain_id, signer_address)): Path<(Uuid, Uuid, i64, String)>,
+  Json(form): Json<RejectForm>,
+) -> Response {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.can_review(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let res = db::engage::reject(
+    &db,
+    org_id,
+    campaign_id,
+    chain_id,
+    signer_address,
+    form.reasons,
+    Reviewer::Member(&user.sub),
+  );
+
+  handle_result(res.await)
+}
+
+pub async fn list_pending(
   State(db): State<sqlx::PgPool>,
   user: MyFirebaseUser,
+  Path(org_id): Path<Uuid>,
+  Query(p): Query<db::ListParams<db::engage::PendingFilter>>,
+) -> Response {
+  if !user.can_review(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let res = db::engage::list_pending(&db, org_id, p);
+
+  handle_result(res.await)
+}
+
+#[derive(Deserialize)]
+pub struct ReviewForm {
+  pub reviews: Vec<db::engage::Review>,
+}
+
+pub async fn review(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  Path(org_id): Path<Uuid>,
+  Json(form): Json<ReviewForm>,
+) -> Response {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.can_review(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let res = db::engage::review(&db, org_id, form.reviews, Reviewer::Member(&user.sub));
+
+  handle_result(res.await)
+}
+
+/// How long a claim lasts unless the reviewer asks for another lease.
+const DEFAULT_CLAIM_SECS: i64 = 15 * 60;
+const MAX_CLAIM_SECS: i64 = 2 * 60 * 60;
+
+#[derive(Deserialize, Debug)]
+pub struct ClaimParams {
+  pub lease_secs: Option<i64>,
+}
+
+pub async fn claim(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  Path((org_id, campaign_id, chain_id, signer_address)): Path<(Uuid, Uuid, i64, String)>,
+  Query(p): Query<ClaimParams>,
+) -> Response {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.can_review(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let lease_secs = p.lease_secs.unwrap_or(DEFAULT_CLAIM_SECS);
+  if !(1..=MAX_CLAIM_SECS).contains(&lease_secs) {
+    return (
+      StatusCode::BAD_REQUEST,
+      format!("lease_secs must be between 1 and {}", MAX_CLAIM_SECS),
+    )
+      .into_response();
+  }
+
+  let res = db::engage_review::claim(
+    &db,
+    org_id,
+    campaign_id,
+    chain_id,
+    &signer_address,
+    &user.sub,
+    Utc::now() + Duration::try_seconds(lease_secs).unwrap(),
+  );
+
+  handle_result(res.await)
+}
+
+pub async fn release(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
   Path((org_id, campaign_id, chain_id, signer_address)): Path<(Uuid, Uuid, i64, String)>,
 ) -> Response {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.can_review(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  // Editors may take back a claim from a reviewer who walked away
+  let reviewer = (!user.can_edit(org_id)).then_some(user.sub.as_str());
+  let res = db::engage_review::release(
+    &db,
+    org_id,
+    campaign_id,
+    chain_id,
+    &signer_address,
+    reviewer,
+  );
+
+  handle_result(res.await)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AssignForm {
+  /// Member to review the engagement, None to unassign it.
+  pub assignee: Option<String>,
+}
+
+pub async fn assign(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  Path((org_id, campaign_id, chain_id, signer_address)): Path<(Uuid, Uuid, i64, String)>,
+  Json(form): Json<AssignForm>,
+) -> Result<Response, Response> {
+  refresh_org_claim(&db, &mut user, org_id).await?;
+  if !user.can_edit(org_id) {
+    return Err(StatusCode::FORBIDDEN.into_response());
+  }
+
+  if let Some(assignee) = &form.assignee {
+    let permission = db::org::get_permission(&db, org_id, assignee)
+      .await
+      .map_err(handle_db_error)?
+      .unwrap_or_default();
+    if permission & (REVIEWER_PERMISSION | EDITOR_PERMISSION) == 0 {
+      return Err(
+        (
+          StatusCode::BAD_REQUEST,
+          String::from("assignee can't review for this org"),
+        )
+          .into_response(),
+      );
+    }
+  }
+
+  let res = db::engage_review::assign(
+    &db,
+    org_id,
+    campaign_id,
+    chain_id,
+    &signer_address,
+    form.assignee.as_deref(),
+  );
+
+  Ok(handle_result(res.await))
+}
+
+pub async fn review_stats(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path(org_id): Path<Uuid>,
+  Query(p): Query<db::engage_review::StatsParams>,
+) -> Response {
+  if !user.can_view(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let res = db::engage_review::stats(&db, org_id, p);
+
+  handle_result(res.await)
+}
+
+pub async fn delete(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  Path((org_id, campaign_id, chain_id, signer_address)): Path<(Uuid, Uuid, i64, String)>,
+) -> Response {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
   if !user.can_edit(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
@@ -124,11 +331,14 @@ pub async fn delete(
 
 pub async fn update_coupon(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
   Path((org_id, campaign_id, chain_id, signer_address)): Path<(Uuid, Uuid, i64, String)>,
   Json(form): Json<UpdateCouponSet>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.can_manage_rewards(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
 
@@ -148,12 +358,13 @@ pub struct EngageUpdated {
 
 pub async fn issue_coupon(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
   State(mut mezzofy_client): State<mezzofy::Client>,
-  State(mut user_service): State<UserService>,
+  State(mut user_service): State<ClaimsStore>,
   Path((org_id, campaign_id, chain_id, signer_address)): Path<(Uuid, Uuid, i64, String)>,
 ) -> Result<Response, Response> {
-  if !user.can_edit(org_id) {
+  refresh_org_claim(&db, &mut user, org_id).await?;
+  if !user.can_manage_rewards(org_id) {
     return Err(StatusCode::FORBIDDEN.into_response());
   }
 
@@ -198,26 +409,46 @@ pub async fn issue_coupon(
         match campaign.coupon_code {
           None => Err(failed_dep("Mezzofy coupon code missing")),
           Some(coupon_code) => {
-            let mez_tx = mezzofy_client
-              .issue_coupon(customer, &coupon_code)
-              .await
-              .map_err(|err| (StatusCode::BAD_GATEWAY, err.to_string()).into_response())?;
-
-            let up_res = db::engage::update(
+            db::engage::reserve_coupon(
               &db,
               org_id,
               campaign_id,
               chain_id,
               signer_address,
-              db::engage::UpdateParam {
-                coupon_issue_id: Some(&mez_tx.transaction_id),
-                coupon_serial: None,
-                coupon_url: None,
-                country_id: None,
-              },
+              campaign.reward_amount.unwrap_or_default(),
             )
             .await
             .map_err(handle_db_error)?;
+            let mez_tx = match mezzofy_client.issue_coupon(customer, &coupon_code).await {
+              Ok(mez_tx) => mez_tx,
+              Err(err) => {
+                db::engage::release_coupon(&db, org_id, campaign_id, chain_id, signer_address)
+                  .await
+                  .map_err(handle_db_error)?;
+                return Err((StatusCode::BAD_GATEWAY, err.to_string()).into_response());
+              }
+            };
+
+            // the reservation keeps counting toward the budget if the coupon can't be recorded
+            let up_res = db::engage::record_coupon(
+              &db,
+              org_id,
+              campaign_id,
+              chain_id,
+              signer_address,
+              &mez_tx.transaction_id,
+            )
+            .await
+            .map_err(|err| {
+              tracing::error!(
+                "Error recording coupon {} for {}/{}: {}",
+                mez_tx.transaction_id,
+                chain_id,
+                signer_address,
+                err
+              );
+              handle_db_error(err)
+            })?;
 
             Ok(into_json_response(&EngageUpdated {
               coupon_issue_id: Some(mez_tx.transaction_id),
@@ -238,11 +469,12 @@ fn failed_dep(msg: &str) -> Response {
 
 pub async fn commit_coupon(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
   State(mut mezzofy_client): State<mezzofy::Client>,
   Path((org_id, campaign_id, chain_id, signer_address)): Path<(Uuid, Uuid, i64, String)>,
 ) -> Result<Response, Response> {
-  if !user.can_edit(org_id) {
+  refresh_org_claim(&db, &mut user, org_id).await?;
+  if !user.can_manage_rewards(org_id) {
     return Err(StatusCode::FORBIDDEN.into_response());
   }
 
@@ -309,10 +541,13 @@ pub async fn commit_coupon(
 
 pub async fn delete_coupon_url(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
   Path((org_id, campaign_id, chain_id, signer_address)): Path<(Uuid, Uuid, i64, String)>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.can_manage_rewards(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
 
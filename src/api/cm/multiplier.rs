@@ -0,0 +1,143 @@
+use axum::{
+  extract::{Path, State},
+  http::StatusCode,
+  response::{IntoResponse, Json, Response},
+};
+use axum_extra::extract::Query;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+  api::{handle_db_error, handle_result, into_json_response, refresh_org_claim},
+  auth::MyFirebaseUser,
+  db::{self, new_uuid, point_multiplier::Condition, IdCreateResult, IdPrefix},
+};
+
+/// Largest factor a multiplier can have, to keep typos from flooding a project with points.
+const MAX_FACTOR: i64 = 100;
+
+#[derive(Deserialize, Debug)]
+pub struct ListParams {
+  pub project_id: Option<Uuid>,
+  pub campaign_id: Option<Uuid>,
+}
+
+pub async fn list(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path(org_id): Path<Uuid>,
+  Query(p): Query<ListParams>,
+) -> Response {
+  if !user.can_view(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  let res = db::point_multiplier::list(&db, org_id, p.project_id, p.campaign_id);
+
+  handle_result(res.await)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateForm {
+  pub project_id: Uuid,
+  pub campaign_id: Option<Uuid>,
+  pub name: String,
+  pub factor: Decimal,
+  #[serde(default)]
+  pub conditions: Vec<Condition>,
+  pub starts_at: DateTime<Utc>,
+  pub ends_at: DateTime<Utc>,
+}
+
+pub async fn create(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  Path(org_id): Path<Uuid>,
+  Json(form): Json<CreateForm>,
+) -> Result<Response, Response> {
+  refresh_org_claim(&db, &mut user, org_id).await?;
+  if !user.can_edit(org_id) {
+    return Err(StatusCode::FORBIDDEN.into_response());
+  }
+  if let Err(msg) = check_form(&form) {
+    return Err((StatusCode::BAD_REQUEST, msg).into_response());
+  }
+  db::project::get(&db, org_id, form.project_id)
+    .await
+    .map_err(handle_db_error)?;
+  if let Some(campaign_id) = form.campaign_id {
+    let campaign = db::campaign::get(&db, org_id, campaign_id)
+      .await
+      .map_err(handle_db_error)?;
+    if campaign.project_id != form.project_id {
+      return Err(
+        (
+          StatusCode::BAD_REQUEST,
+          String::from("campaign is not in the project"),
+        )
+          .into_response(),
+      );
+    }
+  }
+
+  let id = new_uuid(IdPrefix::Multiplier);
+  let res = db::point_multiplier::create(
+    &db,
+    db::point_multiplier::CreateParam {
+      id,
+      org_id,
+      project_id: form.project_id,
+      campaign_id: form.campaign_id,
+      name: &form.name,
+      factor: form.factor,
+      conditions: &form.conditions,
+      starts_at: form.starts_at,
+      ends_at: form.ends_at,
+    },
+  )
+  .await
+  .map_err(handle_db_error)?;
+
+  Ok(into_json_response(&IdCreateResult {
+    id,
+    created_at: res.created_at,
+  }))
+}
+
+pub async fn delete(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  Path((org_id, multiplier_id)): Path<(Uuid, Uuid)>,
+) -> Response {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.can_edit(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  match db::point_multiplier::delete(&db, org_id, multiplier_id).await {
+    Err(err) => handle_db_error(err),
+    _ => StatusCode::ACCEPTED.into_response(),
+  }
+}
+
+fn check_form(form: &CreateForm) -> Result<(), String> {
+  if form.factor <= Decimal::ONE || form.factor > Decimal::from(MAX_FACTOR) {
+    return Err(format!(
+      "factor should be above 1 and at most {}",
+      MAX_FACTOR
+    ));
+  }
+  if form.ends_at <= form.starts_at {
+    return Err(String::from("ends_at should be after starts_at"));
+  }
+  for (i, condition) in form.conditions.iter().enumerate() {
+    condition
+      .validate()
+      .map_err(|msg| format!("condition {}: {}", i, msg))?;
+  }
+
+  Ok(())
+}
@@ -11,8 +11,8 @@ use serde::Serialize;
 use uuid::Uuid;
 
 use crate::{
-  api::handle_result,
-  auth::{user::UserService, MyFirebaseUser, OWNER_PERMISSION},
+  api::{handle_result, refresh_org_claim},
+  auth::{provider::ClaimsStore, MyFirebaseUser, OWNER_PERMISSION},
   db::{
     self, new_uuid,
     org::{CreateForm, ReplaceParams, UpdateParam},
@@ -25,7 +25,7 @@ pub async fn get(
   user: MyFirebaseUser,
   Path(org_id): Path<Uuid>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if !user.can_view(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
   let res = db::org::get(&db, org_id);
@@ -53,8 +53,8 @@ pub struct OrgCreated {
 pub async fn create(
   State(db): State<sqlx::PgPool>,
   user: MyFirebaseUser,
-  State(mut claims_service): State<UserService>,
-  Json(mut form): Json<CreateForm>,
+  State(mut claims_service): State<ClaimsStore>,
+  Json(form): Json<CreateForm>,
 ) -> Response {
   let id = new_uuid(IdPrefix::Org);
   let permission = OWNER_PERMISSION;
@@ -66,20 +66,19 @@ pub async fn create(
     .await
   {
     Ok(()) => {
-      form.admins.insert(user.sub, permission);
-
       let res = db::org::create(
         &db,
         CreateParam {
           id,
           form: &form,
         },
+        &user.sub,
       )
       .await;
 
       handle_result(res.map(move |r| OrgCreated {
         id,
-        admins: form.admins,
+        admins: HashMap::from([(user.sub, permission)]),
         created_at: r.created_at,
       }))
     }
@@ -95,16 +94,18 @@ pub async fn create(
 pub struct UpdateForm {
   pub name: Option<String>,
   pub logo: Option<String>,
-  pub admins: Option<HashMap<String, i64>>,
 }
 
 pub async fn update(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
   Path(org_id): Path<Uuid>,
   Json(form): Json<UpdateForm>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.is_owner(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
 
@@ -114,7 +115,6 @@ pub async fn update(
     UpdateParam {
       name: form.name,
       logo: form.logo,
-      admins: form.admins,
     },
   );
 
@@ -123,11 +123,14 @@ pub async fn update(
 
 pub async fn replace(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
   Path(org_id): Path<Uuid>,
   Json(p): Json<ReplaceParams>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.is_owner(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
 
@@ -138,10 +141,13 @@ pub async fn replace(
 
 pub async fn delete(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
   Path(org_id): Path<Uuid>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.is_owner(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
 
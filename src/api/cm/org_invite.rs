@@ -0,0 +1,167 @@
+use axum::{
+  extract::{Path, Query, State},
+  http::StatusCode,
+  response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+  api::{handle_db_error, handle_result, into_json_response, refresh_org_claim},
+  auth::{provider::ClaimsStore, secret, CustomClaims, MyFirebaseUser, Role},
+  db::{self, new_uuid, org_invite::InviteFilter, IdPrefix},
+};
+
+const INVITE_TTL_DAYS: i64 = 7;
+
+pub async fn list(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path(org_id): Path<Uuid>,
+  Query(p): Query<db::ListParams<InviteFilter>>,
+) -> Response {
+  if !user.can_view(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let res = db::org_invite::list(&db, org_id, p);
+
+  handle_result(res.await)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateForm {
+  pub email: String,
+  pub role: Role,
+}
+
+/// The token is only returned here, it's up to the inviter to pass it on to the invitee.
+#[derive(Serialize)]
+pub struct InviteCreated {
+  id: Uuid,
+  token: String,
+  expires_at: DateTime<Utc>,
+  created_at: DateTime<Utc>,
+}
+
+pub async fn create(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  Path(org_id): Path<Uuid>,
+  Json(form): Json<CreateForm>,
+) -> Result<Response, Response> {
+  refresh_org_claim(&db, &mut user, org_id).await?;
+  if !user.is_owner(org_id) {
+    return Err(StatusCode::FORBIDDEN.into_response());
+  }
+  if !form.email.contains('@') {
+    return Err((StatusCode::BAD_REQUEST, String::from("invalid email")).into_response());
+  }
+
+  let id = new_uuid(IdPrefix::OrgInvite);
+  let token = secret::generate();
+  let expires_at = Utc::now() + Duration::try_days(INVITE_TTL_DAYS).unwrap();
+  let res = db::org_invite::create(
+    &db,
+    db::org_invite::CreateParam {
+      id,
+      org_id,
+      email: &form.email,
+      permission: form.role.permission(),
+      token_hash: &secret::hash(&token),
+      invited_by: &user.sub,
+      expires_at,
+    },
+  )
+  .await
+  .map_err(handle_db_error)?;
+
+  Ok(into_json_response(&InviteCreated {
+    id,
+    token,
+    expires_at,
+    created_at: res.created_at,
+  }))
+}
+
+pub async fn delete(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  Path((org_id, invite_id)): Path<(Uuid, Uuid)>,
+) -> Response {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.is_owner(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  match db::org_invite::delete(&db, org_id, invite_id).await {
+    Err(err) => handle_db_error(err),
+    _ => StatusCode::ACCEPTED.into_response(),
+  }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RespondForm {
+  pub token: String,
+}
+
+pub async fn accept(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  State(mut claims_service): State<ClaimsStore>,
+  Path((org_id, invite_id)): Path<(Uuid, Uuid)>,
+  Json(form): Json<RespondForm>,
+) -> Result<Response, Response> {
+  let email = match (&user.email, user.email_verified) {
+    (Some(email), Some(true)) => email,
+    _ => return Err(StatusCode::FORBIDDEN.into_response()),
+  };
+
+  let permission = db::org_invite::accept(
+    &db,
+    org_id,
+    invite_id,
+    &secret::hash(&form.token),
+    email,
+    &user.sub,
+  )
+  .await
+  .map_err(handle_db_error)?;
+
+  let mut orgs = user.claims.orgs;
+  orgs.insert(org_id.to_string(), permission);
+  match claims_service
+    .set_custom_attributes(
+      &user.sub,
+      CustomClaims {
+        admin: user.claims.admin,
+        orgs,
+        wallets: user.claims.wallets,
+      },
+    )
+    .await
+  {
+    Ok(()) => Ok(StatusCode::OK.into_response()),
+    Err(err) => Err((StatusCode::BAD_GATEWAY, err.to_string()).into_response()),
+  }
+}
+
+pub async fn decline(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path((org_id, invite_id)): Path<(Uuid, Uuid)>,
+  Json(form): Json<RespondForm>,
+) -> Response {
+  let email = match (&user.email, user.email_verified) {
+    (Some(email), Some(true)) => email,
+    _ => return StatusCode::FORBIDDEN.into_response(),
+  };
+
+  let token_hash = secret::hash(&form.token);
+  let res = db::org_invite::decline(&db, org_id, invite_id, &token_hash, email);
+
+  handle_result(res.await)
+}
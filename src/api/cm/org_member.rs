@@ -0,0 +1,125 @@
+use axum::{
+  extract::{Path, State},
+  http::StatusCode,
+  response::{IntoResponse, Json, Response},
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+  api::{handle_db_error, into_json_response, refresh_org_claim},
+  auth::{provider::ClaimsStore, MyFirebaseUser, Role},
+  db,
+};
+
+#[derive(Serialize)]
+pub struct Member {
+  pub user_id: String,
+  pub permission: i64,
+}
+
+pub async fn list(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path(org_id): Path<Uuid>,
+) -> Result<Response, Response> {
+  if !user.can_view(org_id) {
+    return Err(StatusCode::FORBIDDEN.into_response());
+  }
+
+  let org = db::org::get(&db, org_id).await.map_err(handle_db_error)?;
+
+  let members = org
+    .admins
+    .0
+    .into_iter()
+    .map(|(user_id, permission)| Member {
+      user_id,
+      permission,
+    })
+    .collect::<Vec<Member>>();
+
+  Ok(into_json_response(&members))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateForm {
+  pub role: Role,
+}
+
+pub async fn update(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  State(mut claims_service): State<ClaimsStore>,
+  Path((org_id, user_id)): Path<(Uuid, String)>,
+  Json(form): Json<UpdateForm>,
+) -> Result<Response, Response> {
+  refresh_org_claim(&db, &mut user, org_id).await?;
+  if !user.is_owner(org_id) {
+    return Err(StatusCode::FORBIDDEN.into_response());
+  }
+
+  let permission = form.role.permission();
+  let res = db::org::set_admin(&db, org_id, &user_id, permission)
+    .await
+    .map_err(handle_db_error)?;
+
+  revoke_tokens(&db, &user_id).await?;
+  sync_org_claim(&mut claims_service, &user_id, org_id, Some(permission)).await?;
+
+  Ok(into_json_response(&res))
+}
+
+pub async fn delete(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  State(mut claims_service): State<ClaimsStore>,
+  Path((org_id, user_id)): Path<(Uuid, String)>,
+) -> Result<Response, Response> {
+  refresh_org_claim(&db, &mut user, org_id).await?;
+  // Members may leave on their own, only owners can remove others
+  if !user.is_owner(org_id) && user.sub != user_id {
+    return Err(StatusCode::FORBIDDEN.into_response());
+  }
+
+  db::org::remove_admin(&db, org_id, &user_id)
+    .await
+    .map_err(handle_db_error)?;
+
+  revoke_tokens(&db, &user_id).await?;
+  sync_org_claim(&mut claims_service, &user_id, org_id, None).await?;
+
+  Ok(StatusCode::ACCEPTED.into_response())
+}
+
+/// Update the org permission in a user's custom claims, removing it when `permission` is None.
+pub async fn sync_org_claim(
+  claims_service: &mut ClaimsStore,
+  user_id: &str,
+  org_id: Uuid,
+  permission: Option<i64>,
+) -> Result<(), Response> {
+  let u = claims_service
+    .lookup(user_id)
+    .await
+    .map_err(|err| (StatusCode::BAD_GATEWAY, err.to_string()).into_response())?;
+
+  let mut claims = u.customAttributes;
+  match permission {
+    Some(p) => claims.orgs.insert(org_id.to_string(), p),
+    None => claims.orgs.remove(&org_id.to_string()),
+  };
+
+  claims_service
+    .set_custom_attributes(user_id, claims)
+    .await
+    .map_err(|err| (StatusCode::BAD_GATEWAY, err.to_string()).into_response())
+}
+
+/// Revoke the tokens issued to a user so they have to pick up their updated claims.
+pub async fn revoke_tokens(db: &sqlx::PgPool, user_id: &str) -> Result<(), Response> {
+  db::auth_revocation::revoke(db, user_id)
+    .await
+    .map(|_| ())
+    .map_err(handle_db_error)
+}
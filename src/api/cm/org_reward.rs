@@ -0,0 +1,94 @@
+use axum::{
+  extract::{Path, Query, State},
+  http::StatusCode,
+  response::{IntoResponse, Response},
+  Json,
+};
+use uuid::Uuid;
+
+use crate::{
+  api::{handle_db_error, handle_result, refresh_org_claim},
+  auth::MyFirebaseUser,
+  db::{self, org_reward::OrgRewardFilter},
+};
+
+pub async fn get(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path((org_id, reward_id)): Path<(Uuid, Uuid)>,
+) -> Response {
+  if !user.can_view(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let res = db::org_reward::get(&db, org_id, reward_id);
+
+  handle_result(res.await)
+}
+
+pub async fn list(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path(org_id): Path<Uuid>,
+  Query(p): Query<db::ListParams<OrgRewardFilter>>,
+) -> Response {
+  if !user.can_view(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  let res = db::org_reward::list(&db, org_id, p);
+
+  handle_result(res.await)
+}
+
+pub async fn create(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  Path(org_id): Path<Uuid>,
+  Json(p): Json<db::org_reward::CreateParam>,
+) -> Response {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.can_manage_rewards(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let res = db::org_reward::create(&db, org_id, p);
+  handle_result(res.await)
+}
+
+pub async fn update(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  Path((org_id, reward_id)): Path<(Uuid, Uuid)>,
+  Json(p): Json<db::org_reward::UpdateParam>,
+) -> Response {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.can_manage_rewards(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let res = db::org_reward::update(&db, org_id, reward_id, p);
+
+  handle_result(res.await)
+}
+
+pub async fn unlink(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  Path((org_id, reward_id)): Path<(Uuid, Uuid)>,
+) -> Response {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.can_manage_rewards(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  match db::org_reward::unlink(&db, org_id, reward_id).await {
+    Err(err) => handle_db_error(err),
+    _ => StatusCode::OK.into_response(),
+  }
+}
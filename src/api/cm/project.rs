@@ -22,7 +22,7 @@ pub async fn get(
   user: MyFirebaseUser,
   Path((org_id, project_id)): Path<(Uuid, Uuid)>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if !user.can_view(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
   let res = db::project::get(&db, org_id, project_id);
@@ -42,7 +42,7 @@ pub async fn list(
   Query(p): Query<db::ListParams<ListFilter>>,
   Path(org_id): Path<Uuid>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if !user.can_view(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
   let res = db::project::list(
@@ -123,6 +123,8 @@ pub async fn update(
       networks: p.networks,
       feature_from: p.feature_from,
       feature_until: p.feature_until,
+      point_transfer: p.point_transfer,
+      point_transfer_daily_limit: p.point_transfer_daily_limit,
     }
   } else {
     UpdateParams {
@@ -134,6 +136,8 @@ pub async fn update(
       networks: p.networks,
       feature_from: None,
       feature_until: None,
+      point_transfer: p.point_transfer,
+      point_transfer_daily_limit: p.point_transfer_daily_limit,
     }
   };
 
@@ -167,6 +171,8 @@ pub async fn replace(
       networks: p.networks,
       feature_from: p.feature_from,
       feature_until: p.feature_until,
+      point_transfer: p.point_transfer,
+      point_transfer_daily_limit: p.point_transfer_daily_limit,
     }
   } else {
     ReplaceParams {
@@ -178,6 +184,8 @@ pub async fn replace(
       networks: p.networks,
       feature_from: None,
       feature_until: None,
+      point_transfer: p.point_transfer,
+      point_transfer_daily_limit: p.point_transfer_daily_limit,
     }
   };
 
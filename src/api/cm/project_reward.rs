@@ -7,7 +7,7 @@ use axum::{
 use uuid::Uuid;
 
 use crate::{
-  api::{handle_db_error, handle_result},
+  api::{handle_db_error, handle_result, refresh_org_claim},
   auth::MyFirebaseUser,
   db::{self, project_reward::ProjectRewardFilter},
 };
@@ -17,7 +17,7 @@ pub async fn get(
   user: MyFirebaseUser,
   Path((org_id, project_id, reward_id)): Path<(Uuid, Uuid, Uuid)>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if !user.can_view(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
 
@@ -32,7 +32,7 @@ pub async fn list(
   Path((org_id, project_id)): Path<(Uuid, Uuid)>,
   Query(p): Query<db::ListParams<ProjectRewardFilter>>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if !user.can_view(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
   let res = db::project_reward::list(&db, org_id, project_id, p);
@@ -42,11 +42,14 @@ pub async fn list(
 
 pub async fn create(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
   Path((org_id, project_id)): Path<(Uuid, Uuid)>,
   Json(p): Json<db::project_reward::CreateParam>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.can_manage_rewards(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
 
@@ -56,11 +59,14 @@ pub async fn create(
 
 pub async fn update(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
   Path((org_id, project_id, reward_id)): Path<(Uuid, Uuid, Uuid)>,
   Json(p): Json<db::project_reward::UpdateParam>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.can_manage_rewards(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
 
@@ -83,10 +89,13 @@ pub async fn update(
 
 pub async fn unlink(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
   Path((org_id, project_id, reward_id)): Path<(Uuid, Uuid, Uuid)>,
 ) -> Response {
-  if !user.can_edit(org_id) {
+  if let Err(res) = refresh_org_claim(&db, &mut user, org_id).await {
+    return res;
+  }
+  if !user.can_manage_rewards(org_id) {
     return StatusCode::FORBIDDEN.into_response();
   }
 
@@ -0,0 +1,139 @@
+use axum::{
+  extract::{Path, State},
+  http::StatusCode,
+  response::{IntoResponse, Json, Response},
+};
+use axum_extra::extract::Query;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+  api::{handle_db_error, handle_result, into_json_response, refresh_org_claim},
+  auth::{secret, MyFirebaseUser},
+  db::{self, new_uuid, IdPrefix},
+};
+
+#[derive(Deserialize, Debug)]
+pub struct ListParams {
+  pub project_id: Option<Uuid>,
+}
+
+pub async fn list(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path(org_id): Path<Uuid>,
+  Query(p): Query<ListParams>,
+) -> Response {
+  if !user.can_view(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  let res = db::raffle::list(&db, Some(org_id), p.project_id);
+
+  handle_result(res.await)
+}
+
+pub async fn get(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path((org_id, raffle_id)): Path<(Uuid, Uuid)>,
+) -> Response {
+  if !user.can_view(org_id) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  let res = db::raffle::get(&db, Some(org_id), None, raffle_id);
+
+  handle_result(res.await)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateForm {
+  pub project_id: Uuid,
+  pub reward_id: Uuid,
+  pub name: String,
+  pub ticket_point: i64,
+  pub user_tickets: Option<i64>,
+  pub winners: i64,
+  #[serde(default)]
+  pub refund: bool,
+  pub draw_at: DateTime<Utc>,
+}
+
+/// The seed stays secret until the draw, `seed_hash` is what to publish to make it auditable.
+#[derive(Serialize)]
+pub struct RaffleCreated {
+  id: Uuid,
+  seed_hash: String,
+  created_at: DateTime<Utc>,
+}
+
+pub async fn create(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  Path(org_id): Path<Uuid>,
+  Json(form): Json<CreateForm>,
+) -> Result<Response, Response> {
+  refresh_org_claim(&db, &mut user, org_id).await?;
+  if !user.can_manage_rewards(org_id) {
+    return Err(StatusCode::FORBIDDEN.into_response());
+  }
+  if let Err(msg) = check_form(&form) {
+    return Err((StatusCode::BAD_REQUEST, msg).into_response());
+  }
+  db::project::get(&db, org_id, form.project_id)
+    .await
+    .map_err(handle_db_error)?;
+
+  let id = new_uuid(IdPrefix::Raffle);
+  let seed = secret::generate();
+  let seed_hash = secret::hash(&seed);
+  let res = db::raffle::create(
+    &db,
+    db::raffle::CreateParam {
+      id,
+      org_id,
+      project_id: form.project_id,
+      reward_id: form.reward_id,
+      name: &form.name,
+      ticket_point: form.ticket_point,
+      user_tickets: form.user_tickets,
+      winners: form.winners,
+      refund: form.refund,
+      seed: &seed,
+      seed_hash: &seed_hash,
+      draw_at: form.draw_at,
+    },
+  )
+  .await
+  .map_err(|err| match err {
+    db::Error::LimitReached => (
+      StatusCode::CONFLICT,
+      String::from("Not enough coupons left for the winners"),
+    )
+      .into_response(),
+    err => handle_db_error(err),
+  })?;
+
+  Ok(into_json_response(&RaffleCreated {
+    id,
+    seed_hash,
+    created_at: res.created_at,
+  }))
+}
+
+fn check_form(form: &CreateForm) -> Result<(), String> {
+  if form.ticket_point <= 0 {
+    return Err(String::from("ticket_point should be positive"));
+  }
+  if form.winners <= 0 {
+    return Err(String::from("winners should be positive"));
+  }
+  if form.user_tickets.is_some_and(|n| n <= 0) {
+    return Err(String::from("user_tickets should be positive"));
+  }
+  if form.draw_at <= Utc::now() {
+    return Err(String::from("draw_at should be in the future"));
+  }
+
+  Ok(())
+}
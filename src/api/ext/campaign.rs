@@ -0,0 +1,54 @@
+use axum::{
+  extract::{Path, Query, State},
+  http::StatusCode,
+  response::{IntoResponse, Response},
+};
+use uuid::Uuid;
+
+use crate::{
+  api::{cm::campaign::ListFilter, handle_result},
+  auth::api_key::{ApiScope, OrgApiKey},
+  db::{self, campaign::CampaignFilter},
+};
+
+pub async fn get(
+  State(db): State<sqlx::PgPool>,
+  key: OrgApiKey,
+  Path((org_id, campaign_id)): Path<(Uuid, Uuid)>,
+) -> Response {
+  if !key.can(org_id, ApiScope::CampaignRead) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  let res = db::campaign::get(&db, org_id, campaign_id);
+
+  handle_result(res.await)
+}
+
+pub async fn list(
+  State(db): State<sqlx::PgPool>,
+  key: OrgApiKey,
+  Query(p): Query<db::ListParams<ListFilter>>,
+  Path(org_id): Path<Uuid>,
+) -> Response {
+  if !key.can(org_id, ApiScope::CampaignRead) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  let res = db::campaign::list(
+    &db,
+    db::ListParams::<CampaignFilter> {
+      filter: CampaignFilter {
+        org_id: Some(org_id),
+        project_id: p.filter.project_id,
+        chain_id: p.filter.chain_id,
+        contracts: p.filter.contracts,
+        created_after: p.filter.created_after,
+        created_before: p.filter.created_before,
+      },
+      order: p.order,
+      offset: p.offset,
+      limit: p.limit,
+    },
+  );
+
+  handle_result(res.await)
+}
@@ -0,0 +1,120 @@
+use axum::{
+  extract::{Path, State},
+  http::StatusCode,
+  response::{IntoResponse, Json, Response},
+};
+use axum_extra::extract::Query;
+use uuid::Uuid;
+
+use crate::{
+  api::{
+    cm::engage::{ListParams, RejectForm, UpdateForm},
+    handle_result,
+  },
+  auth::api_key::{ApiScope, OrgApiKey},
+  db::{self, engage_review::Reviewer},
+};
+
+pub async fn get(
+  State(db): State<sqlx::PgPool>,
+  key: OrgApiKey,
+  Path((org_id, campaign_id, chain_id, signer_address)): Path<(Uuid, Uuid, i64, String)>,
+) -> Response {
+  if !key.can(org_id, ApiScope::EngageRead) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let res = db::engage::get(&db, org_id, campaign_id, chain_id, &signer_address);
+
+  handle_result(res.await)
+}
+
+pub async fn list(
+  State(db): State<sqlx::PgPool>,
+  key: OrgApiKey,
+  Query(p): Query<ListParams>,
+  Path(org_id): Path<Uuid>,
+) -> Response {
+  if !key.can(org_id, ApiScope::EngageRead) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let res = db::engage::list(
+    &db,
+    db::engage::ListParams {
+      org_id,
+      project_id: p.project_id,
+      campaign_id: p.campaign_id,
+      chain_id: p.chain_id,
+      signer_address: p.signer_address,
+      user_id: p.user_id,
+      created_after: p.created_after,
+      created_before: p.created_before,
+      order: p.order,
+      offset: p.offset.unwrap_or_default(),
+      limit: p.limit.unwrap_or(20u64),
+    },
+  );
+
+  handle_result(res.await)
+}
+
+pub async fn approve(
+  State(db): State<sqlx::PgPool>,
+  key: OrgApiKey,
+  Path((org_id, campaign_id, chain_id, signer_address)): Path<(Uuid, Uuid, i64, String)>,
+  Json(form): Json<UpdateForm>,
+) -> Response {
+  if !key.can(org_id, ApiScope::EngageApprove) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  tracing::info!(
+    "API key {} approving {}/{}/{}",
+    key.id,
+    campaign_id,
+    chain_id,
+    signer_address
+  );
+
+  let res = db::engage::approve(
+    &db,
+    org_id,
+    campaign_id,
+    chain_id,
+    signer_address,
+    form.accepted,
+    Reviewer::ApiKey,
+  );
+
+  handle_result(res.await)
+}
+
+pub async fn reject(
+  State(db): State<sqlx::PgPool>,
+  key: OrgApiKey,
+  Path((org_id, campaign_id, chain_id, signer_address)): Path<(Uuid, Uuid, i64, String)>,
+  Json(form): Json<RejectForm>,
+) -> Response {
+  if !key.can(org_id, ApiScope::EngageApprove) {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  tracing::info!(
+    "API key {} rejecting {}/{}/{}",
+    key.id,
+    campaign_id,
+    chain_id,
+    signer_address
+  );
+
+  let res = db::engage::reject(
+    &db,
+    org_id,
+    campaign_id,
+    chain_id,
+    signer_address,
+    form.reasons,
+    Reviewer::ApiKey,
+  );
+
+  handle_result(res.await)
+}
@@ -4,6 +4,9 @@ pub mod campaign_reward;
 pub mod engage;
 pub mod project;
 pub mod project_reward;
+pub mod raffle;
+pub mod org_reward;
+pub mod point_transfer;
 pub mod auth;
 pub mod club;
 pub mod country;
@@ -11,4 +14,5 @@ pub mod nft;
 pub mod reward;
 pub mod voucher;
 pub mod coupon;
+pub mod coupon_transfer;
 pub mod me;
@@ -8,11 +8,11 @@ use axum::{
 };
 use ethers::types::Address;
 
-use crate::auth::{user::UserService, CustomClaims, MyFirebaseUser};
+use crate::auth::{provider::ClaimsStore, CustomClaims, MyFirebaseUser};
 
 pub async fn fix_claims(
   user: MyFirebaseUser,
-  State(mut claims_service): State<UserService>,
+  State(mut claims_service): State<ClaimsStore>,
 ) -> Result<StatusCode, Response> {
   let mut my_wallets = HashMap::<String, bool>::new();
   user.claims.wallets.keys().for_each(|k| {
@@ -51,7 +51,7 @@ pub struct ConnectParams {
 
 pub async fn link(
   user: MyFirebaseUser,
-  State(mut claims_service): State<UserService>,
+  State(mut claims_service): State<ClaimsStore>,
   Json(param): Json<ConnectParams>,
 ) -> Result<StatusCode, Response> {
   let signer_address = param.signer_address.to_lowercase();
@@ -0,0 +1,119 @@
+use axum::{
+  extract::{Path, State},
+  http::StatusCode,
+  response::{IntoResponse, Response},
+  Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+  api::{handle_db_error, handle_result, into_json_response},
+  auth::{secret, MyFirebaseUser},
+  db::{self, new_uuid, IdPrefix},
+};
+
+const CLAIM_LINK_TTL_DAYS: i64 = 7;
+
+pub async fn list(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path((reward_id, number)): Path<(Uuid, i64)>,
+) -> Response {
+  let res = db::coupon_transfer::list(&db, reward_id, number, &user.sub);
+
+  handle_result(res.await)
+}
+
+/// Without a `user_id` a claim link is created instead, for the sender to pass on.
+#[derive(Deserialize, Debug)]
+pub struct CreateForm {
+  pub user_id: Option<String>,
+}
+
+/// The token is only returned here, it's up to the sender to pass it on with the link.
+#[derive(Serialize)]
+pub struct LinkCreated {
+  id: Uuid,
+  token: String,
+  expires_at: DateTime<Utc>,
+  created_at: DateTime<Utc>,
+}
+
+fn handle_transfer_error(err: db::Error) -> Response {
+  match err {
+    db::Error::Validation => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    _ => handle_db_error(err),
+  }
+}
+
+/// Hand a coupon over, directly or through a claim link.
+pub async fn create(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path((reward_id, number)): Path<(Uuid, i64)>,
+  Json(form): Json<CreateForm>,
+) -> Result<Response, Response> {
+  let id = new_uuid(IdPrefix::CouponTransfer);
+  if let Some(recipient_id) = form.user_id {
+    let res = db::coupon_transfer::give(&db, id, reward_id, number, &user.sub, &recipient_id)
+      .await
+      .map_err(handle_transfer_error)?;
+    return Ok(into_json_response(&res));
+  }
+
+  let token = secret::generate();
+  let expires_at = Utc::now() + Duration::try_days(CLAIM_LINK_TTL_DAYS).unwrap();
+  let res = db::coupon_transfer::create_link(
+    &db,
+    db::coupon_transfer::CreateParam {
+      id,
+      reward_id,
+      number,
+      sender_id: &user.sub,
+      token_hash: &secret::hash(&token),
+      expires_at,
+    },
+  )
+  .await
+  .map_err(handle_transfer_error)?;
+
+  Ok(into_json_response(&LinkCreated {
+    id,
+    token,
+    expires_at,
+    created_at: res.created_at,
+  }))
+}
+
+pub async fn cancel(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path((reward_id, number, transfer_id)): Path<(Uuid, i64, Uuid)>,
+) -> Response {
+  let res = db::coupon_transfer::cancel(&db, reward_id, number, transfer_id, &user.sub);
+
+  handle_result(res.await)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ClaimForm {
+  pub token: String,
+}
+
+/// Claim a coupon through its link.
+pub async fn claim(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path(transfer_id): Path<Uuid>,
+  Json(form): Json<ClaimForm>,
+) -> Response {
+  let token_hash = secret::hash(&form.token);
+  let res = db::coupon_transfer::claim(&db, transfer_id, &token_hash, &user.sub);
+
+  match res.await {
+    Ok(data) => into_json_response(&data),
+    Err(err) => handle_transfer_error(err),
+  }
+}
@@ -9,7 +9,7 @@ use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use serde_repr::Serialize_repr;
 use sqlx::postgres::PgListener;
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 use thiserror::Error;
 use tokio::sync::broadcast::Sender;
 use tokio_stream::wrappers::BroadcastStream;
@@ -18,7 +18,17 @@ use uuid::Uuid;
 use crate::{
   api::{handle_db_error, handle_result, into_json_response},
   auth::MyFirebaseUser,
-  db::{self, engage::Submissions, engage_event::EngageEventLog, Never},
+  db::{
+    self,
+    campaign::{CampaignLock, RuleCheck, TaskLock},
+    campaign_pub::CampaignTasks,
+    engage::{Accepted, Submissions},
+    engage_event::EngageEventLog,
+    engage_pub::PubEngage,
+    engage_review::Reviewer,
+    Never,
+  },
+  eligibility, evm, subscan, verify,
 };
 
 #[derive(Error, Debug)]
@@ -31,14 +41,18 @@ pub async fn get(
   State(db): State<sqlx::PgPool>,
   user: MyFirebaseUser,
   Path((chain_id, signer_address, campaign_id)): Path<(i64, String, Uuid)>,
-) -> Response {
+) -> Result<Response, Response> {
   let signer_address = &signer_address.to_lowercase();
   if !user.has_wallet_claim(chain_id, signer_address) {
-    return StatusCode::FORBIDDEN.into_response();
+    return Err(StatusCode::FORBIDDEN.into_response());
   }
-  let res = db::engage_pub::get(&db, campaign_id, chain_id, signer_address);
+  let engage = db::engage_pub::get(&db, campaign_id, chain_id, signer_address)
+    .await
+    .map_err(handle_db_error)?;
 
-  handle_result(res.await)
+  let res = with_locks(&db, engage).await.map_err(handle_db_error)?;
+
+  Ok(into_json_response(&res))
 }
 
 pub async fn get_tasks(
@@ -46,15 +60,53 @@ pub async fn get_tasks(
   user: MyFirebaseUser,
   Query(p): Query<db::ListParams<db::engage_pub::PubListFilter>>,
   Path((chain_id, signer_address)): Path<(i64, String)>,
-) -> Response {
+) -> Result<Response, Response> {
   let signer_address = &signer_address.to_lowercase();
   if !user.has_wallet_claim(chain_id, signer_address) {
-    return StatusCode::FORBIDDEN.into_response();
+    return Err(StatusCode::FORBIDDEN.into_response());
   }
 
-  let res = db::engage_pub::list(&db, chain_id, signer_address, p);
+  let engages = db::engage_pub::list(&db, chain_id, signer_address, p)
+    .await
+    .map_err(handle_db_error)?;
 
-  handle_result(res.await)
+  let mut res = Vec::<EngageTasks>::new();
+  for engage in engages {
+    res.push(with_locks(&db, engage).await.map_err(handle_db_error)?);
+  }
+
+  Ok(into_json_response(&res))
+}
+
+#[derive(Serialize)]
+pub struct EngageTasks {
+  #[serde(flatten)]
+  engage: PubEngage,
+  /// Campaign tasks the participant can't submit right now.
+  locked: HashMap<String, TaskLock>,
+}
+
+async fn with_locks(db: &sqlx::PgPool, engage: PubEngage) -> Result<EngageTasks, db::Error> {
+  let campaign = db::campaign_pub::get_tasks(db, engage.campaign_id).await?;
+  let completions = if campaign.tasks.iter().any(|t| t.max_completions.is_some()) {
+    db::engage_pub::count_completions(db, engage.campaign_id).await?
+  } else {
+    HashMap::new()
+  };
+
+  let locked = campaign
+    .tasks
+    .iter()
+    .filter_map(|t| {
+      t.lock(
+        &engage.accepted,
+        completions.get(&t.id).copied().unwrap_or_default(),
+      )
+      .map(|lock| (t.id.to_owned(), lock))
+    })
+    .collect();
+
+  Ok(EngageTasks { engage, locked })
 }
 
 #[derive(Serialize, Debug)]
@@ -129,20 +181,69 @@ pub struct Created {
   created_at: DateTime<Utc>,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct CreateEngageParams {
+  /// Required to join campaigns limited to some countries.
+  pub country_id: Option<u16>,
+}
+
 pub async fn create(
   State(db): State<sqlx::PgPool>,
   user: MyFirebaseUser,
+  State(subscan_client): State<subscan::Client>,
+  State(evm_client): State<evm::Client>,
   Path((chain_id, signer_address, campaign_id)): Path<(i64, String, Uuid)>,
-  Json(submissions): Json<Submissions>,
+  Query(p): Query<CreateEngageParams>,
+  Json(mut submissions): Json<Submissions>,
 ) -> Result<Response, Response> {
   let signer_address = &signer_address.to_lowercase();
   if !user.has_wallet_claim(chain_id, signer_address) {
     Err(StatusCode::FORBIDDEN.into_response())?;
   }
-  let campaign = db::campaign_pub::get_relation_ids(&db, campaign_id)
+  let campaign = db::campaign_pub::get_tasks(&db, campaign_id)
     .await
     .map_err(handle_db_error)?;
 
+  let country_id = p.country_id.map(|id| id as i16);
+  let (lock, _) = join_lock(
+    &eligibility::Context {
+      db: &db,
+      subscan_client: &subscan_client,
+      chain_id,
+      contract_address: &campaign.contract_address,
+      signer_address: Some(signer_address),
+      user_id: &user.sub,
+      country_id,
+    },
+    campaign_id,
+    &campaign,
+  )
+  .await
+  .map_err(handle_db_error)?;
+  if let Some(lock) = lock {
+    return Err(refuse(lock));
+  }
+
+  let ctx = verify::Context {
+    db: &db,
+    subscan_client: &subscan_client,
+    evm_client: &evm_client,
+    project_id: campaign.project_id,
+    campaign_id,
+    chain_id,
+    contract_address: &campaign.contract_address,
+    start_at: campaign.start_at,
+    signer_address,
+    user_id: &user.sub,
+    block_fraud: campaign.block_fraud,
+  };
+  let verified = verify::verify(&ctx, &campaign.tasks, &mut submissions)
+    .await
+    .map_err(handle_verify_error)?;
+  let matched = verify::match_rules(&ctx, &campaign.tasks, &submissions)
+    .await
+    .map_err(handle_verify_error)?;
+
   let res = db::engage::create(
     &db,
     db::engage::CreateParam {
@@ -154,14 +255,142 @@ pub async fn create(
       submissions,
       user_id: &user.sub,
       user_name: user.name,
+      country_id,
+      max_participants: campaign.max_participants,
     },
   )
   .await
-  .map_err(handle_db_error)?;
+  .map_err(|err| match (err, campaign.max_participants) {
+    (db::Error::LimitReached, Some(max_participants)) => {
+      refuse(CampaignLock::Full { max_participants })
+    }
+    (err, _) => handle_db_error(err),
+  })?;
+
+  auto_approve(
+    &db,
+    campaign.org_id,
+    campaign_id,
+    chain_id,
+    signer_address,
+    verified,
+    Reviewer::Verified,
+  )
+  .await?;
+  auto_approve(
+    &db,
+    campaign.org_id,
+    campaign_id,
+    chain_id,
+    signer_address,
+    matched,
+    Reviewer::Rule,
+  )
+  .await?;
 
   Ok(into_json_response(&res))
 }
 
+#[derive(Deserialize, Debug)]
+pub struct EligibilityParams {
+  /// Defaults to the campaign chain.
+  pub chain_id: Option<i64>,
+  /// Needed to check NFT holdings.
+  pub signer_address: Option<String>,
+  pub country_id: Option<u16>,
+}
+
+#[derive(Serialize)]
+pub struct Eligibility {
+  eligible: bool,
+  lock: Option<CampaignLock>,
+  rules: Vec<RuleCheck>,
+}
+
+/// Check whether the user could join a campaign, without joining it.
+pub async fn eligibility(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  State(subscan_client): State<subscan::Client>,
+  Path(campaign_id): Path<Uuid>,
+  Query(p): Query<EligibilityParams>,
+) -> Result<Response, Response> {
+  let campaign = db::campaign_pub::get_tasks(&db, campaign_id)
+    .await
+    .map_err(handle_db_error)?;
+  let chain_id = p.chain_id.unwrap_or(campaign.chain_id);
+  let signer_address = p.signer_address.map(|s| s.to_lowercase());
+  if let Some(signer_address) = &signer_address {
+    if !user.has_wallet_claim(chain_id, signer_address) {
+      return Err(StatusCode::FORBIDDEN.into_response());
+    }
+  }
+
+  let (lock, rules) = join_lock(
+    &eligibility::Context {
+      db: &db,
+      subscan_client: &subscan_client,
+      chain_id,
+      contract_address: &campaign.contract_address,
+      signer_address: signer_address.as_deref(),
+      user_id: &user.sub,
+      country_id: p.country_id.map(|id| id as i16),
+    },
+    campaign_id,
+    &campaign,
+  )
+  .await
+  .map_err(handle_db_error)?;
+
+  Ok(into_json_response(&Eligibility {
+    eligible: lock.is_none(),
+    lock,
+    rules,
+  }))
+}
+
+/// Find why a participant can't join a campaign, if they can't, along with the outcome of
+/// each of its eligibility rules.
+async fn join_lock(
+  ctx: &eligibility::Context<'_>,
+  campaign_id: Uuid,
+  campaign: &CampaignTasks,
+) -> Result<(Option<CampaignLock>, Vec<RuleCheck>), db::Error> {
+  let mut lock = campaign.lock(ctx.country_id);
+  if let (None, Some(max_participants)) = (&lock, campaign.max_participants) {
+    if db::engage_pub::count_participants(ctx.db, campaign_id).await? >= max_participants {
+      lock = Some(CampaignLock::Full { max_participants });
+    }
+  }
+  if let (None, true, Some(signer_address)) = (&lock, campaign.block_fraud, ctx.signer_address) {
+    if let Some(other) = db::engage_pub::find_other_wallet(
+      ctx.db,
+      campaign_id,
+      ctx.user_id,
+      ctx.chain_id,
+      signer_address,
+    )
+    .await?
+    {
+      lock = Some(CampaignLock::OtherWallet {
+        signer_address: other,
+      });
+    }
+  }
+
+  let rules = eligibility::check(ctx, &campaign.eligibility).await?;
+  let failed = rules
+    .iter()
+    .filter(|r| !r.passed)
+    .cloned()
+    .collect::<Vec<RuleCheck>>();
+  if lock.is_none() && !failed.is_empty() {
+    lock = Some(CampaignLock::NotEligible { rules: failed });
+  }
+
+  Ok((lock, rules))
+}
+
 #[derive(Deserialize, Debug)]
 pub struct UpdateEngageForm {
   pub country_id: u16,
@@ -172,10 +401,18 @@ pub async fn update(
   user: MyFirebaseUser,
   Path((chain_id, signer_address, campaign_id)): Path<(i64, String, Uuid)>,
   Json(form): Json<UpdateEngageForm>,
-) -> Response {
+) -> Result<Response, Response> {
   let signer_address = &signer_address.to_lowercase();
   if !user.has_wallet_claim(chain_id, signer_address) {
-    return StatusCode::FORBIDDEN.into_response();
+    return Err(StatusCode::FORBIDDEN.into_response());
+  }
+  let campaign = db::campaign_pub::get_tasks(&db, campaign_id)
+    .await
+    .map_err(handle_db_error)?;
+
+  let country_id = form.country_id as i16;
+  if let Some(lock) = campaign.lock(Some(country_id)) {
+    return Err(refuse(lock));
   }
 
   let res = db::engage_pub::update(
@@ -184,34 +421,139 @@ pub async fn update(
     chain_id,
     signer_address,
     db::engage_pub::UpdateParam {
-      country_id: Some(form.country_id as i16),
+      country_id: Some(country_id),
     },
   );
 
-  handle_result(res.await)
+  Ok(handle_result(res.await))
 }
 
 pub async fn submit_proof(
   State(db): State<sqlx::PgPool>,
   user: MyFirebaseUser,
+  State(subscan_client): State<subscan::Client>,
+  State(evm_client): State<evm::Client>,
   Path((chain_id, signer_address, campaign_id)): Path<(i64, String, Uuid)>,
-  Json(form): Json<Submissions>,
-) -> Response {
+  Json(mut form): Json<Submissions>,
+) -> Result<Response, Response> {
   let signer_address = &signer_address.to_lowercase();
   if !user.has_wallet_claim(chain_id, signer_address) {
-    return StatusCode::FORBIDDEN.into_response();
+    return Err(StatusCode::FORBIDDEN.into_response());
+  }
+  let campaign = db::campaign_pub::get_tasks(&db, campaign_id)
+    .await
+    .map_err(handle_db_error)?;
+  let engage = db::engage_pub::get(&db, campaign_id, chain_id, signer_address)
+    .await
+    .map_err(handle_db_error)?;
+
+  if let Some(lock) = campaign.lock(engage.country_id) {
+    return Err(refuse(lock));
+  }
+
+  let ctx = verify::Context {
+    db: &db,
+    subscan_client: &subscan_client,
+    evm_client: &evm_client,
+    project_id: campaign.project_id,
+    campaign_id,
+    chain_id,
+    contract_address: &campaign.contract_address,
+    start_at: campaign.start_at,
+    signer_address,
+    user_id: &user.sub,
+    block_fraud: campaign.block_fraud,
+  };
+  let verified = verify::verify(&ctx, &campaign.tasks, &mut form)
+    .await
+    .map_err(handle_verify_error)?;
+  let matched = verify::match_rules(&ctx, &campaign.tasks, &form)
+    .await
+    .map_err(handle_verify_error)?;
+
+  let quiz_id = form
+    .iter()
+    .find(|(_, s)| s.attempts.is_some())
+    .map(|(id, _)| id.to_owned());
+  let res = db::engage_pub::submit_proof(&db, campaign_id, chain_id, signer_address, form)
+    .await
+    .map_err(|err| match (err, quiz_id) {
+      (db::Error::LimitReached, Some(task_id)) => {
+        handle_verify_error(verify::Error::AttemptsExhausted(task_id))
+      }
+      (err, _) => handle_db_error(err),
+    })?;
+
+  auto_approve(
+    &db,
+    campaign.org_id,
+    campaign_id,
+    chain_id,
+    signer_address,
+    verified,
+    Reviewer::Verified,
+  )
+  .await?;
+  auto_approve(
+    &db,
+    campaign.org_id,
+    campaign_id,
+    chain_id,
+    signer_address,
+    matched,
+    Reviewer::Rule,
+  )
+  .await?;
+
+  Ok(into_json_response(&res))
+}
+
+/// Accept the tasks the server verified itself, or that met their auto-approval rules, as a
+/// reviewer would.
+async fn auto_approve(
+  db: &sqlx::PgPool,
+  org_id: Uuid,
+  campaign_id: Uuid,
+  chain_id: i64,
+  signer_address: &str,
+  accepted: Accepted,
+  reviewer: Reviewer<'_>,
+) -> Result<(), Response> {
+  if accepted.is_empty() {
+    return Ok(());
   }
-  if form.values().any(|sub| {
-    sub.link.is_none()
-      && sub.message.is_none()
-      && (sub.images.is_none() || sub.images.as_ref().unwrap().is_empty())
-  }) {
-    return StatusCode::BAD_REQUEST.into_response();
+
+  match db::engage::approve(
+    db,
+    org_id,
+    campaign_id,
+    chain_id,
+    signer_address.to_owned(),
+    accepted,
+    reviewer,
+  )
+  .await
+  {
+    // leave the tasks for a reviewer rather than fail the submission
+    Ok(_) | Err(db::Error::OverBudget | db::Error::Full) => Ok(()),
+    Err(err) => Err(handle_db_error(err)),
   }
+}
 
-  let res = db::engage_pub::submit_proof(&db, campaign_id, chain_id, signer_address, form);
+/// Refuse to let a participant in, explaining why in a way clients can act on.
+fn refuse(lock: CampaignLock) -> Response {
+  (StatusCode::FORBIDDEN, Json(lock)).into_response()
+}
 
-  handle_result(res.await)
+fn handle_verify_error(err: verify::Error) -> Response {
+  match err {
+    verify::Error::Db(err) => handle_db_error(err),
+    verify::Error::AttemptsExhausted(_) | verify::Error::TaskLocked(..) => {
+      (StatusCode::FORBIDDEN, err.to_string()).into_response()
+    }
+    verify::Error::DuplicateProof(_) => (StatusCode::CONFLICT, err.to_string()).into_response(),
+    _ => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+  }
 }
 
 #[derive(Clone, Serialize_repr, Debug)]
@@ -232,6 +574,8 @@ pub struct EngageEvent {
   pub signer_address: String,
   pub kind: EngageKind,
   pub task_ids: Option<Vec<String>>,
+  /// For approvals, whether a member, the server or a rule accepted the tasks.
+  pub review_source: Option<String>,
 }
 
 impl TryFrom<EngageEventLog> for EngageEvent {
@@ -266,6 +610,7 @@ impl EngageEvent {
       signer_address: p.signer_address,
       kind,
       task_ids,
+      review_source: p.review_source,
     }
   }
 }
@@ -317,6 +662,8 @@ pub async fn list_events(
       new_accepted: log.new_accepted,
       new_coupon_serial: log.new_coupon_serial,
       new_coupon_url: log.new_coupon_url,
+      review_source: log.review_source,
+      reviewer_id: log.reviewer_id,
       created_at: log.created_at,
     };
     if let Ok(ev) = EngageEvent::try_from(l) {
@@ -1,7 +1,9 @@
 use axum::{extract::State, response::Response};
+use axum_extra::extract::Query;
+use serde::Deserialize;
 
 use crate::{
-  api::{handle_db_error, into_json_response},
+  api::{handle_db_error, handle_result, into_json_response},
   auth::MyFirebaseUser,
   db,
 };
@@ -20,3 +22,25 @@ pub async fn get(
 
   Ok(into_json_response(&db::me::Me { xp, club }))
 }
+
+const DEFAULT_EXPIRING_DAYS: i32 = 7;
+const MAX_EXPIRING_DAYS: i32 = 365;
+
+#[derive(Deserialize, Debug)]
+pub struct ExpiringParams {
+  pub days: Option<i32>,
+}
+
+pub async fn get_expiring_points(
+  user: MyFirebaseUser,
+  State(db): State<sqlx::PgPool>,
+  Query(p): Query<ExpiringParams>,
+) -> Response {
+  let days = p
+    .days
+    .unwrap_or(DEFAULT_EXPIRING_DAYS)
+    .clamp(1, MAX_EXPIRING_DAYS);
+  let res = db::me::get_expiring(&db, &user.sub, days);
+
+  handle_result(res.await)
+}
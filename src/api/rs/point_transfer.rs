@@ -0,0 +1,69 @@
+use axum::{
+  extract::{Path, State},
+  http::StatusCode,
+  response::{IntoResponse, Response},
+  Json,
+};
+use axum_extra::extract::Query;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+  api::{handle_db_error, handle_result, into_json_response},
+  auth::MyFirebaseUser,
+  db::{self, new_uuid, point_transfer::Recipient, CreatedFilter, IdPrefix},
+};
+
+#[derive(Deserialize, Debug)]
+pub struct TransferForm {
+  #[serde(flatten)]
+  pub recipient: Recipient,
+  pub amount: i64,
+}
+
+pub async fn create(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path(project_id): Path<Uuid>,
+  Json(p): Json<TransferForm>,
+) -> Result<Response, Response> {
+  let project = db::project_pub::get(&db, project_id)
+    .await
+    .map_err(handle_db_error)?;
+  if !project.point_transfer {
+    return Err(StatusCode::FORBIDDEN.into_response());
+  }
+
+  let res = db::point_transfer::create(
+    &db,
+    db::point_transfer::CreateParam {
+      id: new_uuid(IdPrefix::PointTransfer),
+      project_id,
+      sender_id: &user.sub,
+      recipient: &p.recipient,
+      amount: p.amount,
+      daily_limit: project.point_transfer_daily_limit,
+    },
+  )
+  .await
+  .map_err(|err| match err {
+    db::Error::Validation => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    db::Error::LimitReached => {
+      (StatusCode::CONFLICT, "Daily transfer limit reached").into_response()
+    }
+    _ => handle_db_error(err),
+  })?;
+
+  Ok(into_json_response(&res))
+}
+
+pub async fn list(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path(project_id): Path<Uuid>,
+  Query(p): Query<db::ListParams<CreatedFilter>>,
+) -> Response {
+  let res = db::point_transfer::list(&db, project_id, &user.sub, p);
+
+  handle_result(res.await)
+}
@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use axum::{
+  extract::{Path, State},
+  http::StatusCode,
+  response::{IntoResponse, Response},
+  Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{api::handle_result, auth::MyFirebaseUser, db};
+
+pub async fn list(State(db): State<sqlx::PgPool>, Path(project_id): Path<Uuid>) -> Response {
+  let res = db::raffle::list(&db, None, Some(project_id));
+
+  handle_result(res.await)
+}
+
+pub async fn get(
+  State(db): State<sqlx::PgPool>,
+  Path((project_id, raffle_id)): Path<(Uuid, Uuid)>,
+) -> Response {
+  let res = db::raffle::get(&db, None, Some(project_id), raffle_id);
+
+  handle_result(res.await)
+}
+
+pub async fn list_entrants(
+  State(db): State<sqlx::PgPool>,
+  Path((project_id, raffle_id)): Path<(Uuid, Uuid)>,
+) -> Response {
+  let res = db::raffle::list_entrants(&db, project_id, raffle_id);
+
+  handle_result(res.await)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BuyForm {
+  pub tickets: i64,
+}
+
+pub async fn buy(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path((project_id, raffle_id)): Path<(Uuid, Uuid)>,
+  Json(form): Json<BuyForm>,
+) -> Response {
+  let res = db::raffle::buy(&db, project_id, raffle_id, &user.sub, form.tickets);
+
+  match res.await {
+    Err(db::Error::Validation) => (StatusCode::BAD_REQUEST, "Invalid tickets").into_response(),
+    Err(db::Error::LimitReached) => (StatusCode::CONFLICT, "Ticket limit reached").into_response(),
+    res => handle_result(res),
+  }
+}
+
+pub async fn start_drawing(db: sqlx::PgPool, period: Duration) {
+  let mut interval = tokio::time::interval(period);
+  loop {
+    interval.tick().await;
+    match db::raffle::draw_due(&db).await {
+      Ok(0) => {}
+      Ok(n) => {
+        tracing::info!("Drew {} raffles", n);
+      }
+      Err(err) => {
+        tracing::error!("Error drawing raffles: {}", err.to_string());
+      }
+    }
+  }
+}
@@ -44,3 +44,13 @@ pub async fn mint_from_campaign(
 
   handle_result(res.await)
 }
+
+pub async fn mint_from_org(
+  State(db): State<sqlx::PgPool>,
+  user: MyFirebaseUser,
+  Path((org_id, reward_id)): Path<(Uuid, Uuid)>,
+) -> Response {
+  let res = db::reward::mint_org_reward(&db, &user.sub, reward_id, org_id);
+
+  handle_result(res.await)
+}
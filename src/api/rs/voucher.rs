@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use axum::{
   extract::{Path, State},
   response::{IntoResponse, Response},
@@ -45,3 +47,19 @@ pub async fn get_project_point(
 
   handle_result(res.await)
 }
+
+pub async fn start_expiring(db: sqlx::PgPool, period: Duration) {
+  let mut interval = tokio::time::interval(period);
+  loop {
+    interval.tick().await;
+    match db::voucher::expire(&db).await {
+      Ok(0) => {}
+      Ok(n) => {
+        tracing::info!("Expired {} vouchers", n);
+      }
+      Err(err) => {
+        tracing::error!("Error expiring vouchers: {}", err.to_string());
+      }
+    }
+  }
+}
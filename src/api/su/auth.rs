@@ -8,10 +8,11 @@ use serde::Deserialize;
 
 use crate::{
   // api::cm::org::MAX_PERMISSION,
-  auth::{user::UserService, CustomClaims, MyFirebaseUser},
+  api::{cm::org_member::revoke_tokens, refresh_admin_claim},
+  auth::{provider::ClaimsStore, CustomClaims, MyFirebaseUser},
 };
 
-// pub async fn init(user: MyFirebaseUser, State(mut claims_service): State<UserService>) -> Response {
+// pub async fn init(user: MyFirebaseUser, State(mut claims_service): State<ClaimsStore>) -> Response {
 //   match claims_service
 //     .set_custom_attributes(
 //       &user.sub,
@@ -35,26 +36,54 @@ pub struct GrantParams {
 }
 
 pub async fn grant(
-  user: MyFirebaseUser,
-  State(mut claims_service): State<UserService>,
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  State(mut claims_service): State<ClaimsStore>,
   Json(p): Json<GrantParams>,
-) -> Response {
+) -> Result<Response, Response> {
+  refresh_admin_claim(&mut claims_service, &mut user).await?;
   if user.claims.admin < p.level {
-    return StatusCode::FORBIDDEN.into_response();
+    return Err(StatusCode::FORBIDDEN.into_response());
   }
 
-  match claims_service
+  let target = claims_service
+    .lookup(&p.user_id)
+    .await
+    .map_err(|err| (StatusCode::BAD_GATEWAY, err.to_string()).into_response())?;
+  claims_service
     .set_custom_attributes(
       &p.user_id,
       CustomClaims {
         admin: p.level,
-        orgs: user.claims.orgs,
-        wallets: user.claims.wallets,
+        orgs: target.customAttributes.orgs,
+        wallets: target.customAttributes.wallets,
       },
     )
     .await
-  {
-    Ok(()) => StatusCode::OK.into_response(),
-    Err(err) => (StatusCode::BAD_GATEWAY, err.to_string()).into_response(),
+    .map_err(|err| (StatusCode::BAD_GATEWAY, err.to_string()).into_response())?;
+
+  revoke_tokens(&db, &p.user_id).await?;
+
+  Ok(StatusCode::OK.into_response())
+}
+
+#[derive(Deserialize)]
+pub struct RevokeParams {
+  pub user_id: String,
+}
+
+pub async fn revoke(
+  State(db): State<sqlx::PgPool>,
+  mut user: MyFirebaseUser,
+  State(mut claims_service): State<ClaimsStore>,
+  Json(p): Json<RevokeParams>,
+) -> Result<Response, Response> {
+  refresh_admin_claim(&mut claims_service, &mut user).await?;
+  if !user.can_sudo() {
+    return Err(StatusCode::FORBIDDEN.into_response());
   }
+
+  revoke_tokens(&db, &p.user_id).await?;
+
+  Ok(StatusCode::ACCEPTED.into_response())
 }
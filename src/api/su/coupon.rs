@@ -8,8 +8,8 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
-  api::{handle_db_error, handle_result},
-  auth::MyFirebaseUser,
+  api::{handle_db_error, handle_result, refresh_admin_claim},
+  auth::{provider::ClaimsStore, MyFirebaseUser},
   db::{
     self,
     coupon::{Coupon, CouponFilter, ReplaceParams, UpdateParams},
@@ -57,9 +57,11 @@ pub struct CreateResult {
 
 pub async fn create(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
+  State(mut claims_service): State<ClaimsStore>,
   Json(p): Json<CreateForm>,
 ) -> Result<Json<CreateResult>, Response> {
+  refresh_admin_claim(&mut claims_service, &mut user).await?;
   if !user.can_sudo() {
     return Err(StatusCode::FORBIDDEN.into_response());
   }
@@ -77,10 +79,14 @@ pub async fn create(
 
 pub async fn update(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
+  State(mut claims_service): State<ClaimsStore>,
   Path((reward_id, number)): Path<(Uuid, i64)>,
   Json(p): Json<UpdateParams>,
 ) -> Response {
+  if let Err(res) = refresh_admin_claim(&mut claims_service, &mut user).await {
+    return res;
+  }
   if !user.can_sudo() {
     return StatusCode::FORBIDDEN.into_response();
   }
@@ -92,10 +98,14 @@ pub async fn update(
 
 pub async fn replace(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
+  State(mut claims_service): State<ClaimsStore>,
   Path((reward_id, number)): Path<(Uuid, i64)>,
   Json(p): Json<ReplaceParams>,
 ) -> Response {
+  if let Err(res) = refresh_admin_claim(&mut claims_service, &mut user).await {
+    return res;
+  }
   if !user.can_sudo() {
     return StatusCode::FORBIDDEN.into_response();
   }
@@ -107,9 +117,13 @@ pub async fn replace(
 
 pub async fn delete(
   State(db): State<sqlx::PgPool>,
-  user: MyFirebaseUser,
+  mut user: MyFirebaseUser,
+  State(mut claims_service): State<ClaimsStore>,
   Path((reward_id, number)): Path<(Uuid, i64)>,
 ) -> Response {
+  if let Err(res) = refresh_admin_claim(&mut claims_service, &mut user).await {
+    return res;
+  }
   if !user.can_sudo() {
     return StatusCode::FORBIDDEN.into_response();
   }
@@ -1,4 +1,8 @@
+pub mod api_key;
 pub mod firebase;
+pub mod local;
+pub mod provider;
+pub mod secret;
 pub mod user;
 
 use std::collections::HashMap;
@@ -13,9 +17,34 @@ use uuid::Uuid;
 pub type Orgs = HashMap<String, i64>;
 pub type Wallets = HashMap<String, bool>;
 
-pub const OWNER_PERMISSION: i64 = 0x1fffffffffffff;
+pub const VIEWER_PERMISSION: i64 = 0x1;
 pub const EDITOR_PERMISSION: i64 = 0x2;
-// pub const VIEWER_PERMISSION: i64 = 0x1;
+pub const REVIEWER_PERMISSION: i64 = 0x4;
+pub const FINANCE_PERMISSION: i64 = 0x8;
+pub const OWNER_PERMISSION: i64 = 0x1fffffffffffff;
+
+/// Named org roles, each granting a set of permission bits.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+  Viewer,
+  Reviewer,
+  Editor,
+  Finance,
+  Owner,
+}
+
+impl Role {
+  pub fn permission(&self) -> i64 {
+    match self {
+      Role::Viewer => VIEWER_PERMISSION,
+      Role::Reviewer => VIEWER_PERMISSION | REVIEWER_PERMISSION,
+      Role::Editor => VIEWER_PERMISSION | REVIEWER_PERMISSION | EDITOR_PERMISSION,
+      Role::Finance => VIEWER_PERMISSION | FINANCE_PERMISSION,
+      Role::Owner => OWNER_PERMISSION,
+    }
+  }
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct CustomClaims {
@@ -52,23 +81,42 @@ impl MyFirebaseUser {
     self.claims.admin > 0
   }
 
-  pub fn can_edit(&self, org_id: Uuid) -> bool {
-    let claim = self.claims.orgs.get(&org_id.to_string());
-    matches!(claim, Some(p) if p.ge(&EDITOR_PERMISSION))
+  pub fn permission_level(&self, org_id: Uuid) -> i64 {
+    match self.claims.orgs.get(&org_id.to_string()) {
+      Some(p) => *p,
+      None => 0,
+    }
+  }
+
+  /// Whether any of the bits in `mask` are granted for the org.
+  pub fn has_any_permission(&self, org_id: Uuid, mask: i64) -> bool {
+    self.permission_level(org_id) & mask != 0
   }
-/*   
+
+  /// Any org role grants read access.
   pub fn can_view(&self, org_id: Uuid) -> bool {
-    let claim = self.claims.orgs.get(&org_id.to_string());
-    matches!(claim, Some(p) if p.ge(&VIEWER_PERMISSION))
+    self.has_any_permission(
+      org_id,
+      VIEWER_PERMISSION | REVIEWER_PERMISSION | EDITOR_PERMISSION | FINANCE_PERMISSION,
+    )
   }
 
-  pub fn permission_level(&self, game_id: Uuid) -> i64 {
-    match self.claims.orgs.get(&game_id.to_string()) {
-      Some(p) => *p,
-      None => 0,
-    }
+  pub fn can_review(&self, org_id: Uuid) -> bool {
+    self.has_any_permission(org_id, REVIEWER_PERMISSION | EDITOR_PERMISSION)
   }
- */
+
+  pub fn can_edit(&self, org_id: Uuid) -> bool {
+    self.has_any_permission(org_id, EDITOR_PERMISSION)
+  }
+
+  pub fn can_manage_rewards(&self, org_id: Uuid) -> bool {
+    self.has_any_permission(org_id, FINANCE_PERMISSION | EDITOR_PERMISSION)
+  }
+
+  pub fn is_owner(&self, org_id: Uuid) -> bool {
+    self.permission_level(org_id) == OWNER_PERMISSION
+  }
+
   pub fn has_wallet_claim(&self, chain_id: i64, signer_address: &str) -> bool {
     let id = format!("{}/{}", chain_id, signer_address);
     let claim = self.claims.wallets.get(&id);
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::secret;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+const API_KEY_PREFIX: &str = "rsk_";
+
+/// What an org API key is allowed to do.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ApiScope {
+  #[serde(rename = "campaign:read")]
+  CampaignRead,
+  #[serde(rename = "engage:read")]
+  EngageRead,
+  #[serde(rename = "engage:approve")]
+  EngageApprove,
+}
+
+/// An authenticated org API key, used by our org customers' servers instead of a user token.
+#[derive(Debug, Clone)]
+pub struct OrgApiKey {
+  pub id: Uuid,
+  pub org_id: Uuid,
+  pub scopes: Vec<ApiScope>,
+}
+
+impl OrgApiKey {
+  pub fn can(&self, org_id: Uuid, scope: ApiScope) -> bool {
+    self.org_id == org_id && self.scopes.contains(&scope)
+  }
+}
+
+/// Generate a new API key, returning it along with its display prefix.
+pub fn generate() -> (String, String) {
+  let key = format!("{}{}", API_KEY_PREFIX, secret::generate());
+  let prefix = key.chars().take(API_KEY_PREFIX.len() + 6).collect();
+  (key, prefix)
+}
@@ -0,0 +1,156 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, RwLock},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{TimeZone, Utc};
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+
+use super::{CustomClaims, MyFirebaseUser, User};
+
+/// Key material used to verify locally issued tokens.
+pub enum LocalKey {
+  /// Shared secret for HS256 tokens.
+  Hs256(String),
+  /// PEM encoded public key for RS256 tokens.
+  Rs256(String),
+}
+
+/// Verifies JWTs signed with a locally configured key, for development and tests.
+///
+/// Tokens carry the same claims as Firebase ID tokens. Claims written through the
+/// [`LocalClaimsStore`] take precedence over the ones in the token, the same way a
+/// refreshed Firebase token would pick them up.
+#[derive(Clone)]
+pub struct LocalAuth {
+  key: DecodingKey,
+  validation: Validation,
+  store: LocalClaimsStore,
+}
+
+impl LocalAuth {
+  pub fn new(key: LocalKey, issuer: Option<String>, audience: Option<String>) -> Result<Self> {
+    let (key, mut validation) = match key {
+      LocalKey::Hs256(secret) if secret.trim().is_empty() => {
+        return Err(anyhow!("HS256 secret is empty"));
+      }
+      LocalKey::Hs256(secret) => (
+        DecodingKey::from_secret(secret.as_bytes()),
+        Validation::new(Algorithm::HS256),
+      ),
+      LocalKey::Rs256(pem) => (
+        DecodingKey::from_rsa_pem(pem.as_bytes())?,
+        Validation::new(Algorithm::RS256),
+      ),
+    };
+
+    if let Some(iss) = issuer {
+      validation.set_issuer(&[iss]);
+    }
+    match audience {
+      Some(aud) => validation.set_audience(&[aud]),
+      None => validation.validate_aud = false,
+    }
+
+    Ok(Self {
+      key,
+      validation,
+      store: LocalClaimsStore::default(),
+    })
+  }
+
+  pub fn store(&self) -> LocalClaimsStore {
+    self.store.clone()
+  }
+
+  pub fn verify(&self, token: &str) -> Result<MyFirebaseUser> {
+    let mut user = decode::<MyFirebaseUser>(token, &self.key, &self.validation)?.claims;
+
+    match self.store.claims(&user.sub) {
+      Some(claims) => user.claims = claims,
+      None => self.store.remember(&user),
+    }
+
+    Ok(user)
+  }
+}
+
+/// In-memory stand-in for the Firebase user service.
+#[derive(Debug, Clone, Default)]
+pub struct LocalClaimsStore {
+  users: Arc<RwLock<HashMap<String, LocalUser>>>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct LocalUser {
+  email: Option<String>,
+  email_verified: bool,
+  name: Option<String>,
+  picture: Option<String>,
+  auth_time: i64,
+  /// Claims of the latest token, until some are written through the store.
+  token_claims: CustomClaims,
+  claims: Option<CustomClaims>,
+}
+
+impl LocalClaimsStore {
+  fn claims(&self, uid: &str) -> Option<CustomClaims> {
+    let users = self.users.read().unwrap();
+    users.get(uid).and_then(|u| u.claims.clone())
+  }
+
+  fn remember(&self, user: &MyFirebaseUser) {
+    let mut users = self.users.write().unwrap();
+    users.insert(
+      user.sub.clone(),
+      LocalUser {
+        email: user.email.clone(),
+        email_verified: user.email_verified.unwrap_or(false),
+        name: user.name.clone(),
+        picture: user.picture.clone(),
+        auth_time: user.auth_time as i64,
+        token_claims: user.claims.clone(),
+        claims: None,
+      },
+    );
+  }
+
+  pub fn set_custom_attributes(&mut self, uid: &str, attr: CustomClaims) -> Result<()> {
+    // any uid may hold claims, whether or not it has authenticated since the process started
+    let mut users = self.users.write().unwrap();
+    users.entry(uid.to_string()).or_default().claims = Some(attr);
+    Ok(())
+  }
+
+  pub fn lookup(&mut self, uid: &str) -> Result<User> {
+    let users = self.users.read().unwrap();
+    let user = users.get(uid).cloned().unwrap_or_default();
+    let auth_time = Utc
+      .timestamp_opt(user.auth_time, 0)
+      .single()
+      .unwrap_or_default();
+
+    Ok(User {
+      localId: uid.to_string(),
+      email: user.email.clone().unwrap_or_default(),
+      displayName: user.name.clone(),
+      language: None,
+      photoUrl: user.picture.clone(),
+      timeZone: None,
+      dateOfBirth: None,
+      emailVerified: user.email_verified,
+      passwordUpdatedAt: 0,
+      providerUserInfo: vec![],
+      validSince: auth_time.timestamp().to_string(),
+      disabled: false,
+      lastLoginAt: auth_time,
+      createdAt: auth_time,
+      phoneNumber: None,
+      customAttributes: user.claims.clone().unwrap_or(user.token_claims.clone()),
+      emailLinkSignin: false,
+      initialEmail: user.email.clone(),
+      lastRefreshAt: auth_time.to_rfc3339(),
+    })
+  }
+}
@@ -0,0 +1,47 @@
+use anyhow::{anyhow, Result};
+use firebase_auth::FirebaseAuth;
+
+use super::{
+  local::{LocalAuth, LocalClaimsStore},
+  user::UserService,
+  CustomClaims, MyFirebaseUser, User,
+};
+
+/// Verifies bearer tokens, either against Firebase or a locally configured key.
+#[derive(Clone)]
+pub enum AuthProvider {
+  Firebase(FirebaseAuth),
+  Local(Box<LocalAuth>),
+}
+
+impl AuthProvider {
+  pub fn verify(&self, token: &str) -> Result<MyFirebaseUser> {
+    match self {
+      AuthProvider::Firebase(auth) => auth.verify(token).map_err(|err| anyhow!(err.to_string())),
+      AuthProvider::Local(auth) => auth.verify(token),
+    }
+  }
+}
+
+/// Reads and writes user custom claims for the matching [`AuthProvider`].
+#[derive(Debug, Clone)]
+pub enum ClaimsStore {
+  Firebase(Box<UserService>),
+  Local(LocalClaimsStore),
+}
+
+impl ClaimsStore {
+  pub async fn set_custom_attributes(&mut self, uid: &str, attr: CustomClaims) -> Result<()> {
+    match self {
+      ClaimsStore::Firebase(service) => service.set_custom_attributes(uid, attr).await,
+      ClaimsStore::Local(store) => store.set_custom_attributes(uid, attr),
+    }
+  }
+
+  pub async fn lookup(&mut self, uid: &str) -> Result<User> {
+    match self {
+      ClaimsStore::Firebase(service) => service.lookup(uid).await,
+      ClaimsStore::Local(store) => store.lookup(uid),
+    }
+  }
+}
@@ -0,0 +1,15 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+/// Generate a random URL safe secret to be handed out once, e.g. an invite token.
+pub fn generate() -> String {
+  let mut bytes = [0u8; 32];
+  rand::thread_rng().fill_bytes(&mut bytes);
+  URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Hash a secret for storage. Only the hash is persisted so a DB leak doesn't expose it.
+pub fn hash(secret: &str) -> String {
+  hex::encode(Sha256::digest(secret.as_bytes()))
+}
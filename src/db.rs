@@ -4,23 +4,34 @@ use serde::{Deserialize, Serialize};
 use sqlx::{Postgres, QueryBuilder};
 use uuid::Uuid;
 
+pub mod auth_revocation;
 pub mod campaign;
+pub mod campaign_budget;
 pub mod campaign_participation;
 pub mod campaign_reward;
 pub mod campaign_reward_pub;
 pub mod coupon;
 pub mod coupon_pub;
+pub mod coupon_transfer;
 pub mod engage;
 pub mod engage_event;
+pub mod engage_review;
 pub mod mezzofy;
 pub mod org;
+pub mod org_api_key;
+pub mod org_invite;
+pub mod org_reward;
+pub mod org_reward_pub;
 pub mod project;
 pub mod project_reward;
 pub mod project_reward_pub;
+pub mod raffle;
 pub mod campaign_pub;
 pub mod engage_pub;
 pub mod project_pub;
 pub mod project_membership;
+pub mod point_multiplier;
+pub mod point_transfer;
 pub mod reward;
 pub mod voucher;
 pub mod me;
@@ -40,6 +51,22 @@ pub enum Error {
   LimitReached,
   #[error("Limit reached")]
   Unknown,
+  #[error("Claimed by another reviewer")]
+  Claimed,
+  #[error("Outstanding point debt")]
+  Debt,
+  #[error("Over budget")]
+  OverBudget,
+  #[error("Coupon already redeemed")]
+  Redeemed,
+  #[error("Coupon already issued")]
+  Issued,
+  #[error("Task completion limit reached")]
+  Full,
+  #[error("Org must keep at least one owner")]
+  LastOwner,
+  #[error("Already a member of the org")]
+  Member,
   #[error("Unknown sqlx error {0}")]
   Sqlx(#[from] sqlx::Error),
 }
@@ -50,6 +77,12 @@ pub enum IdPrefix {
   Project = 0x02,
   Campaign = 0x03,
   Reward = 0x04,
+  OrgInvite = 0x05,
+  ApiKey = 0x06,
+  Multiplier = 0x07,
+  PointTransfer = 0x08,
+  CouponTransfer = 0x09,
+  Raffle = 0x0A,
   // IdempotentKey=0xFF,
 }
 
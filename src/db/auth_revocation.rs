@@ -0,0 +1,33 @@
+use chrono::{DateTime, Utc};
+use sqlx::{query, PgPool};
+
+use super::{handle_pg_error, Error};
+
+// revoke all tokens issued to a user so far
+pub async fn revoke(db: &PgPool, user_id: &str) -> Result<DateTime<Utc>, Error> {
+  let res = query!(
+    "INSERT INTO auth_revocation (user_id)
+    VALUES ($1)
+    ON CONFLICT (user_id) DO UPDATE SET revoked_at = NOW()
+    RETURNING revoked_at",
+    user_id
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(res.revoked_at)
+}
+
+// get when a user's tokens were last revoked
+pub async fn get(db: &PgPool, user_id: &str) -> Result<Option<DateTime<Utc>>, Error> {
+  let res = query!(
+    "SELECT revoked_at FROM auth_revocation WHERE user_id = $1",
+    user_id
+  )
+  .fetch_optional(db)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(res.map(|r| r.revoked_at))
+}
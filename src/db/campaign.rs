@@ -1,5 +1,7 @@
 use chrono::{DateTime, NaiveDate, Utc};
+use ethers::types::U256;
 use is_empty::IsEmpty;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use sqlx::{
   prelude::FromRow,
@@ -9,9 +11,15 @@ use sqlx::{
 };
 use uuid::Uuid;
 
-use crate::db::sqlx_macro::{must_bind, maybe_bind, offset_limit};
+use crate::{
+  auth::secret,
+  db::sqlx_macro::{must_bind, maybe_bind, offset_limit},
+};
 
-use super::{handle_pg_error, maybe_order_by, CreateResult, Error, UpdateResult};
+use super::{
+  engage::{Accepted, Submission},
+  handle_pg_error, maybe_order_by, CreateResult, Error, UpdateResult,
+};
 
 #[derive(FromRow, Serialize)]
 pub struct Campaign {
@@ -34,6 +42,12 @@ pub struct Campaign {
   pub end_at: Option<NaiveDate>,
   pub voucher_policy: i16,
   pub voucher_expire_at: Option<NaiveDate>,
+  pub max_participants: Option<i64>,
+  pub allowed_countries: Option<Vec<i16>>,
+  pub blocked_countries: Option<Vec<i16>>,
+  pub eligibility: Json<Vec<Rule>>,
+  pub block_fraud: bool,
+  pub budget_warnings: Option<Vec<i16>>,
   pub created_at: DateTime<Utc>,
   pub updated_at: Option<DateTime<Utc>>,
 }
@@ -46,6 +60,319 @@ pub struct Task {
   pub link: Option<String>,
   pub images: Option<Vec<String>>,
   pub point: Option<i64>,
+  #[serde(default)]
+  pub kind: TaskKind,
+  /// Tasks that must be accepted before this one can be submitted.
+  pub requires: Option<Vec<String>>,
+  pub open_at: Option<DateTime<Utc>>,
+  pub close_at: Option<DateTime<Utc>>,
+  /// How many participants can complete the task.
+  pub max_completions: Option<i64>,
+  /// Rules that, when all met, accept a submission without a reviewer.
+  pub auto_approve: Option<Vec<ApprovalRule>>,
+}
+
+/// Why a task can't be submitted yet, or anymore.
+#[derive(thiserror::Error, Serialize, PartialEq, Clone, Debug)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum TaskLock {
+  #[error("opens at {open_at}")]
+  NotOpen { open_at: DateTime<Utc> },
+  #[error("closed at {close_at}")]
+  Closed { close_at: DateTime<Utc> },
+  #[error("requires {}", task_ids.join(", "))]
+  Requires { task_ids: Vec<String> },
+  #[error("max completions reached")]
+  Full,
+}
+
+/// Why a participant can't join, or submit to, a campaign.
+#[derive(thiserror::Error, Serialize, PartialEq, Clone, Debug)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum CampaignLock {
+  #[error("starts at {start_at}")]
+  NotStarted { start_at: NaiveDate },
+  #[error("ended at {end_at}")]
+  Ended { end_at: NaiveDate },
+  #[error("max participants reached")]
+  Full { max_participants: i64 },
+  #[error("country required")]
+  CountryRequired,
+  #[error("country {country_id} not allowed")]
+  CountryNotAllowed { country_id: i16 },
+  #[error("eligibility rules not met")]
+  NotEligible { rules: Vec<RuleCheck> },
+  #[error("already joined with {signer_address}")]
+  OtherWallet { signer_address: String },
+}
+
+/// A condition participants must meet to join a campaign.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Rule {
+  /// Member of a project's club.
+  ClubMember {
+    project_id: Uuid,
+  },
+  /// Holds NFTs of a contract with the joining wallet.
+  NftHolder(NftHoldConfig),
+  /// Had every task of another campaign accepted.
+  CompletedCampaign {
+    campaign_id: Uuid,
+  },
+  Country {
+    country_ids: Vec<i16>,
+  },
+  /// Number of coupons earned across projects.
+  MinXp {
+    xp: i64,
+  },
+}
+
+impl Rule {
+  /// Check the rule is usable, returning a reason when it isn't.
+  pub fn validate(&self) -> Result<(), &'static str> {
+    match self {
+      Rule::NftHolder(c) if c.min_count == 0 => Err("min_count should be at least 1"),
+      Rule::Country { country_ids } if country_ids.is_empty() => Err("country_ids is empty"),
+      Rule::MinXp { xp } if *xp < 1 => Err("xp should be at least 1"),
+      _ => Ok(()),
+    }
+  }
+}
+
+/// The outcome of an eligibility rule for a participant.
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct RuleCheck {
+  #[serde(flatten)]
+  pub rule: Rule,
+  pub passed: bool,
+  pub detail: Option<String>,
+}
+
+/// A condition on a submission for it to be accepted without a reviewer.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ApprovalRule {
+  /// The link is on one of the domains, or their subdomains.
+  LinkDomain {
+    domains: Vec<String>,
+  },
+  /// The link matches a regular expression.
+  LinkPattern {
+    pattern: String,
+  },
+  MinImages {
+    count: usize,
+  },
+  MinMessageLength {
+    length: usize,
+  },
+  /// Submitted by a member of the campaign project's club.
+  ClubMember,
+}
+
+impl ApprovalRule {
+  /// Check the rule is usable, returning a reason when it isn't.
+  pub fn validate(&self) -> Result<(), &'static str> {
+    match self {
+      ApprovalRule::LinkDomain { domains } if domains.is_empty() => Err("domains is empty"),
+      ApprovalRule::LinkPattern { pattern } if Regex::new(pattern).is_err() => {
+        Err("pattern is not a valid regex")
+      }
+      ApprovalRule::MinImages { count } if *count < 1 => Err("count should be at least 1"),
+      ApprovalRule::MinMessageLength { length } if *length < 1 => {
+        Err("length should be at least 1")
+      }
+      _ => Ok(()),
+    }
+  }
+}
+
+impl Task {
+  /// Check whether a participant, with the given tasks accepted, can submit this task.
+  pub fn lock(&self, accepted: &Accepted, completions: i64) -> Option<TaskLock> {
+    let now = Utc::now();
+    if let Some(open_at) = self.open_at.filter(|t| *t > now) {
+      return Some(TaskLock::NotOpen { open_at });
+    }
+    if let Some(close_at) = self.close_at.filter(|t| *t <= now) {
+      return Some(TaskLock::Closed { close_at });
+    }
+
+    let missing = self
+      .requires
+      .iter()
+      .flatten()
+      .filter(|id| accepted.get(*id) != Some(&true))
+      .cloned()
+      .collect::<Vec<String>>();
+    if !missing.is_empty() {
+      return Some(TaskLock::Requires { task_ids: missing });
+    }
+
+    match self.max_completions {
+      Some(max) if completions >= max && accepted.get(&self.id) != Some(&true) => {
+        Some(TaskLock::Full)
+      }
+      _ => None,
+    }
+  }
+}
+
+/// How a task is completed, and how the server can check a submission for it.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TaskKind {
+  /// Free-form proof reviewed by a human.
+  #[default]
+  Manual,
+  NftHold(NftHoldConfig),
+  OnchainTx(OnchainTxConfig),
+  Quiz(QuizConfig),
+  Referral(ReferralConfig),
+  VisitLink(VisitLinkConfig),
+  CheckIn,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct NftHoldConfig {
+  /// Defaults to the campaign contract.
+  pub contract_address: Option<String>,
+  pub min_count: u32,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct OnchainTxConfig {
+  /// Defaults to the campaign contract.
+  pub contract_address: Option<String>,
+  /// 4 byte method selector the call data should start with, as hex.
+  pub method: Option<String>,
+  /// Minimum value sent, in wei.
+  pub min_value: Option<String>,
+}
+
+impl OnchainTxConfig {
+  /// The parsed method selector, None when unset or invalid.
+  pub fn method_selector(&self) -> Option<Vec<u8>> {
+    self
+      .method
+      .as_ref()
+      .and_then(|m| hex::decode(m.trim_start_matches("0x")).ok())
+      .filter(|s| s.len() == 4)
+  }
+
+  /// The parsed minimum value, None when unset or invalid.
+  pub fn min_value(&self) -> Option<U256> {
+    self
+      .min_value
+      .as_ref()
+      .and_then(|v| U256::from_dec_str(v).ok())
+  }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct QuizConfig {
+  pub questions: Vec<QuizQuestion>,
+  /// Number of correct answers needed to pass.
+  pub pass_score: u32,
+  pub max_attempts: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct QuizQuestion {
+  pub id: String,
+  pub text: String,
+  pub options: Option<Vec<String>>,
+  /// Plain answer as sent by editors, replaced by its hash before the task is stored.
+  #[serde(default, skip_serializing)]
+  pub answer: Option<String>,
+  pub answer_hash: Option<String>,
+}
+
+impl QuizQuestion {
+  pub fn hash_answer(&self, answer: &str) -> String {
+    secret::hash(&format!("{}:{}", self.id, answer.trim().to_lowercase()))
+  }
+
+  pub fn is_correct(&self, answer: &str) -> bool {
+    self.answer_hash.as_deref() == Some(&self.hash_answer(answer))
+  }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct ReferralConfig {
+  pub min_referrals: u32,
+}
+
+/// Left to a reviewer, participants submit the link they visited.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct VisitLinkConfig {
+  pub url: String,
+}
+
+impl TaskKind {
+  /// Check the task config is usable, returning a reason when it isn't.
+  pub fn validate(&self) -> Result<(), &'static str> {
+    match self {
+      TaskKind::NftHold(c) if c.min_count == 0 => Err("min_count should be at least 1"),
+      TaskKind::OnchainTx(c) if c.method.is_some() && c.method_selector().is_none() => {
+        Err("method should be a 4 byte selector")
+      }
+      TaskKind::OnchainTx(c) if c.min_value.is_some() && c.min_value().is_none() => {
+        Err("min_value should be an amount in wei")
+      }
+      TaskKind::Quiz(c) if c.questions.is_empty() => Err("quiz has no questions"),
+      TaskKind::Quiz(c) if c.questions.iter().any(|q| q.answer_hash.is_none()) => {
+        Err("quiz question has no answer")
+      }
+      TaskKind::Quiz(c) if c.pass_score == 0 || c.pass_score as usize > c.questions.len() => {
+        Err("pass_score should be between 1 and the number of questions")
+      }
+      TaskKind::Quiz(QuizConfig {
+        max_attempts: Some(0),
+        ..
+      }) => Err("max_attempts should be at least 1"),
+      TaskKind::Referral(c) if c.min_referrals == 0 => Err("min_referrals should be at least 1"),
+      TaskKind::VisitLink(c) if c.url.is_empty() => Err("url missing"),
+      _ => Ok(()),
+    }
+  }
+
+  /// Replace plain quiz answers with their hashes.
+  pub fn seal(&mut self) {
+    if let TaskKind::Quiz(c) = self {
+      for q in c.questions.iter_mut() {
+        if let Some(answer) = q.answer.take() {
+          q.answer_hash = Some(q.hash_answer(&answer));
+        }
+      }
+    }
+  }
+
+  /// Drop anything participants shouldn't see, like quiz answer hashes.
+  pub fn redact(&mut self) {
+    if let TaskKind::Quiz(c) = self {
+      for q in c.questions.iter_mut() {
+        q.answer_hash = None;
+      }
+    }
+  }
+
+  /// Whether a submission carries what this kind of task needs.
+  pub fn accepts(&self, sub: &Submission) -> bool {
+    let has_proof = sub.link.is_some()
+      || sub.message.is_some()
+      || sub.images.as_ref().is_some_and(|i| !i.is_empty());
+
+    match self {
+      TaskKind::Manual => has_proof,
+      TaskKind::Quiz(_) => sub.answers.as_ref().is_some_and(|a| !a.is_empty()),
+      TaskKind::OnchainTx(_) => sub.tx_hash.is_some(),
+      TaskKind::VisitLink(_) => sub.link.is_some(),
+      TaskKind::NftHold(_) | TaskKind::Referral(_) | TaskKind::CheckIn => true,
+    }
+  }
 }
 
 #[derive(IsEmpty, Deserialize, Clone, Debug)]
@@ -84,6 +411,12 @@ pub async fn list(
       end_at,
       voucher_policy,
       voucher_expire_at,
+      max_participants,
+      allowed_countries,
+      blocked_countries,
+      eligibility,
+      block_fraud,
+      budget_warnings,
       created_at,
       updated_at
     FROM campaign"#,
@@ -154,7 +487,13 @@ pub async fn get(db: &PgPool, org_id: Uuid, campaign_id: Uuid) -> Result<Campaig
     end_at,
     voucher_policy,
     voucher_expire_at,
-  created_at,
+    max_participants,
+    allowed_countries,
+    blocked_countries,
+    eligibility AS "eligibility: Json<Vec<Rule>>",
+    block_fraud,
+    budget_warnings,
+    created_at,
     updated_at
   FROM campaign
     WHERE org_id = $1 AND id = $2"#,
@@ -186,6 +525,12 @@ pub struct CreateParam {
   pub end_at: Option<NaiveDate>,
   pub voucher_policy: i16,
   pub voucher_expire_at: Option<NaiveDate>,
+  pub max_participants: Option<i64>,
+  pub allowed_countries: Option<Vec<i16>>,
+  pub blocked_countries: Option<Vec<i16>>,
+  pub eligibility: Vec<Rule>,
+  pub block_fraud: bool,
+  pub budget_warnings: Option<Vec<i16>>,
 }
 
 // create a campaign
@@ -211,9 +556,15 @@ pub async fn create(db: &PgPool, p: CreateParam) -> Result<CreateResult, Error>
       start_at,
       end_at,
       voucher_policy,
-      voucher_expire_at
+      voucher_expire_at,
+      max_participants,
+      allowed_countries,
+      blocked_countries,
+      eligibility,
+      block_fraud,
+      budget_warnings
       )
-    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25)
     RETURNING created_at",
     p.org_id,
     p.project_id,
@@ -234,6 +585,12 @@ pub async fn create(db: &PgPool, p: CreateParam) -> Result<CreateResult, Error>
     p.end_at,
     p.voucher_policy,
     p.voucher_expire_at,
+    p.max_participants,
+    p.allowed_countries.as_deref(),
+    p.blocked_countries.as_deref(),
+    Json(&p.eligibility) as _,
+    p.block_fraud,
+    p.budget_warnings.as_deref(),
   )
   .fetch_one(db)
   .await
@@ -260,6 +617,12 @@ pub struct UpdateParams {
   pub updated_at: Option<DateTime<Utc>>,
   pub voucher_policy: Option<i16>,
   pub voucher_expire_at: Option<NaiveDate>,
+  pub max_participants: Option<i64>,
+  pub allowed_countries: Option<Vec<i16>>,
+  pub blocked_countries: Option<Vec<i16>>,
+  pub eligibility: Option<Vec<Rule>>,
+  pub block_fraud: Option<bool>,
+  pub budget_warnings: Option<Vec<i16>>,
 }
 
 // update a campaign
@@ -291,6 +654,12 @@ pub async fn update(
   maybe_bind!(sep, "end_at" = p.end_at);
   maybe_bind!(sep, "voucher_policy" = p.voucher_policy);
   maybe_bind!(sep, "voucher_expire_at" = p.voucher_expire_at);
+  maybe_bind!(sep, "max_participants" = p.max_participants);
+  maybe_bind!(sep, "allowed_countries" = p.allowed_countries);
+  maybe_bind!(sep, "blocked_countries" = p.blocked_countries);
+  maybe_bind!(sep, "eligibility", p.eligibility, Json);
+  maybe_bind!(sep, "block_fraud" = p.block_fraud);
+  maybe_bind!(sep, "budget_warnings" = p.budget_warnings);
   query.push(" WHERE org_id = ").push_bind(org_id);
   query.push(" AND id = ").push_bind(campaign_id);
   query.push(" RETURNING updated_at");
@@ -319,6 +688,18 @@ pub struct ReplaceParams {
   pub end_at: Option<NaiveDate>,
   pub voucher_policy: i16,
   pub voucher_expire_at: Option<NaiveDate>,
+  pub max_participants: Option<i64>,
+  pub allowed_countries: Option<Vec<i16>>,
+  pub blocked_countries: Option<Vec<i16>>,
+  #[serde(default)]
+  pub eligibility: Vec<Rule>,
+  #[serde(default = "default_block_fraud")]
+  pub block_fraud: bool,
+  pub budget_warnings: Option<Vec<i16>>,
+}
+
+fn default_block_fraud() -> bool {
+  true
 }
 
 // replace a campaign
@@ -347,6 +728,12 @@ pub async fn replace(
   must_bind!(sep, "end_at" = p.end_at);
   must_bind!(sep, "voucher_policy" = p.voucher_policy);
   must_bind!(sep, "voucher_expire_at" = p.voucher_expire_at);
+  must_bind!(sep, "max_participants" = p.max_participants);
+  must_bind!(sep, "allowed_countries" = p.allowed_countries);
+  must_bind!(sep, "blocked_countries" = p.blocked_countries);
+  must_bind!(sep, "eligibility" = Json(p.eligibility));
+  must_bind!(sep, "block_fraud" = p.block_fraud);
+  must_bind!(sep, "budget_warnings" = p.budget_warnings);
   query.push(" WHERE org_id = ").push_bind(org_id);
   query.push(" AND id = ").push_bind(campaign_id);
   query.push(" RETURNING updated_at");
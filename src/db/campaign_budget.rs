@@ -0,0 +1,193 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, query, query_as, PgConnection, PgPool};
+use uuid::Uuid;
+
+use super::{campaign::Campaign, handle_pg_error, Error};
+
+/// What a campaign has committed of its budget, through the points of approved tasks and the
+/// `reward_amount` of each coupon issued.
+#[derive(Serialize, Debug)]
+pub struct BudgetUsage {
+  pub committed_points: i64,
+  pub issued_coupons: i64,
+  pub committed: Decimal,
+  pub remaining: Option<Decimal>,
+}
+
+#[derive(Serialize)]
+pub struct BudgetedCampaign {
+  #[serde(flatten)]
+  pub campaign: Campaign,
+  pub budget_usage: BudgetUsage,
+}
+
+/// Left by the first commitment that took a campaign past one of its `budget_warnings`.
+#[derive(FromRow, Serialize, Debug)]
+pub struct BudgetWarning {
+  pub threshold: i16,
+  pub budget: Decimal,
+  pub committed: Decimal,
+  pub created_at: DateTime<Utc>,
+}
+
+struct Budget {
+  org_id: Uuid,
+  budget: Option<Decimal>,
+  budget_warnings: Option<Vec<i16>>,
+  usage: BudgetUsage,
+}
+
+// read the budget of a campaign and what it has committed so far, locking it when `for_update`
+async fn get_budget(
+  conn: &mut PgConnection,
+  campaign_id: Uuid,
+  for_update: bool,
+) -> Result<Budget, Error> {
+  if for_update {
+    query!("SELECT id FROM campaign WHERE id = $1 FOR NO KEY UPDATE", campaign_id)
+      .fetch_one(&mut *conn)
+      .await
+      .map_err(handle_pg_error)?;
+  }
+
+  let res = query!(
+    r#"SELECT
+      org_id AS "org_id!",
+      budget,
+      budget_warnings,
+      reward_amount,
+      (
+        SELECT COALESCE(SUM(value), 0)
+        FROM voucher
+        WHERE campaign_id = c.id
+          AND voided_at IS NULL
+          AND transfer_id IS NULL
+      )::BIGINT AS "committed_points!",
+      (
+        SELECT COUNT(*)
+        FROM engage
+        WHERE campaign_id = c.id
+          AND (coupon_issue_id IS NOT NULL
+            OR coupon_serial IS NOT NULL
+            OR coupon_url IS NOT NULL
+            OR coupon_pending_at IS NOT NULL)
+      ) AS "issued_coupons!"
+    FROM campaign c
+    WHERE id = $1"#,
+    campaign_id
+  )
+  .fetch_one(&mut *conn)
+  .await
+  .map_err(handle_pg_error)?;
+
+  let committed = Decimal::from(res.committed_points)
+    + Decimal::from(res.issued_coupons) * res.reward_amount.unwrap_or_default();
+  Ok(Budget {
+    org_id: res.org_id,
+    budget: res.budget,
+    budget_warnings: res.budget_warnings,
+    usage: BudgetUsage {
+      committed_points: res.committed_points,
+      issued_coupons: res.issued_coupons,
+      committed,
+      remaining: res.budget.map(|b| b - committed),
+    },
+  })
+}
+
+// get what a campaign has committed of its budget
+pub async fn get_usage(db: &PgPool, campaign_id: Uuid) -> Result<BudgetUsage, Error> {
+  let mut conn = db.acquire().await?;
+  let budget = get_budget(&mut conn, campaign_id, false).await?;
+
+  Ok(budget.usage)
+}
+
+// charge `amount` to a campaign's budget within a transaction, refusing to go past it and
+// warning about the thresholds it crosses. the caller records what the amount was spent on
+pub(super) async fn charge_in(
+  conn: &mut PgConnection,
+  campaign_id: Uuid,
+  amount: Decimal,
+) -> Result<(), Error> {
+  if amount <= Decimal::ZERO {
+    return Ok(());
+  }
+  let b = get_budget(conn, campaign_id, true).await?;
+  let Some(budget) = b.budget else {
+    return Ok(());
+  };
+  let before = b.usage.committed;
+  let after = before + amount;
+  if after > budget {
+    return Err(Error::OverBudget);
+  }
+
+  let crossed = b
+    .budget_warnings
+    .unwrap_or_default()
+    .into_iter()
+    .filter(|t| {
+      let at = budget * Decimal::from(*t) / Decimal::ONE_HUNDRED;
+      before < at && at <= after
+    })
+    .collect::<Vec<i16>>();
+  if crossed.is_empty() {
+    return Ok(());
+  }
+  tracing::warn!(
+    "Campaign {} committed {} of its {} budget",
+    campaign_id,
+    after,
+    budget
+  );
+
+  query!(
+    "INSERT INTO campaign_budget_warning (
+      org_id,
+      campaign_id,
+      threshold,
+      budget,
+      committed
+    )
+    SELECT $1, $2, t, $4, $5
+    FROM UNNEST($3::SMALLINT[]) t",
+    b.org_id,
+    campaign_id,
+    &crossed,
+    budget,
+    after
+  )
+  .execute(&mut *conn)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(())
+}
+
+// list the budget warnings of a campaign, latest first
+pub async fn list_warnings(
+  db: &PgPool,
+  org_id: Uuid,
+  campaign_id: Uuid,
+) -> Result<Vec<BudgetWarning>, Error> {
+  query_as!(
+    BudgetWarning,
+    "SELECT
+      threshold,
+      budget,
+      committed,
+      created_at
+    FROM campaign_budget_warning
+    WHERE org_id = $1
+      AND campaign_id = $2
+    ORDER BY created_at DESC, threshold DESC",
+    org_id,
+    campaign_id
+  )
+  .fetch_all(db)
+  .await
+  .map_err(handle_pg_error)
+}
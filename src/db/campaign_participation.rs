@@ -25,7 +25,7 @@ pub async fn list(
       campaign_id,
       user_id,
       SUM(balance)::BIGINT AS "balance!",
-      SUM(value)::BIGINT AS "point!"
+      COALESCE(SUM(value) FILTER (WHERE voided_at IS NULL AND transfer_id IS NULL), 0)::BIGINT AS "point!"
     FROM voucher
     WHERE user_id = $1
       AND campaign_id = $2
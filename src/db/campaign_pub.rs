@@ -6,7 +6,7 @@ use uuid::Uuid;
 use crate::db::sqlx_macro::{maybe_bind, offset_limit};
 
 use super::{
-  campaign::{CampaignFilter, Task},
+  campaign::{CampaignFilter, CampaignLock, Rule, Task},
   handle_pg_error, maybe_order_by, Error,
 };
 
@@ -26,6 +26,10 @@ pub struct PubCampaign {
   pub tasks: Json<Vec<Task>>,
   pub start_at: Option<NaiveDate>,
   pub end_at: Option<NaiveDate>,
+  pub max_participants: Option<i64>,
+  pub allowed_countries: Option<Vec<i16>>,
+  pub blocked_countries: Option<Vec<i16>>,
+  pub eligibility: Json<Vec<Rule>>,
   pub created_at: DateTime<Utc>,
   pub updated_at: Option<DateTime<Utc>>,
 }
@@ -53,6 +57,10 @@ pub async fn list(
       tasks,
       start_at,
       end_at,
+      max_participants,
+      allowed_countries,
+      blocked_countries,
+      eligibility,
       created_at,
       updated_at
     FROM campaign"#,
@@ -91,16 +99,21 @@ pub async fn list(
   )?;
   offset_limit!(query, p.offset, p.limit);
 
-  query
+  let mut res: Vec<PubCampaign> = query
     .build_query_as()
     .fetch_all(db)
     .await
-    .map_err(handle_pg_error)
+    .map_err(handle_pg_error)?;
+
+  res
+    .iter_mut()
+    .for_each(|c| c.tasks.iter_mut().for_each(|t| t.kind.redact()));
+  Ok(res)
 }
 
 // get a campaign
 pub async fn get(db: &PgPool, campaign_id: Uuid) -> Result<PubCampaign, Error> {
-  query_as!(
+  let mut res = query_as!(
     PubCampaign,
     r#"SELECT
       org_id,
@@ -117,6 +130,10 @@ pub async fn get(db: &PgPool, campaign_id: Uuid) -> Result<PubCampaign, Error> {
       tasks AS "tasks: Json<Vec<Task>>",
       start_at,
       end_at,
+      max_participants,
+      allowed_countries,
+      blocked_countries,
+      eligibility AS "eligibility: Json<Vec<Rule>>",
       created_at,
       updated_at
     FROM campaign
@@ -128,7 +145,10 @@ pub async fn get(db: &PgPool, campaign_id: Uuid) -> Result<PubCampaign, Error> {
   )
   .fetch_one(db)
   .await
-  .map_err(handle_pg_error)
+  .map_err(handle_pg_error)?;
+
+  res.tasks.iter_mut().for_each(|t| t.kind.redact());
+  Ok(res)
 }
 
 #[derive(FromRow, Serialize)]
@@ -149,19 +169,71 @@ pub async fn get_org_id(db: &PgPool, campaign_id: Uuid) -> Result<GetOrgIdResult
 }
 
 #[derive(FromRow, Serialize)]
-pub struct GetRelationIdResult {
+pub struct CampaignTasks {
   pub org_id: Uuid,
   pub project_id: Uuid,
+  pub chain_id: i64,
+  pub contract_address: String,
+  pub start_at: Option<NaiveDate>,
+  pub end_at: Option<NaiveDate>,
+  pub max_participants: Option<i64>,
+  pub allowed_countries: Option<Vec<i16>>,
+  pub blocked_countries: Option<Vec<i16>>,
+  pub eligibility: Json<Vec<Rule>>,
+  /// Refuse second wallets of a participant and proofs reused from other participants.
+  pub block_fraud: bool,
+  pub tasks: Json<Vec<Task>>,
 }
 
-// get a campaign's org id
-pub async fn get_relation_ids(
-  db: &PgPool,
-  campaign_id: Uuid,
-) -> Result<GetRelationIdResult, Error> {
+impl CampaignTasks {
+  /// Check whether the campaign is running and open to participants from `country_id`.
+  /// Like the public listing, a campaign ends as its `end_at` day begins.
+  pub fn lock(&self, country_id: Option<i16>) -> Option<CampaignLock> {
+    let today = Utc::now().date_naive();
+    if let Some(start_at) = self.start_at.filter(|d| *d > today) {
+      return Some(CampaignLock::NotStarted { start_at });
+    }
+    if let Some(end_at) = self.end_at.filter(|d| *d <= today) {
+      return Some(CampaignLock::Ended { end_at });
+    }
+
+    let allowed = self.allowed_countries.as_ref().filter(|c| !c.is_empty());
+    let blocked = self.blocked_countries.as_ref().filter(|c| !c.is_empty());
+    if allowed.is_none() && blocked.is_none() {
+      return None;
+    }
+    match country_id {
+      None => Some(CampaignLock::CountryRequired),
+      Some(id)
+        if allowed.is_some_and(|c| !c.contains(&id))
+          || blocked.is_some_and(|c| c.contains(&id)) =>
+      {
+        Some(CampaignLock::CountryNotAllowed { country_id: id })
+      }
+      _ => None,
+    }
+  }
+}
+
+// get a campaign's tasks along with what's needed to verify them
+pub async fn get_tasks(db: &PgPool, campaign_id: Uuid) -> Result<CampaignTasks, Error> {
   query_as!(
-    GetRelationIdResult,
-    r#"SELECT org_id, project_id FROM campaign WHERE id = $1"#,
+    CampaignTasks,
+    r#"SELECT
+      org_id,
+      project_id,
+      chain_id,
+      contract_address,
+      start_at,
+      end_at,
+      max_participants,
+      allowed_countries,
+      blocked_countries,
+      eligibility AS "eligibility: Json<Vec<Rule>>",
+      block_fraud,
+      tasks AS "tasks: Json<Vec<Task>>"
+    FROM campaign
+    WHERE id = $1"#,
     campaign_id
   )
   .fetch_one(db)
@@ -7,12 +7,20 @@ use crate::db::sqlx_macro::{must_bind, maybe_bind, offset_limit};
 
 use super::{handle_pg_error, maybe_order_by, Error};
 
+/// A coupon of the user, `received_from` is set when it was handed over to them and
+/// `pending_transfer_id` while a claim link for it is open.
+///
+/// Nothing records redemption yet, so a coupon with `received_from` may already have been
+/// used through its `url` by a previous owner.
 #[derive(FromRow, Serialize)]
 pub struct Coupon {
   pub reward_id: Uuid,
   pub number: i64,
   pub url: String,
   pub minted_at: Option<DateTime<Utc>>,
+  pub redeemed_at: Option<DateTime<Utc>>,
+  pub received_from: Option<String>,
+  pub pending_transfer_id: Option<Uuid>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -33,8 +41,27 @@ pub async fn list(
       reward_id,
       number,
       url,
-      minted_at
-    FROM coupon"#,
+      minted_at,
+      redeemed_at,
+      (
+        SELECT sender_id
+        FROM coupon_transfer
+        WHERE reward_id = c.reward_id
+          AND number = c.number
+          AND claimed_by = c.user_id
+        ORDER BY claimed_at DESC
+        LIMIT 1
+      ) AS received_from,
+      (
+        SELECT id
+        FROM coupon_transfer
+        WHERE reward_id = c.reward_id
+          AND number = c.number
+          AND claimed_at IS NULL
+          AND cancelled_at IS NULL
+          AND expires_at > NOW()
+      ) AS pending_transfer_id
+    FROM coupon c"#,
   );
 
   query.push(" WHERE ");
@@ -67,8 +94,27 @@ pub async fn get(
       reward_id,
       number,
       url,
-      minted_at
-    FROM coupon
+      minted_at,
+      redeemed_at,
+      (
+        SELECT sender_id
+        FROM coupon_transfer
+        WHERE reward_id = c.reward_id
+          AND number = c.number
+          AND claimed_by = c.user_id
+        ORDER BY claimed_at DESC
+        LIMIT 1
+      ) AS received_from,
+      (
+        SELECT id
+        FROM coupon_transfer
+        WHERE reward_id = c.reward_id
+          AND number = c.number
+          AND claimed_at IS NULL
+          AND cancelled_at IS NULL
+          AND expires_at > NOW()
+      ) AS pending_transfer_id
+    FROM coupon c
     WHERE reward_id = $1
       AND number = $2
       AND user_id = $3"#,
@@ -0,0 +1,359 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{prelude::FromRow, query, query_as, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::{handle_pg_error, CreateResult, Error, UpdateResult};
+
+/// A minted coupon handed over by its owner, to `recipient_id` directly or to whoever claims
+/// the link first.
+#[derive(FromRow, Serialize, Debug)]
+pub struct CouponTransfer {
+  pub id: Uuid,
+  pub reward_id: Uuid,
+  pub number: i64,
+  pub sender_id: String,
+  pub recipient_id: Option<String>,
+  pub expires_at: Option<DateTime<Utc>>,
+  pub claimed_by: Option<String>,
+  pub claimed_at: Option<DateTime<Utc>>,
+  pub cancelled_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: Option<DateTime<Utc>>,
+}
+
+// lock a coupon of a user, refusing to hand it over once redeemed
+async fn lock_in(
+  tx: &mut Transaction<'_, Postgres>,
+  reward_id: Uuid,
+  number: i64,
+  user_id: &str,
+) -> Result<(), Error> {
+  let coupon = query!(
+    "SELECT redeemed_at
+    FROM coupon
+    WHERE reward_id = $1
+      AND number = $2
+      AND user_id = $3
+    FOR UPDATE",
+    reward_id,
+    number,
+    user_id
+  )
+  .fetch_one(&mut **tx)
+  .await
+  .map_err(handle_pg_error)?;
+  if coupon.redeemed_at.is_some() {
+    return Err(Error::Redeemed);
+  }
+
+  Ok(())
+}
+
+// cancel the claim links of a coupon still open, handing it over again supersedes them
+async fn cancel_open_in(
+  tx: &mut Transaction<'_, Postgres>,
+  reward_id: Uuid,
+  number: i64,
+) -> Result<(), Error> {
+  query!(
+    "UPDATE coupon_transfer
+    SET
+      cancelled_at = NOW(),
+      updated_at = NOW()
+    WHERE reward_id = $1
+      AND number = $2
+      AND claimed_at IS NULL
+      AND cancelled_at IS NULL",
+    reward_id,
+    number
+  )
+  .execute(&mut **tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(())
+}
+
+// give a coupon to its new owner
+async fn move_in(
+  tx: &mut Transaction<'_, Postgres>,
+  reward_id: Uuid,
+  number: i64,
+  user_id: &str,
+) -> Result<(), Error> {
+  query!(
+    "UPDATE coupon
+    SET
+      user_id = $3,
+      updated_at = NOW()
+    WHERE reward_id = $1
+      AND number = $2",
+    reward_id,
+    number,
+    user_id
+  )
+  .execute(&mut **tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(())
+}
+
+pub struct CreateParam<'a> {
+  pub id: Uuid,
+  pub reward_id: Uuid,
+  pub number: i64,
+  pub sender_id: &'a str,
+  pub token_hash: &'a str,
+  pub expires_at: DateTime<Utc>,
+}
+
+// create a one-time link to claim a coupon, replacing the one open before
+pub async fn create_link(db: &PgPool, p: CreateParam<'_>) -> Result<CreateResult, Error> {
+  let mut tx = db.begin().await?;
+  lock_in(&mut tx, p.reward_id, p.number, p.sender_id).await?;
+  cancel_open_in(&mut tx, p.reward_id, p.number).await?;
+
+  let res = query_as!(
+    CreateResult,
+    "INSERT INTO coupon_transfer (
+      id,
+      reward_id,
+      number,
+      sender_id,
+      token_hash,
+      expires_at
+    )
+    VALUES ($1, $2, $3, $4, $5, $6)
+    RETURNING created_at",
+    p.id,
+    p.reward_id,
+    p.number,
+    p.sender_id,
+    p.token_hash,
+    p.expires_at
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+  tx.commit().await?;
+
+  Ok(res)
+}
+
+// hand a coupon over to a user right away, they must have engaged somewhere before
+pub async fn give(
+  db: &PgPool,
+  id: Uuid,
+  reward_id: Uuid,
+  number: i64,
+  sender_id: &str,
+  recipient_id: &str,
+) -> Result<CouponTransfer, Error> {
+  if sender_id == recipient_id {
+    return Err(Error::Validation);
+  }
+
+  let mut tx = db.begin().await?;
+  lock_in(&mut tx, reward_id, number, sender_id).await?;
+
+  let known = query!(
+    r#"SELECT EXISTS (
+      SELECT 1 FROM engage WHERE user_id = $1
+    ) AS "known!""#,
+    recipient_id
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?
+  .known;
+  if !known {
+    return Err(Error::NotFound);
+  }
+
+  cancel_open_in(&mut tx, reward_id, number).await?;
+  move_in(&mut tx, reward_id, number, recipient_id).await?;
+
+  let res = query_as!(
+    CouponTransfer,
+    "INSERT INTO coupon_transfer (
+      id,
+      reward_id,
+      number,
+      sender_id,
+      recipient_id,
+      claimed_by,
+      claimed_at
+    )
+    VALUES ($1, $2, $3, $4, $5, $5, NOW())
+    RETURNING
+      id,
+      reward_id,
+      number,
+      sender_id,
+      recipient_id,
+      expires_at,
+      claimed_by,
+      claimed_at,
+      cancelled_at,
+      created_at,
+      updated_at",
+    id,
+    reward_id,
+    number,
+    sender_id,
+    recipient_id
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+  tx.commit().await?;
+
+  Ok(res)
+}
+
+// claim a coupon through its link, as long as the sender still holds it unredeemed
+pub async fn claim(
+  db: &PgPool,
+  transfer_id: Uuid,
+  token_hash: &str,
+  user_id: &str,
+) -> Result<CouponTransfer, Error> {
+  let mut tx = db.begin().await?;
+
+  let transfer = query!(
+    "SELECT
+      reward_id,
+      number,
+      sender_id
+    FROM coupon_transfer
+    WHERE id = $1
+      AND token_hash = $2
+      AND claimed_at IS NULL
+      AND cancelled_at IS NULL
+      AND expires_at > NOW()
+    FOR UPDATE",
+    transfer_id,
+    token_hash
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+  if transfer.sender_id == user_id {
+    return Err(Error::Validation);
+  }
+
+  lock_in(
+    &mut tx,
+    transfer.reward_id,
+    transfer.number,
+    &transfer.sender_id,
+  )
+  .await?;
+  move_in(&mut tx, transfer.reward_id, transfer.number, user_id).await?;
+
+  let res = query_as!(
+    CouponTransfer,
+    "UPDATE coupon_transfer
+    SET
+      claimed_by = $2,
+      claimed_at = NOW(),
+      updated_at = NOW()
+    WHERE id = $1
+    RETURNING
+      id,
+      reward_id,
+      number,
+      sender_id,
+      recipient_id,
+      expires_at,
+      claimed_by,
+      claimed_at,
+      cancelled_at,
+      created_at,
+      updated_at",
+    transfer_id,
+    user_id
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+  tx.commit().await?;
+
+  Ok(res)
+}
+
+// cancel a claim link before anyone claims it
+pub async fn cancel(
+  db: &PgPool,
+  reward_id: Uuid,
+  number: i64,
+  transfer_id: Uuid,
+  sender_id: &str,
+) -> Result<UpdateResult, Error> {
+  query_as!(
+    UpdateResult,
+    r#"UPDATE coupon_transfer
+    SET
+      cancelled_at = NOW(),
+      updated_at = NOW()
+    WHERE id = $1
+      AND reward_id = $2
+      AND number = $3
+      AND sender_id = $4
+      AND claimed_at IS NULL
+      AND cancelled_at IS NULL
+    RETURNING updated_at AS "updated_at!""#,
+    transfer_id,
+    reward_id,
+    number,
+    sender_id
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)
+}
+
+// list the transfers of a coupon, to its owner and those who handed it over or received it
+pub async fn list(
+  db: &PgPool,
+  reward_id: Uuid,
+  number: i64,
+  user_id: &str,
+) -> Result<Vec<CouponTransfer>, Error> {
+  query_as!(
+    CouponTransfer,
+    "SELECT
+      id,
+      reward_id,
+      number,
+      sender_id,
+      recipient_id,
+      expires_at,
+      claimed_by,
+      claimed_at,
+      cancelled_at,
+      created_at,
+      updated_at
+    FROM coupon_transfer t
+    WHERE reward_id = $1
+      AND number = $2
+      AND (sender_id = $3
+        OR claimed_by = $3
+        OR EXISTS (
+          SELECT 1
+          FROM coupon
+          WHERE reward_id = t.reward_id
+            AND number = t.number
+            AND user_id = $3
+        ))
+    ORDER BY created_at DESC",
+    reward_id,
+    number,
+    user_id
+  )
+  .fetch_all(db)
+  .await
+  .map_err(handle_pg_error)
+}
@@ -1,14 +1,22 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 use chrono::{DateTime, Utc};
 use is_empty::IsEmpty;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use sqlx::{query, query_as, types::Json, PgPool, Postgres, QueryBuilder, Row};
+use serde_with::{serde_as, DisplayFromStr};
+use sqlx::{
+  postgres::PgRow, query, query_as, types::Json, PgPool, Postgres, QueryBuilder, Row, Transaction,
+};
 use uuid::Uuid;
 
 use crate::db::{
   campaign::Task,
+  campaign_budget,
+  engage_review::{self, Claim, Reviewer},
+  point_multiplier,
   sqlx_macro::{maybe_bind, offset_limit},
+  voucher,
 };
 
 use super::{
@@ -17,6 +25,8 @@ use super::{
 
 pub type Accepted = HashMap<String, bool>;
 pub type Messages = HashMap<String, String>;
+/// Number of times each task was rejected.
+pub type Rejections = HashMap<String, i64>;
 pub type Submissions = HashMap<String, Submission>;
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
@@ -32,6 +42,7 @@ pub struct Engage {
   pub submissions: Json<Submissions>,
   pub accepted: Json<Accepted>,
   pub messages: Json<Messages>,
+  pub rejections: Json<Rejections>,
   pub coupon_issue_id: Option<String>,
   pub coupon_serial: Option<String>,
   pub coupon_url: Option<String>,
@@ -52,6 +63,7 @@ pub struct PubEngage {
   pub submissions: Json<Submissions>,
   pub accepted: Json<Accepted>,
   pub messages: Json<Messages>,
+  pub rejections: Json<Rejections>,
   pub coupon_issue_id: Option<String>,
   pub coupon_serial: Option<String>,
   pub coupon_url: Option<String>,
@@ -65,6 +77,34 @@ pub struct Submission {
   pub message: Option<String>,
   pub link: Option<String>,
   pub images: Option<Vec<String>>,
+  pub tx_hash: Option<String>,
+  /// Quiz answers by question id.
+  pub answers: Option<HashMap<String, String>>,
+  /// Set by the server, the number of times a quiz was attempted.
+  pub attempts: Option<u32>,
+  /// Signer address of the participant who referred this one.
+  pub referrer: Option<String>,
+  /// Set by the server when it checks the submission itself.
+  pub verification: Option<Verification>,
+}
+
+impl Submission {
+  /// Links and images submitted as proof.
+  pub fn proof_urls(&self) -> Vec<String> {
+    self
+      .link
+      .iter()
+      .chain(self.images.iter().flatten())
+      .cloned()
+      .collect()
+  }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Verification {
+  pub passed: bool,
+  pub detail: Option<String>,
+  pub checked_at: DateTime<Utc>,
 }
 
 pub async fn get(
@@ -86,6 +126,7 @@ pub async fn get(
       submissions AS "submissions: Json<Submissions>",
       accepted AS "accepted: Json<Accepted>",
       messages AS "messages: Json<Messages>",
+      rejections AS "rejections: Json<Rejections>",
       coupon_issue_id,
       coupon_serial,
       coupon_url,
@@ -118,6 +159,7 @@ pub async fn get(
     submissions: res.submissions,
     accepted: res.accepted,
     messages: res.messages,
+    rejections: res.rejections,
     coupon_issue_id: res.coupon_issue_id,
     coupon_serial: res.coupon_serial,
     coupon_url: res.coupon_url,
@@ -141,7 +183,30 @@ pub struct ListParams {
   pub limit: u64,
 }
 
-pub async fn list(db: &PgPool, p: ListParams) -> Result<Vec<Engage>, Error> {
+/// A sign an engagement may be farmed.
+#[derive(Serialize, PartialEq, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FraudFlag {
+  /// The participant also joined the campaign with other wallets.
+  MultiWallet { signer_addresses: Vec<String> },
+  /// Links or images other participants of the campaign submitted too.
+  DuplicateProof { urls: Vec<String> },
+  /// The participant made many submissions, across campaigns, shortly before their last one.
+  Burst { submissions: i64 },
+}
+
+/// Submissions within this long of a participant's last activity count towards a burst.
+const BURST_WINDOW_SECS: f64 = 10.0 * 60.0;
+const BURST_SUBMISSIONS: i64 = 10;
+
+#[derive(Serialize)]
+pub struct FlaggedEngage {
+  #[serde(flatten)]
+  pub engage: Engage,
+  pub flags: Vec<FraudFlag>,
+}
+
+pub async fn list(db: &PgPool, p: ListParams) -> Result<Vec<FlaggedEngage>, Error> {
   let mut query = QueryBuilder::<Postgres>::new(
     r#"SELECT
       org_id,
@@ -154,12 +219,46 @@ pub async fn list(db: &PgPool, p: ListParams) -> Result<Vec<Engage>, Error> {
       submissions,
       accepted,
       messages,
+      rejections,
       coupon_issue_id,
       coupon_serial,
       coupon_url,
       country_id,
       created_at,
-      updated_at
+      updated_at,
+      ARRAY(
+        SELECT o.signer_address
+        FROM engage o
+        WHERE o.campaign_id = engage.campaign_id
+          AND o.user_id = engage.user_id
+          AND (o.chain_id, o.signer_address) != (engage.chain_id, engage.signer_address)
+        ORDER BY o.signer_address
+      ) AS other_wallets,
+      ARRAY(
+        SELECT u
+        FROM engage_proof_urls(engage.submissions) u
+        WHERE EXISTS (
+          SELECT 1
+          FROM engage o, engage_proof_urls(o.submissions) ou
+          WHERE o.campaign_id = engage.campaign_id
+            AND o.user_id != engage.user_id
+            AND ou = u
+        )
+        ORDER BY u
+      ) AS duplicate_proofs,
+      (
+        SELECT COUNT(*)
+        FROM engage_event ev
+        WHERE ev.user_id = engage.user_id
+          AND ev.new_submissions IS DISTINCT FROM ev.old_submissions
+          AND ev.created_at <= COALESCE(engage.updated_at, engage.created_at)
+          AND ev.created_at > COALESCE(engage.updated_at, engage.created_at)
+            - make_interval(secs => "#,
+  );
+  query.push_bind(BURST_WINDOW_SECS);
+  query.push(
+    r#")
+      ) AS recent_submissions
     FROM engage
     WHERE "#,
   );
@@ -175,36 +274,170 @@ pub async fn list(db: &PgPool, p: ListParams) -> Result<Vec<Engage>, Error> {
   let res = query.build().fetch_all(db).await.map_err(handle_pg_error)?;
 
   let res = res
-    .into_iter()
-    .map(|en| {
-      let campaign_id = en.get("campaign_id");
-      let chain_id = en.get("chain_id");
-      let signer_address: String = en.get("signer_address");
-      Engage {
-        id: format!("{}/{}/{}", campaign_id, chain_id, signer_address),
-        org_id: en.get("org_id"),
-        project_id: en.get("project_id"),
-        campaign_id,
-        chain_id,
-        signer_address: signer_address.to_owned(),
-        user_id: en.get("user_id"),
-        user_name: en.get("user_name"),
-        submissions: en.get("submissions"),
-        accepted: en.get("accepted"),
-        messages: en.get("messages"),
-        coupon_issue_id: en.get("coupon_issue_id"),
-        coupon_serial: en.get("coupon_serial"),
-        coupon_url: en.get("coupon_url"),
-        country_id: en.get("country_id"),
-        created_at: en.get("created_at"),
-        updated_at: en.get("updated_at"),
-      }
+    .iter()
+    .map(|en| FlaggedEngage {
+      engage: from_row(en),
+      flags: fraud_flags(en),
     })
-    .collect::<Vec<Engage>>();
+    .collect::<Vec<FlaggedEngage>>();
 
   Ok(res)
 }
 
+fn fraud_flags(en: &PgRow) -> Vec<FraudFlag> {
+  let mut flags = vec![];
+  let other_wallets: Vec<String> = en.get("other_wallets");
+  if !other_wallets.is_empty() {
+    flags.push(FraudFlag::MultiWallet {
+      signer_addresses: other_wallets,
+    });
+  }
+  let duplicate_proofs: Vec<String> = en.get("duplicate_proofs");
+  if !duplicate_proofs.is_empty() {
+    flags.push(FraudFlag::DuplicateProof {
+      urls: duplicate_proofs,
+    });
+  }
+  let recent_submissions: i64 = en.get("recent_submissions");
+  if recent_submissions >= BURST_SUBMISSIONS {
+    flags.push(FraudFlag::Burst {
+      submissions: recent_submissions,
+    });
+  }
+  flags
+}
+
+fn from_row(en: &PgRow) -> Engage {
+  let campaign_id = en.get("campaign_id");
+  let chain_id = en.get("chain_id");
+  let signer_address: String = en.get("signer_address");
+  Engage {
+    id: format!("{}/{}/{}", campaign_id, chain_id, signer_address),
+    org_id: en.get("org_id"),
+    project_id: en.get("project_id"),
+    campaign_id,
+    chain_id,
+    signer_address: signer_address.to_owned(),
+    user_id: en.get("user_id"),
+    user_name: en.get("user_name"),
+    submissions: en.get("submissions"),
+    accepted: en.get("accepted"),
+    messages: en.get("messages"),
+    rejections: en.get("rejections"),
+    coupon_issue_id: en.get("coupon_issue_id"),
+    coupon_serial: en.get("coupon_serial"),
+    coupon_url: en.get("coupon_url"),
+    country_id: en.get("country_id"),
+    created_at: en.get("created_at"),
+    updated_at: en.get("updated_at"),
+  }
+}
+
+#[serde_as]
+#[derive(Deserialize, Clone, Debug)]
+pub struct PendingFilter {
+  pub campaign_id: Option<Uuid>,
+  pub task_id: Option<String>,
+  pub assigned_to: Option<String>,
+  /// Whether someone holds a live claim on the engagement.
+  #[serde_as(as = "Option<DisplayFromStr>")]
+  #[serde(default)]
+  pub claimed: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct PendingEngage {
+  #[serde(flatten)]
+  pub engage: Engage,
+  /// Submitted tasks waiting for a review.
+  pub pending: Vec<String>,
+  #[serde(flatten)]
+  pub claim: Claim,
+}
+
+// list engagements with submitted tasks that were neither accepted nor rejected, oldest first
+pub async fn list_pending(
+  db: &PgPool,
+  org_id: Uuid,
+  p: super::ListParams<PendingFilter>,
+) -> Result<Vec<PendingEngage>, Error> {
+  let mut query = QueryBuilder::<Postgres>::new(
+    r#"SELECT
+      org_id,
+      project_id,
+      campaign_id,
+      chain_id,
+      signer_address,
+      user_id,
+      user_name,
+      submissions,
+      accepted,
+      messages,
+      rejections,
+      coupon_issue_id,
+      coupon_serial,
+      coupon_url,
+      country_id,
+      created_at,
+      updated_at,
+      assigned_to,
+      claimed_by,
+      claimed_until,
+      ARRAY(
+        SELECT k
+        FROM jsonb_object_keys(submissions) k
+        WHERE NOT accepted ? k
+        ORDER BY k
+      ) AS pending
+    FROM engage
+    WHERE "#,
+  );
+  let mut sep = query.separated(" AND ");
+  must_bind!(sep, "org_id" = org_id);
+  maybe_bind!(sep, "campaign_id" = p.filter.campaign_id);
+  maybe_bind!(sep, "assigned_to" = p.filter.assigned_to);
+  match p.filter.claimed {
+    Some(true) => {
+      sep.push("claimed_by IS NOT NULL AND claimed_until > NOW()");
+    }
+    Some(false) => {
+      sep.push("(claimed_by IS NULL OR claimed_until <= NOW())");
+    }
+    None => {}
+  }
+  match p.filter.task_id {
+    Some(task_id) => {
+      sep
+        .push("submissions ? ")
+        .push_bind_unseparated(task_id.clone())
+        .push_unseparated(" AND NOT accepted ? ")
+        .push_bind_unseparated(task_id);
+    }
+    None => {
+      sep.push("EXISTS (SELECT 1 FROM jsonb_object_keys(submissions) k WHERE NOT accepted ? k)");
+    }
+  }
+  query.push(" ORDER BY COALESCE(updated_at, created_at) ASC");
+  offset_limit!(query, p.offset, p.limit);
+
+  let res = query.build().fetch_all(db).await.map_err(handle_pg_error)?;
+
+  Ok(
+    res
+      .iter()
+      .map(|en| PendingEngage {
+        engage: from_row(en),
+        pending: en.get("pending"),
+        claim: Claim {
+          assigned_to: en.get("assigned_to"),
+          claimed_by: en.get("claimed_by"),
+          claimed_until: en.get("claimed_until"),
+        },
+      })
+      .collect(),
+  )
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct CreateParam<'a> {
   pub org_id: Uuid,
@@ -215,10 +448,35 @@ pub struct CreateParam<'a> {
   pub user_id: &'a str,
   pub user_name: Option<String>,
   pub submissions: Submissions,
+  pub country_id: Option<i16>,
+  pub max_participants: Option<i64>,
 }
 
-// create an engagement
+// create an engagement, refusing it once the campaign has max_participants
 pub async fn create<'a>(db: &PgPool, p: CreateParam<'a>) -> Result<CreateResult, Error> {
+  let mut tx = db.begin().await?;
+  if let Some(max_participants) = p.max_participants {
+    // joins queue behind the campaign lock so they all see each other in the count
+    query!(
+      "SELECT id FROM campaign WHERE id = $1 FOR NO KEY UPDATE",
+      p.campaign_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(handle_pg_error)?;
+    let participants = query!(
+      r#"SELECT COUNT(*) AS "count!" FROM engage WHERE campaign_id = $1"#,
+      p.campaign_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(handle_pg_error)?
+    .count;
+    if participants >= max_participants {
+      return Err(Error::LimitReached);
+    }
+  }
+
   let res = sqlx::query_as!(
     CreateResult,
     "INSERT INTO engage (
@@ -229,7 +487,8 @@ pub async fn create<'a>(db: &PgPool, p: CreateParam<'a>) -> Result<CreateResult,
       signer_address,
       user_id,
       user_name,
-      submissions)
+      submissions,
+      country_id)
     VALUES (
       $1,
       $2,
@@ -238,7 +497,8 @@ pub async fn create<'a>(db: &PgPool, p: CreateParam<'a>) -> Result<CreateResult,
       $5,
       $6,
       $7,
-      $8)
+      $8,
+      $9)
     RETURNING created_at",
     p.org_id,
     p.project_id,
@@ -248,10 +508,12 @@ pub async fn create<'a>(db: &PgPool, p: CreateParam<'a>) -> Result<CreateResult,
     p.user_id,
     p.user_name,
     Json(&p.submissions) as _,
+    p.country_id,
   )
-  .fetch_one(db)
+  .fetch_one(&mut *tx)
   .await
   .map_err(handle_pg_error)?;
+  tx.commit().await?;
 
   _ = super::project_membership::join(db, p.project_id, p.user_id)
   .await?;
@@ -302,6 +564,110 @@ pub async fn update<'a>(
     .map_err(handle_pg_error)
 }
 
+// reserve a coupon for an engagement before asking Mezzofy for it, charging its
+// `reward_amount` to the campaign budget until it's recorded or released
+pub async fn reserve_coupon(
+  db: &PgPool,
+  org_id: Uuid,
+  campaign_id: Uuid,
+  chain_id: i64,
+  signer_address: &str,
+  amount: Decimal,
+) -> Result<(), Error> {
+  let mut tx = db.begin().await?;
+  campaign_budget::charge_in(&mut tx, campaign_id, amount).await?;
+
+  let res = query!(
+    "UPDATE engage
+    SET
+      coupon_pending_at = NOW(),
+      updated_at = NOW()
+    WHERE org_id = $1
+      AND campaign_id = $2
+      AND chain_id = $3
+      AND signer_address = $4
+      AND coupon_pending_at IS NULL
+      AND coupon_issue_id IS NULL
+      AND coupon_serial IS NULL
+      AND coupon_url IS NULL",
+    org_id,
+    campaign_id,
+    chain_id,
+    signer_address
+  )
+  .execute(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+  if res.rows_affected() == 0 {
+    return Err(Error::Issued);
+  }
+  tx.commit().await?;
+
+  Ok(())
+}
+
+// record the coupon Mezzofy issued for a reservation
+pub async fn record_coupon(
+  db: &PgPool,
+  org_id: Uuid,
+  campaign_id: Uuid,
+  chain_id: i64,
+  signer_address: &str,
+  coupon_issue_id: &str,
+) -> Result<UpdateResult, Error> {
+  query_as!(
+    UpdateResult,
+    r#"UPDATE engage
+    SET
+      coupon_issue_id = $5,
+      coupon_pending_at = NULL,
+      updated_at = NOW()
+    WHERE org_id = $1
+      AND campaign_id = $2
+      AND chain_id = $3
+      AND signer_address = $4
+      AND coupon_pending_at IS NOT NULL
+    RETURNING updated_at AS "updated_at!""#,
+    org_id,
+    campaign_id,
+    chain_id,
+    signer_address,
+    coupon_issue_id
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)
+}
+
+// release a reservation Mezzofy didn't issue a coupon for, giving its budget back
+pub async fn release_coupon(
+  db: &PgPool,
+  org_id: Uuid,
+  campaign_id: Uuid,
+  chain_id: i64,
+  signer_address: &str,
+) -> Result<(), Error> {
+  query!(
+    "UPDATE engage
+    SET
+      coupon_pending_at = NULL,
+      updated_at = NOW()
+    WHERE org_id = $1
+      AND campaign_id = $2
+      AND chain_id = $3
+      AND signer_address = $4",
+    org_id,
+    campaign_id,
+    chain_id,
+    signer_address
+  )
+  .execute(db)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(())
+}
+
 #[derive(Deserialize)]
 pub struct ReplaceParams {
   pub coupon_issue_id: Option<String>,
@@ -310,7 +676,7 @@ pub struct ReplaceParams {
   pub country_id: Option<i16>,
 }
 
-// delete an engagement
+// delete an engagement, voiding its vouchers
 pub async fn delete(
   db: &PgPool,
   org_id: Uuid,
@@ -318,19 +684,23 @@ pub async fn delete(
   chain_id: i64,
   signer_address: String,
 ) -> Result<(), Error> {
-  match sqlx::query!(
+  let mut tx = db.begin().await?;
+  let res = sqlx::query!(
     "DELETE FROM engage WHERE org_id = $1 AND campaign_id = $2 AND chain_id = $3 AND signer_address = $4",
     org_id,
     campaign_id,
     chain_id,
     signer_address
   )
-  .execute(db)
+  .execute(&mut *tx)
   .await
-  {
-    Ok(_) => Ok(()),
-    Err(err) => Err(handle_pg_error(err)),
+  .map_err(handle_pg_error)?;
+  if res.rows_affected() > 0 {
+    voucher::revoke_in(&mut tx, campaign_id, chain_id, &signer_address, None).await?;
   }
+  tx.commit().await?;
+
+  Ok(())
 }
 
 pub async fn approve(
@@ -340,12 +710,37 @@ pub async fn approve(
   chain_id: i64,
   signer_address: String,
   accepted: Accepted,
+  reviewer: Reviewer<'_>,
 ) -> Result<UpdateResult, Error> {
   if accepted.is_empty() {
     return Err(Error::EmptyUpdateSet);
   }
   let mut tx = db.begin().await?;
+  let res = approve_in(
+    &mut tx,
+    org_id,
+    campaign_id,
+    chain_id,
+    &signer_address,
+    accepted,
+    reviewer,
+  )
+  .await?;
+  tx.commit().await?;
 
+  Ok(res)
+}
+
+// approve tasks within a transaction, issuing or revoking their vouchers
+async fn approve_in(
+  tx: &mut Transaction<'_, Postgres>,
+  org_id: Uuid,
+  campaign_id: Uuid,
+  chain_id: i64,
+  signer_address: &str,
+  accepted: Accepted,
+  reviewer: Reviewer<'_>,
+) -> Result<UpdateResult, Error> {
   let campaign = query!(
     r#"SELECT
       project_id,
@@ -354,12 +749,14 @@ pub async fn approve(
       voucher_expire_at,
       end_at
     FROM campaign
-    WHERE id = $1"#,
+    WHERE id = $1
+    FOR NO KEY UPDATE"#,
     campaign_id
   )
-  .fetch_one(&mut *tx)
+  .fetch_one(&mut **tx)
   .await
   .map_err(handle_pg_error)?;
+  engage_review::check_in(tx, org_id, campaign_id, chain_id, signer_address, reviewer).await?;
 
   let engage = query!(
     r#"SELECT
@@ -373,11 +770,48 @@ pub async fn approve(
     chain_id,
     signer_address
   )
-  .fetch_one(&mut *tx)
+  .fetch_one(&mut **tx)
   .await
   .map_err(handle_pg_error)?;
 
+  // approvals in the campaign queue behind the lock above, so completion limits hold however
+  // many submissions are pending
+  for task in campaign.tasks.0.iter().filter(|t| {
+    t.max_completions.is_some()
+      && accepted.get(&t.id) == Some(&true)
+      && engage.accepted.0.get(&t.id) != Some(&true)
+  }) {
+    let completions = query!(
+      r#"SELECT COUNT(*) AS "count!"
+      FROM engage
+      WHERE campaign_id = $1
+        AND accepted ->> $2 = 'true'"#,
+      campaign_id,
+      task.id
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(handle_pg_error)?
+    .count;
+    if task.max_completions.is_some_and(|max| completions >= max) {
+      return Err(Error::Full);
+    }
+  }
+
+  let multipliers =
+    point_multiplier::active_in(tx, campaign.project_id, campaign_id, &engage.user_id).await?;
+  let points = campaign
+    .tasks
+    .0
+    .iter()
+    .filter(|t| accepted.get(&t.id) == Some(&true) && engage.accepted.0.get(&t.id) != Some(&true))
+    .filter_map(|t| t.point.map(|p| (t, p)))
+    .map(|(t, p)| p + multipliers.bonus(&t.id, p).map_or(0, |(_, b)| b))
+    .sum::<i64>();
+  campaign_budget::charge_in(tx, campaign_id, Decimal::from(points)).await?;
+
   let num_tasks = campaign.tasks.0.len();
+  let mut issued = false;
   for task in campaign.tasks.0 {
     if let Some(amount) = task.point {
       if let Some(accept) = accepted.get(&task.id) {
@@ -394,13 +828,14 @@ pub async fn approve(
                     WHERE org_id = $1
                       AND campaign_id = $2
                       AND chain_id = $3
-                      AND signer_address = $4",
+                      AND signer_address = $4
+                      AND voided_at IS NULL",
                     org_id,
                     campaign_id,
                     chain_id,
                     signer_address,
                   )
-                  .execute(&mut *tx)
+                  .execute(&mut **tx)
                   .await
                   .map_err(handle_pg_error)?;
                 } else {
@@ -410,13 +845,14 @@ pub async fn approve(
                     WHERE org_id = $1
                       AND campaign_id = $2
                       AND chain_id = $3
-                      AND signer_address = $4",
+                      AND signer_address = $4
+                      AND voided_at IS NULL",
                     org_id,
                     campaign_id,
                     chain_id,
                     signer_address,
                   )
-                  .execute(&mut *tx)
+                  .execute(&mut **tx)
                   .await
                   .map_err(handle_pg_error)?;
                 }
@@ -433,6 +869,8 @@ pub async fn approve(
             -- 3: Reward at campain end
             -- 4: Reward on a specific date
             */
+            let (multiplier_id, bonus) = multipliers.bonus(&task.id, amount).unzip();
+            let bonus = bonus.unwrap_or_default();
             query!(
               "INSERT INTO voucher (
                 org_id,
@@ -446,6 +884,9 @@ pub async fn approve(
                 balance,
                 valid_from,
                 valid_until,
+                base,
+                bonus,
+                multiplier_id,
                 created_at
               ) VALUES (
                 $1,
@@ -459,8 +900,22 @@ pub async fn approve(
                 $9,
                 $10,
                 $11,
+                $12,
+                $13,
+                $14,
                 NOW()
-              )",
+              )
+              ON CONFLICT (campaign_id, chain_id, signer_address, task_id) DO UPDATE SET
+                value = EXCLUDED.value,
+                balance = voucher.balance + EXCLUDED.value,
+                valid_from = EXCLUDED.valid_from,
+                valid_until = EXCLUDED.valid_until,
+                base = EXCLUDED.base,
+                bonus = EXCLUDED.bonus,
+                multiplier_id = EXCLUDED.multiplier_id,
+                voided_at = NULL,
+                expired_at = NULL,
+                updated_at = NOW()",
               org_id,
               campaign.project_id,
               campaign_id,
@@ -468,37 +923,32 @@ pub async fn approve(
               signer_address,
               engage.user_id,
               task.id,
-              amount,
-              amount,
+              amount + bonus,
+              amount + bonus,
               valid_from,
-              campaign.voucher_expire_at
+              campaign.voucher_expire_at,
+              amount,
+              bonus,
+              multiplier_id
             )
-            .execute(&mut *tx)
+            .execute(&mut **tx)
             .await
             .map_err(handle_pg_error)?;
+            issued = true;
           } else {
-            query!(
-              "DELETE
-              FROM voucher 
-              WHERE campaign_id = $1
-                AND chain_id = $2
-                AND signer_address = $3
-                AND task_id = $4",
-              campaign_id,
-              chain_id,
-              signer_address,
-              task.id,
-            )
-            .execute(&mut *tx)
-            .await
-            .map_err(handle_pg_error)?;
+            let task_ids = std::slice::from_ref(&task.id);
+            voucher::revoke_in(tx, campaign_id, chain_id, signer_address, Some(task_ids)).await?;
           }
         }
       }
     }
   }
+  // points of re-approved tasks pay off their own debt first, then any other
+  if issued {
+    voucher::settle_debt_in(tx, campaign.project_id, &engage.user_id).await?;
+  }
 
-  let up_res = query_as!(
+  let res = query_as!(
     UpdateResult,
     r#"UPDATE engage
     SET
@@ -516,13 +966,222 @@ pub async fn approve(
     chain_id,
     signer_address
   )
-  .fetch_one(&mut *tx)
+  .fetch_one(&mut **tx)
   .await
-  .map_err(handle_pg_error);
+  .map_err(handle_pg_error)?;
+
+  engage_review::record_in(
+    tx,
+    org_id,
+    campaign_id,
+    chain_id,
+    signer_address,
+    reviewer,
+    &accepted,
+  )
+  .await?;
+
+  Ok(res)
+}
 
+// reject submitted tasks, leaving the reason as their message and counting the rejection
+pub async fn reject(
+  db: &PgPool,
+  org_id: Uuid,
+  campaign_id: Uuid,
+  chain_id: i64,
+  signer_address: String,
+  reasons: Messages,
+  reviewer: Reviewer<'_>,
+) -> Result<UpdateResult, Error> {
+  if reasons.is_empty() {
+    return Err(Error::EmptyUpdateSet);
+  }
+  let mut tx = db.begin().await?;
+  let res = reject_in(
+    &mut tx,
+    org_id,
+    campaign_id,
+    chain_id,
+    &signer_address,
+    reasons,
+    reviewer,
+  )
+  .await?;
   tx.commit().await?;
 
-  up_res
+  Ok(res)
+}
+
+// reject tasks within a transaction
+async fn reject_in(
+  tx: &mut Transaction<'_, Postgres>,
+  org_id: Uuid,
+  campaign_id: Uuid,
+  chain_id: i64,
+  signer_address: &str,
+  reasons: Messages,
+  reviewer: Reviewer<'_>,
+) -> Result<UpdateResult, Error> {
+  lock_campaign_in(tx, campaign_id).await?;
+  engage_review::check_in(tx, org_id, campaign_id, chain_id, signer_address, reviewer).await?;
+
+  let task_ids = reasons.keys().cloned().collect::<Vec<String>>();
+  let rejected = task_ids
+    .iter()
+    .map(|id| (id.to_owned(), false))
+    .collect::<Accepted>();
+
+  let res = query_as!(
+    UpdateResult,
+    r#"UPDATE engage
+    SET
+      updated_at = NOW(),
+      accepted = accepted || $1,
+      messages = messages || $2,
+      rejections = rejections || (
+        SELECT jsonb_object_agg(t, COALESCE((rejections ->> t)::BIGINT, 0) + 1)
+        FROM UNNEST($3::TEXT[]) t
+      )
+    WHERE org_id = $4
+      AND campaign_id = $5
+      AND chain_id = $6
+      AND signer_address = $7
+      AND submissions ?& $3
+    RETURNING
+      updated_at AS "updated_at!""#,
+    Json(&rejected) as _,
+    Json(&reasons) as _,
+    &task_ids,
+    org_id,
+    campaign_id,
+    chain_id,
+    signer_address
+  )
+  .fetch_one(&mut **tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  voucher::revoke_in(tx, campaign_id, chain_id, signer_address, Some(&task_ids)).await?;
+
+  engage_review::record_in(
+    tx,
+    org_id,
+    campaign_id,
+    chain_id,
+    signer_address,
+    reviewer,
+    &rejected,
+  )
+  .await?;
+
+  Ok(res)
+}
+
+// lock a campaign for review, decisions take the campaign lock before the engagement's to
+// avoid deadlocking with each other
+async fn lock_campaign_in(
+  tx: &mut Transaction<'_, Postgres>,
+  campaign_id: Uuid,
+) -> Result<(), Error> {
+  query!(
+    "SELECT id FROM campaign WHERE id = $1 FOR NO KEY UPDATE",
+    campaign_id
+  )
+  .fetch_one(&mut **tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(())
+}
+
+/// A reviewer's decision on one submitted task.
+#[derive(Deserialize, Debug)]
+pub struct Review {
+  pub campaign_id: Uuid,
+  pub chain_id: i64,
+  pub signer_address: String,
+  pub task_id: String,
+  #[serde(flatten)]
+  pub decision: Decision,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum Decision {
+  Approve,
+  Reject { reason: String },
+}
+
+#[derive(Serialize, Default)]
+pub struct ReviewResult {
+  pub approved: usize,
+  pub rejected: usize,
+}
+
+// apply many reviews in a single transaction, failing them all if any engagement is missing
+pub async fn review(
+  db: &PgPool,
+  org_id: Uuid,
+  reviews: Vec<Review>,
+  reviewer: Reviewer<'_>,
+) -> Result<ReviewResult, Error> {
+  if reviews.is_empty() {
+    return Err(Error::EmptyUpdateSet);
+  }
+
+  // engagements are decided in key order, after locking all their campaigns
+  let mut decisions = BTreeMap::<(Uuid, i64, String), (Accepted, Messages)>::new();
+  let mut res = ReviewResult::default();
+  for r in reviews {
+    let key = (r.campaign_id, r.chain_id, r.signer_address.to_lowercase());
+    let (accepted, reasons) = decisions.entry(key).or_default();
+    match r.decision {
+      Decision::Approve => {
+        res.approved += 1;
+        accepted.insert(r.task_id, true);
+      }
+      Decision::Reject { reason } => {
+        res.rejected += 1;
+        reasons.insert(r.task_id, reason);
+      }
+    }
+  }
+
+  let mut tx = db.begin().await?;
+  let campaign_ids = decisions.keys().map(|k| k.0).collect::<BTreeSet<Uuid>>();
+  for campaign_id in campaign_ids {
+    lock_campaign_in(&mut tx, campaign_id).await?;
+  }
+  for ((campaign_id, chain_id, signer_address), (accepted, reasons)) in decisions {
+    if !accepted.is_empty() {
+      approve_in(
+        &mut tx,
+        org_id,
+        campaign_id,
+        chain_id,
+        &signer_address,
+        accepted,
+        reviewer,
+      )
+      .await?;
+    }
+    if !reasons.is_empty() {
+      reject_in(
+        &mut tx,
+        org_id,
+        campaign_id,
+        chain_id,
+        &signer_address,
+        reasons,
+        reviewer,
+      )
+      .await?;
+    }
+  }
+  tx.commit().await?;
+
+  Ok(res)
 }
 
 #[derive(Deserialize, IsEmpty)]
@@ -577,6 +1236,7 @@ pub async fn delete_coupon_url<'a>(
       coupon_issue_id = NULL,
       coupon_serial = NULL,
       coupon_url = NULL,
+      coupon_pending_at = NULL,
       updated_at = NOW()
     WHERE ",
   );
@@ -26,6 +26,9 @@ pub struct EngageEventLog {
   pub new_accepted: Option<Json<HashMap<String, bool>>>,
   pub new_coupon_serial: Option<String>,
   pub new_coupon_url: Option<String>,
+  /// Who, or what, changed accepted tasks, see `engage_review::Reviewer`.
+  pub review_source: Option<String>,
+  pub reviewer_id: Option<String>,
   pub created_at: DateTime<Utc>,
 }
 
@@ -47,6 +50,8 @@ pub async fn list(db: &PgPool, p: ListParams<Never>) -> Result<Vec<EngageEventLo
       new_accepted,
       new_coupon_serial,
       new_coupon_url,
+      review_source,
+      reviewer_id,
       created_at
     FROM engage_event"#,
   );
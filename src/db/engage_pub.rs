@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use chrono::{DateTime, Utc};
 use is_empty::IsEmpty;
 use serde::{Deserialize, Serialize};
@@ -7,7 +9,7 @@ use uuid::Uuid;
 use crate::db::sqlx_macro::{maybe_bind, offset_limit};
 
 use super::{
-  engage::{Accepted, Messages, Submissions},
+  engage::{Accepted, Messages, Rejections, Submissions},
   handle_pg_error, maybe_order_by,
   sqlx_macro::must_bind,
   Error, UpdateResult,
@@ -25,6 +27,7 @@ pub struct PubEngage {
   pub submissions: Json<Submissions>,
   pub accepted: Json<Accepted>,
   pub messages: Json<Messages>,
+  pub rejections: Json<Rejections>,
   pub coupon_issue_id: Option<String>,
   pub coupon_serial: Option<String>,
   pub coupon_url: Option<String>,
@@ -50,6 +53,7 @@ pub async fn get(
       submissions AS "submissions: Json<Submissions>",
       accepted AS "accepted: Json<Accepted>",
       messages AS "messages: Json<Messages>",
+      rejections AS "rejections: Json<Rejections>",
       coupon_issue_id,
       coupon_serial,
       coupon_url,
@@ -79,6 +83,7 @@ pub async fn get(
     submissions: res.submissions,
     accepted: res.accepted,
     messages: res.messages,
+    rejections: res.rejections,
     coupon_issue_id: res.coupon_issue_id,
     coupon_serial: res.coupon_serial,
     coupon_url: res.coupon_url,
@@ -97,6 +102,7 @@ pub struct PubListEngage {
   pub submissions: Json<Submissions>,
   pub accepted: Json<Accepted>,
   pub messages: Json<Messages>,
+  pub rejections: Json<Rejections>,
   pub coupon_issue_id: Option<String>,
   pub coupon_serial: Option<String>,
   pub coupon_url: Option<String>,
@@ -130,6 +136,7 @@ pub async fn list(
       submissions,
       accepted,
       messages,
+      rejections,
       coupon_issue_id,
       coupon_serial,
       coupon_url,
@@ -164,6 +171,7 @@ pub async fn list(
         submissions: en.get("submissions"),
         accepted: en.get("accepted"),
         messages: en.get("messages"),
+        rejections: en.get("rejections"),
         coupon_issue_id: en.get("coupon_issue_id"),
         coupon_serial: en.get("coupon_serial"),
         coupon_url: en.get("coupon_url"),
@@ -223,10 +231,45 @@ pub async fn submit_proof(
     return Err(Error::EmptyUpdateSet);
   }
 
+  let mut tx = db.begin().await?;
+  let previous = sqlx::query!(
+    r#"SELECT submissions AS "submissions: Json<Submissions>"
+    FROM engage
+    WHERE campaign_id = $1 AND chain_id = $2 AND signer_address = $3
+    FOR UPDATE"#,
+    campaign_id,
+    chain_id,
+    signer_address
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?
+  .submissions
+  .0;
+  // attempts were counted before the row was locked, another submission may have used them
+  let attempts_used = p.iter().any(|(task_id, sub)| {
+    sub.attempts.is_some_and(|attempts| {
+      previous
+        .get(task_id)
+        .and_then(|s| s.attempts)
+        .unwrap_or_default()
+        + 1
+        != attempts
+    })
+  });
+  if attempts_used {
+    return Err(Error::LimitReached);
+  }
+
   let mut query = QueryBuilder::<Postgres>::new("UPDATE engage SET ");
   let mut sep = query.separated(", ");
   sep.push(" updated_at = NOW() ");
   must_bind!(sep, "submissions = submissions" || Json(&p));
+  // Resubmitted tasks go back to the review queue
+  sep
+    .push("accepted = accepted - ")
+    .push_bind_unseparated(p.keys().cloned().collect::<Vec<String>>())
+    .push_unseparated("::TEXT[]");
   query.push(" WHERE ");
   let mut sep = query.separated(" AND ");
   for task_id in p.keys() {
@@ -243,9 +286,189 @@ pub async fn submit_proof(
   must_bind!(sep, "signer_address" = signer_address);
   query.push(" RETURNING updated_at");
 
-  query
+  let res = query
     .build_query_as()
-    .fetch_one(db)
+    .fetch_one(&mut *tx)
     .await
-    .map_err(handle_pg_error)
+    .map_err(handle_pg_error)?;
+  tx.commit().await?;
+
+  Ok(res)
+}
+
+// count the other participants of a campaign who named a signer as their referrer
+pub async fn count_referrals(
+  db: &PgPool,
+  campaign_id: Uuid,
+  referrer: &str,
+  user_id: &str,
+) -> Result<i64, Error> {
+  let res = sqlx::query!(
+    r#"SELECT COUNT(*) AS "count!"
+    FROM engage
+    WHERE campaign_id = $1
+      AND user_id != $3
+      AND EXISTS (
+        SELECT 1
+        FROM jsonb_each(submissions) s
+        WHERE LOWER(s.value ->> 'referrer') = $2
+      )"#,
+    campaign_id,
+    referrer,
+    user_id
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(res.count)
+}
+
+// count the participants of a campaign
+pub async fn count_participants(db: &PgPool, campaign_id: Uuid) -> Result<i64, Error> {
+  let res = sqlx::query!(
+    r#"SELECT COUNT(*) AS "count!" FROM engage WHERE campaign_id = $1"#,
+    campaign_id
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(res.count)
+}
+
+// check whether a user had every task of a campaign accepted
+pub async fn has_completed(db: &PgPool, campaign_id: Uuid, user_id: &str) -> Result<bool, Error> {
+  let res = sqlx::query!(
+    r#"SELECT EXISTS (
+      SELECT 1
+      FROM engage e
+      JOIN campaign c ON c.id = e.campaign_id
+      WHERE e.campaign_id = $1
+        AND e.user_id = $2
+        AND NOT EXISTS (
+          SELECT 1
+          FROM jsonb_array_elements(c.tasks) t
+          WHERE COALESCE(e.accepted ->> (t ->> 'id'), 'false') != 'true'
+        )
+    ) AS "completed!""#,
+    campaign_id,
+    user_id
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(res.completed)
+}
+
+// count accepted participants per task in a campaign
+pub async fn count_completions(
+  db: &PgPool,
+  campaign_id: Uuid,
+) -> Result<HashMap<String, i64>, Error> {
+  let res = sqlx::query!(
+    r#"SELECT
+      a.key AS "task_id!",
+      COUNT(*) AS "count!"
+    FROM engage, jsonb_each_text(accepted) a
+    WHERE campaign_id = $1
+      AND a.value = 'true'
+    GROUP BY a.key"#,
+    campaign_id
+  )
+  .fetch_all(db)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(res.into_iter().map(|r| (r.task_id, r.count)).collect())
+}
+
+// find a wallet, other than the given one, a user joined a campaign with
+pub async fn find_other_wallet(
+  db: &PgPool,
+  campaign_id: Uuid,
+  user_id: &str,
+  chain_id: i64,
+  signer_address: &str,
+) -> Result<Option<String>, Error> {
+  let res = sqlx::query!(
+    r#"SELECT signer_address
+    FROM engage
+    WHERE campaign_id = $1
+      AND user_id = $2
+      AND (chain_id, signer_address) != ($3, $4)
+    LIMIT 1"#,
+    campaign_id,
+    user_id,
+    chain_id,
+    signer_address
+  )
+  .fetch_optional(db)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(res.map(|r| r.signer_address))
+}
+
+// find which of the given proof links and images other participants of a campaign submitted
+pub async fn find_duplicate_proofs(
+  db: &PgPool,
+  campaign_id: Uuid,
+  user_id: &str,
+  urls: &[String],
+) -> Result<Vec<String>, Error> {
+  let res = sqlx::query!(
+    r#"SELECT DISTINCT u AS "url!"
+    FROM engage, engage_proof_urls(submissions) u
+    WHERE campaign_id = $1
+      AND user_id != $2
+      AND u = ANY($3)"#,
+    campaign_id,
+    user_id,
+    urls
+  )
+  .fetch_all(db)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(res.into_iter().map(|r| r.url).collect())
+}
+
+// record the task a verified transaction counts for, false when it already counted for another
+// task or participant
+pub async fn use_tx(
+  db: &PgPool,
+  chain_id: i64,
+  tx_hash: &str,
+  campaign_id: Uuid,
+  task_id: &str,
+  user_id: &str,
+) -> Result<bool, Error> {
+  let res = sqlx::query!(
+    "INSERT INTO onchain_tx (
+      chain_id,
+      tx_hash,
+      campaign_id,
+      task_id,
+      user_id
+    )
+    VALUES ($1, $2, $3, $4, $5)
+    ON CONFLICT (chain_id, tx_hash) DO UPDATE
+    SET chain_id = EXCLUDED.chain_id
+    WHERE onchain_tx.campaign_id = EXCLUDED.campaign_id
+      AND onchain_tx.task_id = EXCLUDED.task_id
+      AND onchain_tx.user_id = EXCLUDED.user_id
+    RETURNING chain_id",
+    chain_id,
+    tx_hash,
+    campaign_id,
+    task_id,
+    user_id
+  )
+  .fetch_optional(db)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(res.is_some())
 }
@@ -0,0 +1,351 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, query, query_as, PgPool, Postgres, QueryBuilder, Transaction};
+use uuid::Uuid;
+
+use crate::db::sqlx_macro::{maybe_bind, must_bind};
+
+use super::{engage::Accepted, handle_pg_error, Error};
+
+/// Who, or what, decided on submitted tasks.
+#[derive(Clone, Copy, Debug)]
+pub enum Reviewer<'a> {
+  /// An org member, held to the claims on the engagement.
+  Member(&'a str),
+  /// An org's server, through its API key.
+  ApiKey,
+  /// The server, having checked the submission itself.
+  Verified,
+  /// The task's auto-approval rules.
+  Rule,
+}
+
+impl<'a> Reviewer<'a> {
+  pub fn source(&self) -> &'static str {
+    match self {
+      Reviewer::Member(_) => "member",
+      Reviewer::ApiKey => "api_key",
+      Reviewer::Verified => "verified",
+      Reviewer::Rule => "rule",
+    }
+  }
+
+  pub fn member_id(&self) -> Option<&'a str> {
+    match self {
+      Reviewer::Member(id) => Some(id),
+      _ => None,
+    }
+  }
+}
+
+/// Who is reviewing an engagement, the member it was assigned to and whoever claimed it last.
+#[derive(Serialize, Debug)]
+pub struct Claim {
+  pub assigned_to: Option<String>,
+  pub claimed_by: Option<String>,
+  pub claimed_until: Option<DateTime<Utc>>,
+}
+
+impl Claim {
+  /// Whether the engagement is assigned to, or under a live claim by, someone other than `reviewer`.
+  pub fn held_by_other(&self, reviewer: &str) -> bool {
+    let assigned = matches!(&self.assigned_to, Some(a) if a != reviewer);
+    let claimed = match (&self.claimed_by, self.claimed_until) {
+      (Some(c), Some(until)) => c != reviewer && until > Utc::now(),
+      _ => false,
+    };
+    assigned || claimed
+  }
+}
+
+// lock an engagement and read its claim
+async fn get_for_update(
+  tx: &mut Transaction<'_, Postgres>,
+  org_id: Uuid,
+  campaign_id: Uuid,
+  chain_id: i64,
+  signer_address: &str,
+) -> Result<Claim, Error> {
+  query_as!(
+    Claim,
+    "SELECT
+      assigned_to,
+      claimed_by,
+      claimed_until
+    FROM engage
+    WHERE org_id = $1
+      AND campaign_id = $2
+      AND chain_id = $3
+      AND signer_address = $4
+    FOR UPDATE",
+    org_id,
+    campaign_id,
+    chain_id,
+    signer_address
+  )
+  .fetch_one(&mut **tx)
+  .await
+  .map_err(handle_pg_error)
+}
+
+// claim an engagement for review until `claimed_until`, renewing the reviewer's own claim
+pub async fn claim(
+  db: &PgPool,
+  org_id: Uuid,
+  campaign_id: Uuid,
+  chain_id: i64,
+  signer_address: &str,
+  reviewer: &str,
+  claimed_until: DateTime<Utc>,
+) -> Result<Claim, Error> {
+  let mut tx = db.begin().await?;
+  let claim = get_for_update(&mut tx, org_id, campaign_id, chain_id, signer_address).await?;
+  if claim.held_by_other(reviewer) {
+    return Err(Error::Claimed);
+  }
+
+  let res = query_as!(
+    Claim,
+    "UPDATE engage
+    SET
+      claimed_by = $5,
+      claimed_until = $6
+    WHERE org_id = $1
+      AND campaign_id = $2
+      AND chain_id = $3
+      AND signer_address = $4
+    RETURNING
+      assigned_to,
+      claimed_by,
+      claimed_until",
+    org_id,
+    campaign_id,
+    chain_id,
+    signer_address,
+    reviewer,
+    claimed_until
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+  tx.commit().await?;
+
+  Ok(res)
+}
+
+// release the claim on an engagement, only the reviewer holding it may unless `reviewer` is None
+pub async fn release(
+  db: &PgPool,
+  org_id: Uuid,
+  campaign_id: Uuid,
+  chain_id: i64,
+  signer_address: &str,
+  reviewer: Option<&str>,
+) -> Result<Claim, Error> {
+  let mut tx = db.begin().await?;
+  let claim = get_for_update(&mut tx, org_id, campaign_id, chain_id, signer_address).await?;
+  if let (Some(reviewer), Some(claimed_by)) = (reviewer, &claim.claimed_by) {
+    if claimed_by != reviewer && claim.claimed_until.is_some_and(|until| until > Utc::now()) {
+      return Err(Error::Claimed);
+    }
+  }
+
+  let res = query_as!(
+    Claim,
+    "UPDATE engage
+    SET
+      claimed_by = NULL,
+      claimed_until = NULL
+    WHERE org_id = $1
+      AND campaign_id = $2
+      AND chain_id = $3
+      AND signer_address = $4
+    RETURNING
+      assigned_to,
+      claimed_by,
+      claimed_until",
+    org_id,
+    campaign_id,
+    chain_id,
+    signer_address
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+  tx.commit().await?;
+
+  Ok(res)
+}
+
+// assign an engagement to a member, dropping claims held by anyone else
+pub async fn assign(
+  db: &PgPool,
+  org_id: Uuid,
+  campaign_id: Uuid,
+  chain_id: i64,
+  signer_address: &str,
+  assignee: Option<&str>,
+) -> Result<Claim, Error> {
+  query_as!(
+    Claim,
+    "UPDATE engage
+    SET
+      assigned_to = $5,
+      claimed_by = CASE WHEN $5::TEXT IS NULL OR claimed_by = $5 THEN claimed_by END,
+      claimed_until = CASE WHEN $5::TEXT IS NULL OR claimed_by = $5 THEN claimed_until END
+    WHERE org_id = $1
+      AND campaign_id = $2
+      AND chain_id = $3
+      AND signer_address = $4
+    RETURNING
+      assigned_to,
+      claimed_by,
+      claimed_until",
+    org_id,
+    campaign_id,
+    chain_id,
+    signer_address,
+    assignee
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)
+}
+
+// refuse to let a member decide on an engagement someone else is reviewing, and tag the
+// event log of the transaction with the reviewer
+pub(super) async fn check_in(
+  tx: &mut Transaction<'_, Postgres>,
+  org_id: Uuid,
+  campaign_id: Uuid,
+  chain_id: i64,
+  signer_address: &str,
+  reviewer: Reviewer<'_>,
+) -> Result<(), Error> {
+  let claim = get_for_update(tx, org_id, campaign_id, chain_id, signer_address).await?;
+  if let Some(member_id) = reviewer.member_id() {
+    if claim.held_by_other(member_id) {
+      return Err(Error::Claimed);
+    }
+  }
+
+  query!(
+    "SELECT
+      set_config('rs.review_source', $1, true) AS review_source,
+      set_config('rs.reviewer_id', $2, true) AS reviewer_id",
+    reviewer.source(),
+    reviewer.member_id().unwrap_or_default()
+  )
+  .fetch_one(&mut **tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(())
+}
+
+// log decisions for the reviewer stats and release the reviewer's claim
+pub(super) async fn record_in(
+  tx: &mut Transaction<'_, Postgres>,
+  org_id: Uuid,
+  campaign_id: Uuid,
+  chain_id: i64,
+  signer_address: &str,
+  reviewer: Reviewer<'_>,
+  decisions: &Accepted,
+) -> Result<(), Error> {
+  let (task_ids, accepted): (Vec<String>, Vec<bool>) =
+    decisions.iter().map(|(k, v)| (k.to_owned(), *v)).unzip();
+
+  query!(
+    "INSERT INTO engage_review (
+      org_id,
+      campaign_id,
+      chain_id,
+      signer_address,
+      task_id,
+      source,
+      reviewer_id,
+      accepted
+    )
+    SELECT $1, $2, $3, $4, t.task_id, $5, $6, t.accepted
+    FROM UNNEST($7::TEXT[], $8::BOOL[]) AS t(task_id, accepted)",
+    org_id,
+    campaign_id,
+    chain_id,
+    signer_address,
+    reviewer.source(),
+    reviewer.member_id(),
+    &task_ids,
+    &accepted
+  )
+  .execute(&mut **tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  if let Some(member_id) = reviewer.member_id() {
+    query!(
+      "UPDATE engage
+      SET
+        claimed_by = NULL,
+        claimed_until = NULL
+      WHERE campaign_id = $1
+        AND chain_id = $2
+        AND signer_address = $3
+        AND claimed_by = $4",
+      campaign_id,
+      chain_id,
+      signer_address,
+      member_id
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(handle_pg_error)?;
+  }
+
+  Ok(())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StatsParams {
+  pub campaign_id: Option<Uuid>,
+  pub reviewed_after: Option<DateTime<Utc>>,
+  pub reviewed_before: Option<DateTime<Utc>>,
+}
+
+/// Decisions made by a reviewer, `reviewer_id` is only set for org members.
+#[derive(FromRow, Serialize, Debug)]
+pub struct ReviewerStats {
+  pub source: String,
+  pub reviewer_id: Option<String>,
+  pub approved: i64,
+  pub rejected: i64,
+  pub first_reviewed_at: DateTime<Utc>,
+  pub last_reviewed_at: DateTime<Utc>,
+}
+
+// count the decisions made by each reviewer of a org, busiest first
+pub async fn stats(db: &PgPool, org_id: Uuid, p: StatsParams) -> Result<Vec<ReviewerStats>, Error> {
+  let mut query = QueryBuilder::<Postgres>::new(
+    r#"SELECT
+      source,
+      reviewer_id,
+      COUNT(*) FILTER (WHERE accepted) AS approved,
+      COUNT(*) FILTER (WHERE NOT accepted) AS rejected,
+      MIN(created_at) AS first_reviewed_at,
+      MAX(created_at) AS last_reviewed_at
+    FROM engage_review
+    WHERE "#,
+  );
+  let mut sep = query.separated(" AND ");
+  must_bind!(sep, "org_id" = org_id);
+  maybe_bind!(sep, "campaign_id" = p.campaign_id);
+  maybe_bind!(sep, "created_at" >= p.reviewed_after);
+  maybe_bind!(sep, "created_at" <= p.reviewed_before);
+  query.push(" GROUP BY source, reviewer_id ORDER BY COUNT(*) DESC");
+
+  query
+    .build_query_as()
+    .fetch_all(db)
+    .await
+    .map_err(handle_pg_error)
+}
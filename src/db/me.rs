@@ -1,5 +1,7 @@
+use chrono::NaiveDate;
 use serde::Serialize;
-use sqlx::{prelude::FromRow, query, PgPool};
+use sqlx::{prelude::FromRow, query, query_as, PgPool};
+use uuid::Uuid;
 
 use super::{handle_pg_error, Error};
 
@@ -40,3 +42,41 @@ pub async fn get_club(db: &PgPool, user_id: &str) -> Result<i64, Error> {
 
   Ok(res.count.unwrap_or_default())
 }
+
+/// Points of a project that expire soon, and the first day some of them are no longer valid.
+#[derive(FromRow, Serialize, Debug)]
+pub struct ExpiringPoints {
+  pub project_id: Uuid,
+  pub points: i64,
+  pub valid_until: NaiveDate,
+}
+
+// get a user's points expiring within `days`, by project
+pub async fn get_expiring(
+  db: &PgPool,
+  user_id: &str,
+  days: i32,
+) -> Result<Vec<ExpiringPoints>, Error> {
+  query_as!(
+    ExpiringPoints,
+    r#"SELECT
+      project_id,
+      SUM(balance)::BIGINT AS "points!",
+      MIN(valid_until) AS "valid_until!"
+    FROM voucher
+    WHERE user_id = $1
+      AND balance > 0
+      AND expired_at IS NULL
+      AND voided_at IS NULL
+      AND valid_from <= NOW()
+      AND valid_until >= NOW()
+      AND valid_until < NOW() + make_interval(days => $2)
+    GROUP BY project_id
+    ORDER BY MIN(valid_until) ASC"#,
+    user_id,
+    days
+  )
+  .fetch_all(db)
+  .await
+  .map_err(handle_pg_error)
+}
@@ -3,11 +3,14 @@ use std::collections::HashMap;
 use chrono::{DateTime, Utc};
 use is_empty::IsEmpty;
 use serde::{Deserialize, Serialize};
-use sqlx::{prelude::FromRow, query, query_as, types::Json, PgPool, Postgres, QueryBuilder};
+use sqlx::{
+  prelude::FromRow, query, query_as, types::Json, PgPool, Postgres, QueryBuilder, Transaction,
+};
 use uuid::Uuid;
 
-use crate::db::sqlx_macro::{
-  must_bind, maybe_bind, offset_limit,
+use crate::{
+  auth::OWNER_PERMISSION,
+  db::sqlx_macro::{must_bind, maybe_bind, offset_limit},
 };
 
 use super::{
@@ -82,21 +85,24 @@ pub async fn get(db: &PgPool, id: Uuid) -> Result<Org, Error> {
 pub struct CreateForm {
   pub name: String,
   pub logo: Option<String>,
-  pub admins: HashMap<String, i64>,
 }
 
-// create a org
+// create a org owned by its creator, other admins join through invites
 pub async fn create<'a>(
   db: &PgPool,
   p: CreateParam<'a, CreateForm>,
+  owner_id: &str,
 ) -> Result<CreateResult, Error> {
   query_as!(
     CreateResult,
-    "INSERT INTO org (id, name, logo, admins) VALUES ($1, $2, $3, $4) RETURNING created_at",
+    "INSERT INTO org (id, name, logo, admins)
+    VALUES ($1, $2, $3, jsonb_build_object($4::TEXT, $5::BIGINT))
+    RETURNING created_at",
     p.id,
     p.form.name,
     p.form.logo,
-    Json(&p.form.admins) as _,
+    owner_id,
+    OWNER_PERMISSION
   )
   .fetch_one(db)
   .await
@@ -107,10 +113,9 @@ pub async fn create<'a>(
 pub struct UpdateParam {
   pub name: Option<String>,
   pub logo: Option<String>,
-  pub admins: Option<HashMap<String, i64>>,
 }
 
-// update a org
+// update a org, admins are managed through set_admin and remove_admin to keep claims in sync
 pub async fn update(db: &PgPool, org_id: Uuid, p: UpdateParam) -> Result<UpdateResult, Error> {
   if p.is_empty() {
     return Err(Error::EmptyUpdateSet);
@@ -121,7 +126,6 @@ pub async fn update(db: &PgPool, org_id: Uuid, p: UpdateParam) -> Result<UpdateR
   sep.push(" updated_at = NOW() ");
   maybe_bind!(sep, "name" = p.name);
   maybe_bind!(sep, "logo" = p.logo);
-  maybe_bind!(sep, "admins" = p.admins, Json);
   query.push(" WHERE id = ").push_bind(org_id);
   query.push(" RETURNING updated_at");
   query
@@ -163,3 +167,107 @@ pub async fn delete(db: &PgPool, org_id: Uuid) -> Result<(), Error> {
     Err(err) => Err(handle_pg_error(err)),
   }
 }
+
+// lock the admins of a org, refusing to leave it without an owner once `user_id` has
+// `permission`, or is removed when None
+async fn lock_admins_in(
+  tx: &mut Transaction<'_, Postgres>,
+  org_id: Uuid,
+  user_id: &str,
+  permission: Option<i64>,
+) -> Result<(), Error> {
+  let admins = query!(
+    r#"SELECT admins AS "admins: Json<HashMap<String, i64>>" FROM org WHERE id = $1 FOR UPDATE"#,
+    org_id
+  )
+  .fetch_one(&mut **tx)
+  .await
+  .map_err(handle_pg_error)?
+  .admins
+  .0;
+
+  let is_owner = match admins.get(user_id) {
+    Some(p) => *p == OWNER_PERMISSION,
+    None => return Err(Error::NotFound),
+  };
+  let has_other_owner = admins
+    .iter()
+    .any(|(id, p)| id != user_id && *p == OWNER_PERMISSION);
+  if is_owner && !has_other_owner && permission != Some(OWNER_PERMISSION) {
+    return Err(Error::LastOwner);
+  }
+
+  Ok(())
+}
+
+// change the permission of an admin of a org, new admins join through invites
+pub async fn set_admin(
+  db: &PgPool,
+  org_id: Uuid,
+  user_id: &str,
+  permission: i64,
+) -> Result<UpdateResult, Error> {
+  let mut tx = db.begin().await?;
+  lock_admins_in(&mut tx, org_id, user_id, Some(permission)).await?;
+
+  let res = query_as!(
+    UpdateResult,
+    r#"UPDATE org
+    SET
+      admins = admins || jsonb_build_object($2::TEXT, $3::BIGINT),
+      updated_at = NOW()
+    WHERE id = $1
+    RETURNING updated_at AS "updated_at!""#,
+    org_id,
+    user_id,
+    permission
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+  tx.commit().await?;
+
+  Ok(res)
+}
+
+// revoke an admin from a org
+pub async fn remove_admin(db: &PgPool, org_id: Uuid, user_id: &str) -> Result<UpdateResult, Error> {
+  let mut tx = db.begin().await?;
+  lock_admins_in(&mut tx, org_id, user_id, None).await?;
+
+  let res = query_as!(
+    UpdateResult,
+    r#"UPDATE org
+    SET
+      admins = admins - $2::TEXT,
+      updated_at = NOW()
+    WHERE id = $1
+    RETURNING updated_at AS "updated_at!""#,
+    org_id,
+    user_id
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+  tx.commit().await?;
+
+  Ok(res)
+}
+
+// get a user's admin permission in a org
+pub async fn get_permission(
+  db: &PgPool,
+  org_id: Uuid,
+  user_id: &str,
+) -> Result<Option<i64>, Error> {
+  let res = query!(
+    r#"SELECT (admins ->> $2)::BIGINT AS permission FROM org WHERE id = $1"#,
+    org_id,
+    user_id
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(res.permission)
+}
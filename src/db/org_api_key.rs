@@ -0,0 +1,135 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{prelude::FromRow, query, query_as, types::Json, PgPool};
+use uuid::Uuid;
+
+use crate::auth::api_key::{ApiScope, OrgApiKey};
+
+use super::{handle_pg_error, CreateResult, Error, UpdateResult};
+
+#[derive(FromRow, Serialize, Debug)]
+pub struct ApiKey {
+  pub id: Uuid,
+  pub org_id: Uuid,
+  pub name: String,
+  pub prefix: String,
+  pub scopes: Json<Vec<ApiScope>>,
+  pub created_by: String,
+  pub expires_at: Option<DateTime<Utc>>,
+  pub last_used_at: Option<DateTime<Utc>>,
+  pub revoked_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: Option<DateTime<Utc>>,
+}
+
+// list a org's API keys
+pub async fn list(db: &PgPool, org_id: Uuid) -> Result<Vec<ApiKey>, Error> {
+  query_as!(
+    ApiKey,
+    r#"SELECT
+      id,
+      org_id,
+      name,
+      prefix,
+      scopes AS "scopes: Json<Vec<ApiScope>>",
+      created_by,
+      expires_at,
+      last_used_at,
+      revoked_at,
+      created_at,
+      updated_at
+    FROM org_api_key
+    WHERE org_id = $1
+    ORDER BY created_at DESC"#,
+    org_id
+  )
+  .fetch_all(db)
+  .await
+  .map_err(handle_pg_error)
+}
+
+pub struct CreateParam<'a> {
+  pub id: Uuid,
+  pub org_id: Uuid,
+  pub name: &'a str,
+  pub prefix: &'a str,
+  pub key_hash: &'a str,
+  pub scopes: &'a Vec<ApiScope>,
+  pub created_by: &'a str,
+  pub expires_at: Option<DateTime<Utc>>,
+}
+
+// create an API key
+pub async fn create<'a>(db: &PgPool, p: CreateParam<'a>) -> Result<CreateResult, Error> {
+  query_as!(
+    CreateResult,
+    "INSERT INTO org_api_key (
+      id,
+      org_id,
+      name,
+      prefix,
+      key_hash,
+      scopes,
+      created_by,
+      expires_at
+    )
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+    RETURNING created_at",
+    p.id,
+    p.org_id,
+    p.name,
+    p.prefix,
+    p.key_hash,
+    Json(p.scopes) as _,
+    p.created_by,
+    p.expires_at,
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)
+}
+
+// revoke an API key
+pub async fn revoke(db: &PgPool, org_id: Uuid, key_id: Uuid) -> Result<UpdateResult, Error> {
+  query_as!(
+    UpdateResult,
+    r#"UPDATE org_api_key
+    SET
+      revoked_at = NOW(),
+      updated_at = NOW()
+    WHERE org_id = $1
+      AND id = $2
+      AND revoked_at IS NULL
+    RETURNING updated_at AS "updated_at!""#,
+    org_id,
+    key_id
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)
+}
+
+// find a live API key by its hash and mark it used
+pub async fn authenticate(db: &PgPool, key_hash: &str) -> Result<OrgApiKey, Error> {
+  let res = query!(
+    r#"UPDATE org_api_key
+    SET last_used_at = NOW()
+    WHERE key_hash = $1
+      AND revoked_at IS NULL
+      AND (expires_at IS NULL OR expires_at > NOW())
+    RETURNING
+      id,
+      org_id,
+      scopes AS "scopes: Json<Vec<ApiScope>>""#,
+    key_hash
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(OrgApiKey {
+    id: res.id,
+    org_id: res.org_id,
+    scopes: res.scopes.0,
+  })
+}
@@ -0,0 +1,222 @@
+use chrono::{DateTime, Utc};
+use is_empty::IsEmpty;
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, query, query_as, PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::db::sqlx_macro::{maybe_bind, must_bind, offset_limit};
+
+use super::{handle_pg_error, maybe_order_by, CreateResult, Error, ListParams, UpdateResult};
+
+#[derive(FromRow, Serialize, Debug)]
+pub struct OrgInvite {
+  pub id: Uuid,
+  pub org_id: Uuid,
+  pub email: String,
+  pub permission: i64,
+  pub invited_by: String,
+  pub expires_at: DateTime<Utc>,
+  pub accepted_by: Option<String>,
+  pub accepted_at: Option<DateTime<Utc>>,
+  pub declined_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(IsEmpty, Deserialize, Clone, Debug)]
+pub struct InviteFilter {
+  pub email: Option<String>,
+  pub pending: Option<bool>,
+  pub created_after: Option<DateTime<Utc>>,
+  pub created_before: Option<DateTime<Utc>>,
+}
+
+// list a org's invites
+pub async fn list(
+  db: &PgPool,
+  org_id: Uuid,
+  p: ListParams<InviteFilter>,
+) -> Result<Vec<OrgInvite>, Error> {
+  let mut query = QueryBuilder::<Postgres>::new(
+    r#"SELECT
+      id,
+      org_id,
+      email,
+      permission,
+      invited_by,
+      expires_at,
+      accepted_by,
+      accepted_at,
+      declined_at,
+      created_at,
+      updated_at
+    FROM org_invite
+    WHERE "#,
+  );
+  let mut sep = query.separated(" AND ");
+  must_bind!(sep, "org_id" = org_id);
+  maybe_bind!(sep, "email" = p.filter.email.map(|e| e.to_lowercase()));
+  maybe_bind!(sep, "created_at" <= p.filter.created_before);
+  maybe_bind!(sep, "created_at" >= p.filter.created_after);
+  match p.filter.pending {
+    Some(true) => {
+      sep.push("accepted_at IS NULL AND declined_at IS NULL AND expires_at > NOW()");
+    }
+    Some(false) => {
+      sep.push("(accepted_at IS NOT NULL OR declined_at IS NOT NULL OR expires_at <= NOW())");
+    }
+    None => {}
+  }
+
+  maybe_order_by(&mut query, &p.order, vec!["created_at", "expires_at"])?;
+  offset_limit!(query, p.offset, p.limit);
+
+  query
+    .build_query_as()
+    .fetch_all(db)
+    .await
+    .map_err(handle_pg_error)
+}
+
+pub struct CreateParam<'a> {
+  pub id: Uuid,
+  pub org_id: Uuid,
+  pub email: &'a str,
+  pub permission: i64,
+  pub token_hash: &'a str,
+  pub invited_by: &'a str,
+  pub expires_at: DateTime<Utc>,
+}
+
+// create an invite
+pub async fn create<'a>(db: &PgPool, p: CreateParam<'a>) -> Result<CreateResult, Error> {
+  query_as!(
+    CreateResult,
+    "INSERT INTO org_invite (
+      id,
+      org_id,
+      email,
+      permission,
+      token_hash,
+      invited_by,
+      expires_at
+    )
+    VALUES ($1, $2, $3, $4, $5, $6, $7)
+    RETURNING created_at",
+    p.id,
+    p.org_id,
+    p.email.to_lowercase(),
+    p.permission,
+    p.token_hash,
+    p.invited_by,
+    p.expires_at,
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)
+}
+
+// accept a pending invite and grant its permission in the org
+pub async fn accept(
+  db: &PgPool,
+  org_id: Uuid,
+  invite_id: Uuid,
+  token_hash: &str,
+  email: &str,
+  user_id: &str,
+) -> Result<i64, Error> {
+  let mut tx = db.begin().await?;
+
+  let invite = query!(
+    r#"UPDATE org_invite
+    SET
+      accepted_by = $5,
+      accepted_at = NOW(),
+      updated_at = NOW()
+    WHERE id = $1
+      AND org_id = $2
+      AND token_hash = $3
+      AND email = $4
+      AND accepted_at IS NULL
+      AND declined_at IS NULL
+      AND expires_at > NOW()
+    RETURNING permission"#,
+    invite_id,
+    org_id,
+    token_hash,
+    email.to_lowercase(),
+    user_id,
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  // members change roles through the members endpoints, an invite never demotes them
+  let res = query!(
+    r#"UPDATE org
+    SET
+      admins = admins || jsonb_build_object($2::TEXT, $3::BIGINT),
+      updated_at = NOW()
+    WHERE id = $1
+      AND NOT admins ? $2"#,
+    org_id,
+    user_id,
+    invite.permission
+  )
+  .execute(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+  if res.rows_affected() == 0 {
+    return Err(Error::Member);
+  }
+
+  tx.commit().await?;
+
+  Ok(invite.permission)
+}
+
+// decline a pending invite
+pub async fn decline(
+  db: &PgPool,
+  org_id: Uuid,
+  invite_id: Uuid,
+  token_hash: &str,
+  email: &str,
+) -> Result<UpdateResult, Error> {
+  query_as!(
+    UpdateResult,
+    r#"UPDATE org_invite
+    SET
+      declined_at = NOW(),
+      updated_at = NOW()
+    WHERE id = $1
+      AND org_id = $2
+      AND token_hash = $3
+      AND email = $4
+      AND accepted_at IS NULL
+      AND declined_at IS NULL
+    RETURNING updated_at AS "updated_at!""#,
+    invite_id,
+    org_id,
+    token_hash,
+    email.to_lowercase(),
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)
+}
+
+// delete an invite
+pub async fn delete(db: &PgPool, org_id: Uuid, invite_id: Uuid) -> Result<(), Error> {
+  match query!(
+    "DELETE FROM org_invite WHERE org_id = $1 AND id = $2",
+    org_id,
+    invite_id
+  )
+  .execute(db)
+  .await
+  {
+    Ok(_) => Ok(()),
+    Err(err) => Err(handle_pg_error(err)),
+  }
+}
@@ -0,0 +1,272 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use is_empty::IsEmpty;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use sqlx::{prelude::FromRow, query, query_as, PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::db::{
+  maybe_order_by,
+  sqlx_macro::{maybe_bind, must_bind, offset_limit},
+  UpdateResult,
+};
+
+use super::{handle_pg_error, CreateResult, Error};
+
+#[derive(FromRow, Serialize)]
+pub struct OrgReward {
+  pub id: Uuid,
+  pub issuer_id: Option<String>,
+  pub category: Option<i16>,
+  pub country_id: Option<i16>,
+  pub name: String,
+  pub tandc: Option<String>,
+  pub images: Vec<String>,
+  pub active_from: Option<NaiveDate>,
+  pub active_until: Option<NaiveDate>,
+  pub valid_from: Option<NaiveDate>,
+  pub valid_until: Option<NaiveDate>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: Option<DateTime<Utc>>,
+  pub org_id: Option<Uuid>,
+  pub approved: Option<bool>,
+  pub active: Option<bool>,
+  pub point: Option<i64>,
+  pub max_mint: Option<i64>,
+  pub user_mint: Option<i64>,
+  pub link_created_at: Option<DateTime<Utc>>,
+  pub link_updated_at: Option<DateTime<Utc>>,
+  pub coupon_minted: i64,
+  pub coupon_total: i64,
+}
+
+// get a reward, with its price in the org's catalog
+pub async fn get(db: &PgPool, org_id: Uuid, reward_id: Uuid) -> Result<OrgReward, Error> {
+  query_as!(
+    OrgReward,
+    r#"SELECT
+      r.id,
+      r.issuer_id,
+      r.category,
+      r.country_id,
+      r.name,
+      r.tandc,
+      r.images,
+      r.active_from,
+      r.active_until,
+      r.valid_from,
+      r.valid_until,
+      r.created_at,
+      r.updated_at,
+      o.org_id,
+      o.point,
+      o.approved,
+      o.active,
+      o.max_mint,
+      o.user_mint,
+      o.created_at as link_created_at,
+      o.updated_at as link_updated_at,
+      (SELECT COUNT(user_id) as "coupon_minted!" FROM coupon WHERE reward_id = r.id),
+      (SELECT COUNT(*) as "coupon_total!" FROM coupon WHERE reward_id = r.id)
+    FROM reward r
+    LEFT JOIN (
+      SELECT *
+      FROM org__reward
+      WHERE org_id = $1
+    ) o
+    ON r.id = o.reward_id
+    WHERE r.id = $2"#,
+    org_id,
+    reward_id,
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)
+}
+
+#[serde_as]
+#[derive(Deserialize, IsEmpty, Clone, Debug)]
+pub struct OrgRewardFilter {
+  pub issuer_id: Option<String>,
+  pub category: Option<i16>,
+  pub country_id: Option<i16>,
+  pub created_after: Option<DateTime<Utc>>,
+  pub created_before: Option<DateTime<Utc>>,
+  #[serde_as(as = "Option<DisplayFromStr>")]
+  #[serde(default)]
+  pub approved: Option<bool>,
+  #[serde_as(as = "Option<DisplayFromStr>")]
+  #[serde(default)]
+  pub active: Option<bool>,
+}
+
+// list the rewards in a org's catalog
+pub async fn list(
+  db: &PgPool,
+  org_id: Uuid,
+  p: super::ListParams<OrgRewardFilter>,
+) -> Result<Vec<OrgReward>, Error> {
+  let mut query = QueryBuilder::<Postgres>::new(
+    r#"SELECT
+      r.id,
+      r.issuer_id,
+      r.category,
+      r.country_id,
+      r.name,
+      r.tandc,
+      r.images,
+      r.active_from,
+      r.active_until,
+      r.valid_from,
+      r.valid_until,
+      r.created_at,
+      r.updated_at,
+      o.org_id,
+      o.point,
+      o.approved,
+      o.active,
+      o.max_mint,
+      o.user_mint,
+      o.created_at as link_created_at,
+      o.updated_at as link_updated_at,
+      (SELECT COUNT(user_id) as "coupon_minted" FROM coupon WHERE reward_id = r.id),
+      (SELECT COUNT(*) as "coupon_total" FROM coupon WHERE reward_id = r.id)
+    FROM reward r
+    JOIN org__reward o
+    ON r.id = o.reward_id"#,
+  );
+
+  query.push(" WHERE ");
+  let mut sep = query.separated(" AND ");
+  must_bind!(sep, "o.org_id" = org_id);
+  maybe_bind!(sep, "r.issuer_id" = p.filter.issuer_id);
+  maybe_bind!(sep, "r.category" = p.filter.category);
+  maybe_bind!(sep, "r.country_id" = p.filter.country_id);
+  maybe_bind!(sep, "r.created_at" <= p.filter.created_before);
+  maybe_bind!(sep, "r.created_at" >= p.filter.created_after);
+  maybe_bind!(sep, "o.approved" = p.filter.approved);
+  maybe_bind!(sep, "o.active" = p.filter.active);
+
+  maybe_order_by(
+    &mut query,
+    &p.order,
+    vec![
+      "r.active_from",
+      "r.active_until",
+      "r.valid_from",
+      "r.valid_until",
+      "r.created_at",
+      "r.updated_at",
+      "o.point",
+      "o.max_mint",
+      "o.user_mint",
+    ],
+  )?;
+  offset_limit!(query, p.offset, p.limit);
+
+  query
+    .build_query_as()
+    .fetch_all(db)
+    .await
+    .map_err(handle_pg_error)
+}
+
+#[derive(Deserialize, Default, Debug)]
+pub struct CreateParam {
+  pub reward_id: Uuid,
+  pub point: i64,
+  pub active: bool,
+  pub max_mint: Option<i64>,
+  pub user_mint: Option<i64>,
+}
+
+// add a reward to a org's catalog
+pub async fn create(db: &PgPool, org_id: Uuid, p: CreateParam) -> Result<CreateResult, Error> {
+  if p.point < 0 || p.max_mint.is_some_and(|n| n < 0) || p.user_mint.is_some_and(|n| n < 0) {
+    return Err(Error::Validation);
+  }
+  query_as!(
+    CreateResult,
+    "INSERT INTO org__reward (
+      org_id,
+      reward_id,
+      point,
+      active,
+      max_mint,
+      user_mint,
+      approved
+    )
+    VALUES ($1, $2, $3, $4, $5, $6, true)
+    RETURNING created_at",
+    org_id,
+    p.reward_id,
+    p.point,
+    p.active,
+    p.max_mint,
+    p.user_mint,
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)
+}
+
+#[derive(Deserialize, IsEmpty, Default)]
+pub struct UpdateParam {
+  pub active: Option<bool>,
+  pub point: Option<i64>,
+  pub max_mint: Option<i64>,
+  pub user_mint: Option<i64>,
+}
+
+// update a reward in a org's catalog
+pub async fn update(
+  db: &PgPool,
+  org_id: Uuid,
+  reward_id: Uuid,
+  p: UpdateParam,
+) -> Result<UpdateResult, Error> {
+  if p.is_empty() {
+    return Err(Error::EmptyUpdateSet);
+  }
+  if p.point.is_some_and(|n| n < 0)
+    || p.max_mint.is_some_and(|n| n < 0)
+    || p.user_mint.is_some_and(|n| n < 0)
+  {
+    return Err(Error::Validation);
+  }
+
+  let mut query = QueryBuilder::<Postgres>::new("UPDATE org__reward SET");
+  let mut sep = query.separated(", ");
+  sep.push(" updated_at = NOW() ");
+  maybe_bind!(sep, "active" = p.active);
+  maybe_bind!(sep, "point" = p.point);
+  maybe_bind!(sep, "max_mint" = p.max_mint);
+  maybe_bind!(sep, "user_mint" = p.user_mint);
+  query.push(" WHERE ");
+  let mut sep = query.separated(" AND ");
+  must_bind!(sep, "org_id" = org_id);
+  must_bind!(sep, "reward_id" = reward_id);
+  query.push(" RETURNING updated_at");
+
+  query
+    .build_query_as()
+    .fetch_one(db)
+    .await
+    .map_err(handle_pg_error)
+}
+
+// remove a reward from a org's catalog
+pub async fn unlink(db: &PgPool, org_id: Uuid, reward_id: Uuid) -> Result<(), Error> {
+  query!(
+    "DELETE FROM org__reward
+    WHERE org_id = $1
+      AND reward_id = $2",
+    org_id,
+    reward_id
+  )
+  .execute(db)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(())
+}
@@ -0,0 +1,151 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use is_empty::IsEmpty;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr, NoneAsEmptyString};
+use sqlx::{prelude::FromRow, query_as, PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::db::{
+  maybe_order_by,
+  sqlx_macro::{maybe_bind, must_bind, offset_limit},
+};
+
+use super::{handle_pg_error, Error};
+
+#[serde_as]
+#[derive(FromRow, Serialize)]
+pub struct PubOrgReward {
+  pub id: Uuid,
+  pub issuer_id: Option<String>,
+  pub category: Option<i16>,
+  pub country_id: Option<i16>,
+  pub name: String,
+  pub tandc: Option<String>,
+  pub images: Vec<String>,
+  #[serde_as(as = "NoneAsEmptyString")]
+  pub active_from: Option<NaiveDate>,
+  #[serde_as(as = "NoneAsEmptyString")]
+  pub active_until: Option<NaiveDate>,
+  #[serde_as(as = "NoneAsEmptyString")]
+  pub valid_from: Option<NaiveDate>,
+  #[serde_as(as = "NoneAsEmptyString")]
+  pub valid_until: Option<NaiveDate>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: Option<DateTime<Utc>>,
+  pub org_id: Uuid,
+  pub active: bool,
+  pub point: i64,
+}
+
+// get a reward of a org's catalog
+pub async fn get(db: &PgPool, org_id: Uuid, reward_id: Uuid) -> Result<PubOrgReward, Error> {
+  query_as!(
+    PubOrgReward,
+    r#"SELECT
+      r.id,
+      r.issuer_id,
+      r.category,
+      r.country_id,
+      r.name,
+      r.tandc,
+      r.images,
+      r.active_from,
+      r.active_until,
+      r.valid_from,
+      r.valid_until,
+      r.created_at,
+      r.updated_at,
+      o.org_id,
+      o.point,
+      o.active
+    FROM reward r
+    JOIN org__reward o
+      ON r.id = o.reward_id
+    WHERE o.org_id = $1
+      AND o.approved = true
+      AND r.id = $2
+    "#,
+    org_id,
+    reward_id,
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)
+}
+
+#[serde_as]
+#[derive(Deserialize, Clone, Debug, IsEmpty)]
+pub struct RewardFilter {
+  pub issuer_id: Option<String>,
+  pub category: Option<i16>,
+  pub country_id: Option<i16>,
+  pub created_after: Option<DateTime<Utc>>,
+  pub created_before: Option<DateTime<Utc>>,
+  #[serde_as(as = "Option<DisplayFromStr>")]
+  #[serde(default)]
+  pub active: Option<bool>,
+}
+
+// list the rewards of a org's catalog
+pub async fn list(
+  db: &PgPool,
+  org_id: Uuid,
+  p: super::ListParams<RewardFilter>,
+) -> Result<Vec<PubOrgReward>, Error> {
+  let mut query = QueryBuilder::<Postgres>::new(
+    r#"SELECT
+      r.id,
+      r.issuer_id,
+      r.category,
+      r.country_id,
+      r.name,
+      r.tandc,
+      r.images,
+      r.active_from,
+      r.active_until,
+      r.valid_from,
+      r.valid_until,
+      r.created_at,
+      r.updated_at,
+      o.org_id,
+      o.point,
+      o.active
+    FROM reward r
+    JOIN org__reward o
+    ON r.id = o.reward_id"#,
+  );
+
+  query.push(" WHERE ");
+  let mut sep = query.separated(" AND ");
+  sep.push(" (r.active_until IS NULL OR r.active_until >= NOW())");
+  sep.push(" r.active_from <= NOW()");
+  sep.push(" o.approved = true");
+  must_bind!(sep, "o.org_id" = org_id);
+  maybe_bind!(sep, "r.issuer_id" = p.filter.issuer_id);
+  maybe_bind!(sep, "r.category" = p.filter.category);
+  maybe_bind!(sep, "r.country_id" = p.filter.country_id);
+  maybe_bind!(sep, "r.created_at" <= p.filter.created_before);
+  maybe_bind!(sep, "r.created_at" >= p.filter.created_after);
+  maybe_bind!(sep, "o.active" = p.filter.active);
+
+  maybe_order_by(
+    &mut query,
+    &p.order,
+    vec![
+      "r.active_from",
+      "r.active_until",
+      "r.valid_from",
+      "r.valid_until",
+      "r.created_at",
+      "r.updated_at",
+      "o.point",
+    ],
+  )?;
+  offset_limit!(query, p.offset, p.limit);
+
+  query
+    .build_query_as()
+    .fetch_all(db)
+    .await
+    .map_err(handle_pg_error)
+}
@@ -0,0 +1,227 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, query, query_as, types::Json, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::{handle_pg_error, CreateResult, Error};
+
+/// A promotion multiplying the points of tasks approved while it runs, in a whole project or
+/// in one of its campaigns.
+#[derive(FromRow, Serialize, Debug)]
+pub struct Multiplier {
+  pub id: Uuid,
+  pub org_id: Uuid,
+  pub project_id: Uuid,
+  pub campaign_id: Option<Uuid>,
+  pub name: String,
+  pub factor: Decimal,
+  pub conditions: Json<Vec<Condition>>,
+  pub starts_at: DateTime<Utc>,
+  pub ends_at: DateTime<Utc>,
+  pub created_at: DateTime<Utc>,
+}
+
+/// Who, or which tasks, a multiplier is for. A multiplier applies when all its conditions hold.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Condition {
+  /// The participant is a member of the project's club.
+  ClubMember,
+  /// The task is one of these.
+  Tasks { task_ids: Vec<String> },
+}
+
+impl Condition {
+  /// Check the condition is usable, returning a reason when it isn't.
+  pub fn validate(&self) -> Result<(), &'static str> {
+    match self {
+      Condition::Tasks { task_ids } if task_ids.is_empty() => Err("task_ids is empty"),
+      _ => Ok(()),
+    }
+  }
+}
+
+/// The multipliers running for a participant's approval.
+pub struct Active {
+  multipliers: Vec<Multiplier>,
+  club_member: bool,
+}
+
+impl Active {
+  /// The bonus for a task's points, from the largest multiplier that applies to it.
+  pub fn bonus(&self, task_id: &str, base: i64) -> Option<(Uuid, i64)> {
+    self
+      .multipliers
+      .iter()
+      .filter(|m| {
+        m.conditions.iter().all(|c| match c {
+          Condition::ClubMember => self.club_member,
+          Condition::Tasks { task_ids } => task_ids.iter().any(|id| id == task_id),
+        })
+      })
+      .max_by_key(|m| m.factor)
+      .and_then(|m| {
+        let bonus = (Decimal::from(base) * (m.factor - Decimal::ONE))
+          .floor()
+          .to_i64()?;
+        Some((m.id, bonus))
+      })
+  }
+}
+
+// get the multipliers running now for a campaign, and whether the participant is in the club
+pub(super) async fn active_in(
+  tx: &mut Transaction<'_, Postgres>,
+  project_id: Uuid,
+  campaign_id: Uuid,
+  user_id: &str,
+) -> Result<Active, Error> {
+  let multipliers = query_as!(
+    Multiplier,
+    r#"SELECT
+      id,
+      org_id,
+      project_id,
+      campaign_id,
+      name,
+      factor,
+      conditions AS "conditions: Json<Vec<Condition>>",
+      starts_at,
+      ends_at,
+      created_at
+    FROM point_multiplier
+    WHERE project_id = $1
+      AND (campaign_id IS NULL OR campaign_id = $2)
+      AND starts_at <= NOW()
+      AND ends_at > NOW()"#,
+    project_id,
+    campaign_id
+  )
+  .fetch_all(&mut **tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  let club_member = if multipliers
+    .iter()
+    .any(|m| m.conditions.contains(&Condition::ClubMember))
+  {
+    query!(
+      r#"SELECT EXISTS (
+        SELECT 1
+        FROM project__user
+        WHERE project_id = $1
+          AND user_id = $2
+          AND subscribed IS NOT false
+      ) AS "member!""#,
+      project_id,
+      user_id
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(handle_pg_error)?
+    .member
+  } else {
+    false
+  };
+
+  Ok(Active {
+    multipliers,
+    club_member,
+  })
+}
+
+// list a org's multipliers, latest first
+pub async fn list(
+  db: &PgPool,
+  org_id: Uuid,
+  project_id: Option<Uuid>,
+  campaign_id: Option<Uuid>,
+) -> Result<Vec<Multiplier>, Error> {
+  query_as!(
+    Multiplier,
+    r#"SELECT
+      id,
+      org_id,
+      project_id,
+      campaign_id,
+      name,
+      factor,
+      conditions AS "conditions: Json<Vec<Condition>>",
+      starts_at,
+      ends_at,
+      created_at
+    FROM point_multiplier
+    WHERE org_id = $1
+      AND ($2::UUID IS NULL OR project_id = $2)
+      AND ($3::UUID IS NULL OR campaign_id = $3)
+    ORDER BY starts_at DESC"#,
+    org_id,
+    project_id,
+    campaign_id
+  )
+  .fetch_all(db)
+  .await
+  .map_err(handle_pg_error)
+}
+
+pub struct CreateParam<'a> {
+  pub id: Uuid,
+  pub org_id: Uuid,
+  pub project_id: Uuid,
+  pub campaign_id: Option<Uuid>,
+  pub name: &'a str,
+  pub factor: Decimal,
+  pub conditions: &'a Vec<Condition>,
+  pub starts_at: DateTime<Utc>,
+  pub ends_at: DateTime<Utc>,
+}
+
+// create a multiplier
+pub async fn create<'a>(db: &PgPool, p: CreateParam<'a>) -> Result<CreateResult, Error> {
+  query_as!(
+    CreateResult,
+    "INSERT INTO point_multiplier (
+      id,
+      org_id,
+      project_id,
+      campaign_id,
+      name,
+      factor,
+      conditions,
+      starts_at,
+      ends_at
+    )
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+    RETURNING created_at",
+    p.id,
+    p.org_id,
+    p.project_id,
+    p.campaign_id,
+    p.name,
+    p.factor,
+    Json(p.conditions) as _,
+    p.starts_at,
+    p.ends_at,
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)
+}
+
+// delete a multiplier, vouchers issued with it keep their bonus
+pub async fn delete(db: &PgPool, org_id: Uuid, multiplier_id: Uuid) -> Result<(), Error> {
+  let res = query!(
+    "DELETE FROM point_multiplier WHERE org_id = $1 AND id = $2",
+    org_id,
+    multiplier_id
+  )
+  .execute(db)
+  .await
+  .map_err(handle_pg_error)?;
+  if res.rows_affected() == 0 {
+    return Err(Error::NotFound);
+  }
+
+  Ok(())
+}
@@ -0,0 +1,355 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, query, query_as, PgPool, Postgres, QueryBuilder, Transaction};
+use uuid::Uuid;
+
+use crate::db::sqlx_macro::{maybe_bind, must_bind, offset_limit};
+
+use super::{
+  handle_pg_error, maybe_order_by,
+  reward::{spend_points_in, PointPool, PointRewardUpdateResult},
+  voucher, CreatedFilter, Error,
+};
+
+/// Points gifted by a user to another participant of a project.
+#[derive(FromRow, Serialize, Debug)]
+pub struct PointTransfer {
+  pub id: Uuid,
+  pub org_id: Uuid,
+  pub project_id: Uuid,
+  pub sender_id: String,
+  pub recipient_id: String,
+  pub chain_id: i64,
+  pub signer_address: String,
+  pub amount: i64,
+  pub created_at: DateTime<Utc>,
+}
+
+/// Who to gift points to, a user or one of their wallets. Either must have engaged in the project.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Recipient {
+  Wallet {
+    chain_id: i64,
+    signer_address: String,
+  },
+  User {
+    user_id: String,
+  },
+}
+
+struct Wallet {
+  user_id: String,
+  chain_id: i64,
+  signer_address: String,
+}
+
+// find the user and wallet receiving points, from their latest engagement in the project
+async fn get_recipient_in(
+  tx: &mut Transaction<'_, Postgres>,
+  project_id: Uuid,
+  recipient: &Recipient,
+) -> Result<Wallet, Error> {
+  let (user_id, chain_id, signer_address) = match recipient {
+    Recipient::Wallet {
+      chain_id,
+      signer_address,
+    } => (None, Some(*chain_id), Some(signer_address.as_str())),
+    Recipient::User { user_id } => (Some(user_id.as_str()), None, None),
+  };
+
+  query_as!(
+    Wallet,
+    "SELECT
+      user_id,
+      chain_id,
+      signer_address
+    FROM engage
+    WHERE project_id = $1
+      AND ($2::TEXT IS NULL OR user_id = $2)
+      AND ($3::BIGINT IS NULL OR chain_id = $3)
+      AND ($4::TEXT IS NULL OR signer_address = $4)
+    ORDER BY created_at DESC
+    LIMIT 1",
+    project_id,
+    user_id,
+    chain_id,
+    signer_address
+  )
+  .fetch_one(&mut **tx)
+  .await
+  .map_err(handle_pg_error)
+}
+
+pub struct CreateParam<'a> {
+  pub id: Uuid,
+  pub project_id: Uuid,
+  pub sender_id: &'a str,
+  pub recipient: &'a Recipient,
+  pub amount: i64,
+  pub daily_limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct TransferResult {
+  pub transfer: PointTransfer,
+  pub vouchers: Vec<PointRewardUpdateResult>,
+  pub balance: i64,
+}
+
+// gift points to another participant of a project. the sender's vouchers are spent like when
+// minting a reward, and the recipient gets a voucher for each, valid for as long as it was
+pub async fn create(db: &PgPool, p: CreateParam<'_>) -> Result<TransferResult, Error> {
+  if p.amount <= 0 {
+    return Err(Error::Validation);
+  }
+
+  let mut tx = db.begin().await?;
+  if voucher::settle_debt_in(&mut tx, p.project_id, p.sender_id).await? > 0 {
+    tx.commit().await?;
+    return Err(Error::Debt);
+  }
+
+  let recipient = get_recipient_in(&mut tx, p.project_id, p.recipient).await?;
+  if recipient.user_id == p.sender_id {
+    return Err(Error::Validation);
+  }
+
+  // spending locks the sender's vouchers, so concurrent transfers see each other in the limit
+  let (vouchers, balance) = spend_points_in(
+    &mut tx,
+    PointPool::Project(p.project_id),
+    p.sender_id,
+    p.amount,
+  )
+  .await?;
+
+  if let Some(limit) = p.daily_limit {
+    let sent = query!(
+      r#"SELECT
+        COALESCE(SUM(amount), 0)::BIGINT AS "sent!"
+      FROM point_transfer
+      WHERE project_id = $1
+        AND sender_id = $2
+        AND created_at > NOW() - INTERVAL '1 day'"#,
+      p.project_id,
+      p.sender_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(handle_pg_error)?
+    .sent;
+    if sent + p.amount > limit {
+      return Err(Error::LimitReached);
+    }
+  }
+
+  let transfer = query_as!(
+    PointTransfer,
+    "INSERT INTO point_transfer (
+      id,
+      org_id,
+      project_id,
+      sender_id,
+      recipient_id,
+      chain_id,
+      signer_address,
+      amount
+    )
+    SELECT $1, org_id, id, $3, $4, $5, $6, $7
+    FROM project
+    WHERE id = $2
+    RETURNING
+      id,
+      org_id,
+      project_id,
+      sender_id,
+      recipient_id,
+      chain_id,
+      signer_address,
+      amount,
+      created_at",
+    p.id,
+    p.project_id,
+    p.sender_id,
+    recipient.user_id,
+    recipient.chain_id,
+    recipient.signer_address,
+    p.amount
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  let mut campaign_ids = Vec::<Uuid>::new();
+  let mut chain_ids = Vec::<i64>::new();
+  let mut signer_addresses = Vec::<String>::new();
+  let mut task_ids = Vec::<String>::new();
+  let mut amounts = Vec::<i64>::new();
+  for v in vouchers.iter() {
+    campaign_ids.push(v.campaign_id);
+    chain_ids.push(v.chain_id);
+    signer_addresses.push(v.signer_address.clone());
+    task_ids.push(v.task_id.clone());
+    amounts.push(v.minted);
+  }
+
+  query!(
+    "WITH spent AS (
+      SELECT
+        v.org_id,
+        v.project_id,
+        v.campaign_id,
+        v.chain_id,
+        v.signer_address,
+        v.user_id,
+        v.task_id,
+        v.valid_from,
+        v.valid_until,
+        t.amount,
+        t.n
+      FROM UNNEST($2::UUID[], $3::BIGINT[], $4::TEXT[], $5::TEXT[], $6::BIGINT[])
+        WITH ORDINALITY AS t(campaign_id, chain_id, signer_address, task_id, amount, n)
+      JOIN voucher v
+      ON v.campaign_id = t.campaign_id
+        AND v.chain_id = t.chain_id
+        AND v.signer_address = t.signer_address
+        AND v.task_id = t.task_id
+    ),
+    received AS (
+      INSERT INTO voucher (
+        org_id,
+        project_id,
+        campaign_id,
+        chain_id,
+        signer_address,
+        user_id,
+        task_id,
+        value,
+        base,
+        balance,
+        valid_from,
+        valid_until,
+        transfer_id
+      )
+      SELECT
+        org_id,
+        project_id,
+        campaign_id,
+        $7,
+        $8,
+        $9,
+        'transfer/' || $1::UUID || '/' || n,
+        amount,
+        amount,
+        amount,
+        valid_from,
+        valid_until,
+        $1::UUID
+      FROM spent
+      RETURNING
+        org_id,
+        project_id,
+        campaign_id,
+        chain_id,
+        signer_address,
+        user_id,
+        task_id,
+        value
+    )
+    INSERT INTO voucher_event (
+      org_id,
+      project_id,
+      campaign_id,
+      chain_id,
+      signer_address,
+      user_id,
+      task_id,
+      kind,
+      amount
+    )
+    SELECT
+      org_id,
+      project_id,
+      campaign_id,
+      chain_id,
+      signer_address,
+      user_id,
+      task_id,
+      'transfer_out',
+      amount
+    FROM spent
+    UNION ALL
+    SELECT
+      org_id,
+      project_id,
+      campaign_id,
+      chain_id,
+      signer_address,
+      user_id,
+      task_id,
+      'transfer_in',
+      value
+    FROM received",
+    transfer.id,
+    &campaign_ids,
+    &chain_ids,
+    &signer_addresses,
+    &task_ids,
+    &amounts,
+    transfer.chain_id,
+    transfer.signer_address,
+    transfer.recipient_id
+  )
+  .execute(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  tx.commit().await?;
+
+  Ok(TransferResult {
+    transfer,
+    vouchers,
+    balance,
+  })
+}
+
+// list the transfers a user sent or received in a project
+pub async fn list(
+  db: &PgPool,
+  project_id: Uuid,
+  user_id: &str,
+  p: super::ListParams<CreatedFilter>,
+) -> Result<Vec<PointTransfer>, Error> {
+  let mut query = QueryBuilder::<Postgres>::new(
+    r#"SELECT
+      id,
+      org_id,
+      project_id,
+      sender_id,
+      recipient_id,
+      chain_id,
+      signer_address,
+      amount,
+      created_at
+    FROM point_transfer
+    WHERE "#,
+  );
+
+  query.push(" (sender_id = ").push_bind(user_id);
+  query.push(" OR recipient_id = ").push_bind(user_id);
+  query.push(") AND ");
+  let mut sep = query.separated(" AND ");
+  must_bind!(sep, "project_id" = project_id);
+  maybe_bind!(sep, "created_at" <= p.filter.created_before);
+  maybe_bind!(sep, "created_at" >= p.filter.created_after);
+
+  maybe_order_by(&mut query, &p.order, vec!["amount", "created_at"])?;
+  offset_limit!(query, p.offset, p.limit);
+
+  query
+    .build_query_as()
+    .fetch_all(db)
+    .await
+    .map_err(handle_pg_error)
+}
@@ -26,6 +26,8 @@ pub struct Project {
   pub networks: Json<Networks>,
   pub feature_from: Option<NaiveDate>,
   pub feature_until: Option<NaiveDate>,
+  pub point_transfer: bool,
+  pub point_transfer_daily_limit: Option<i64>,
   pub created_at: DateTime<Utc>,
   pub updated_at: Option<DateTime<Utc>>,
   pub description: Option<String>,
@@ -51,6 +53,8 @@ pub async fn list(db: &PgPool, p: super::ListParams<ProjectFilter>) -> Result<Ve
       networks,
       feature_from,
       feature_until,
+      point_transfer,
+      point_transfer_daily_limit,
       created_at,
       updated_at,
       description
@@ -91,6 +95,8 @@ pub async fn get(db: &PgPool, org_id: Uuid, project_id: Uuid) -> Result<Project,
       networks AS "networks: Json<Networks>",
       feature_from,
       feature_until,
+      point_transfer,
+      point_transfer_daily_limit,
       created_at,
       updated_at,
       description
@@ -158,6 +164,8 @@ pub struct UpdateParams {
   pub feature_from: Option<NaiveDate>,
   #[serde_as(as = "NoneAsEmptyString")]
   pub feature_until: Option<NaiveDate>,
+  pub point_transfer: Option<bool>,
+  pub point_transfer_daily_limit: Option<i64>,
 }
 
 // update a project
@@ -170,6 +178,9 @@ pub async fn update(
   if p.is_empty() {
     return Err(Error::EmptyUpdateSet);
   }
+  if p.point_transfer_daily_limit.is_some_and(|n| n < 0) {
+    return Err(Error::Validation);
+  }
   let mut query = QueryBuilder::<Postgres>::new("UPDATE project SET");
   let mut sep = query.separated(", ");
   sep.push(" updated_at = NOW() ");
@@ -181,6 +192,11 @@ pub async fn update(
   maybe_bind!(sep, "networks" = p.networks, Json);
   maybe_bind!(sep, "feature_from" = p.feature_from);
   maybe_bind!(sep, "feature_until" = p.feature_until);
+  maybe_bind!(sep, "point_transfer" = p.point_transfer);
+  maybe_bind!(
+    sep,
+    "point_transfer_daily_limit" = p.point_transfer_daily_limit
+  );
   query.push(" WHERE org_id = ").push_bind(org_id);
   query.push(" AND id = ").push_bind(project_id);
   query.push(" RETURNING updated_at");
@@ -204,6 +220,8 @@ pub struct ReplaceParams {
   pub feature_from: Option<NaiveDate>,
   #[serde_as(as = "NoneAsEmptyString")]
   pub feature_until: Option<NaiveDate>,
+  pub point_transfer: Option<bool>,
+  pub point_transfer_daily_limit: Option<i64>,
 }
 
 // replace a project
@@ -213,6 +231,9 @@ pub async fn replace(
   project_id: Uuid,
   p: ReplaceParams,
 ) -> Result<UpdateResult, Error> {
+  if p.point_transfer_daily_limit.is_some_and(|n| n < 0) {
+    return Err(Error::Validation);
+  }
   let mut query = QueryBuilder::<Postgres>::new("UPDATE project SET");
   let mut sep = query.separated(", ");
   sep.push(" updated_at = NOW() ");
@@ -224,6 +245,11 @@ pub async fn replace(
   must_bind!(sep, "networks" = Json(p.networks));
   maybe_bind!(sep, "feature_from" = p.feature_from);
   maybe_bind!(sep, "feature_until" = p.feature_until);
+  maybe_bind!(sep, "point_transfer" = p.point_transfer);
+  maybe_bind!(
+    sep,
+    "point_transfer_daily_limit" = p.point_transfer_daily_limit
+  );
   query.push(" WHERE org_id = ").push_bind(org_id);
   query.push(" AND id = ").push_bind(project_id);
   query.push(" RETURNING updated_at");
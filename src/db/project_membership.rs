@@ -43,7 +43,7 @@ pub async fn list(
         project_id,
         user_id,
         SUM(balance)::BIGINT AS balance,
-        SUM(value)::BIGINT AS point
+        COALESCE(SUM(value) FILTER (WHERE voided_at IS NULL AND transfer_id IS NULL), 0)::BIGINT AS point
       FROM voucher
       GROUP BY (project_id, user_id)
     ) AS v
@@ -95,3 +95,23 @@ pub async fn join(
 
   Ok(JoinResult { subscribed: true })
 }
+
+// check whether a user is subscribed to a project's club
+pub async fn is_member(db: &PgPool, project_id: Uuid, user_id: &str) -> Result<bool, super::Error> {
+  let res = query!(
+    r#"SELECT EXISTS (
+      SELECT 1
+      FROM project__user
+      WHERE project_id = $1
+        AND user_id = $2
+        AND subscribed IS NOT false
+    ) AS "member!""#,
+    project_id,
+    user_id,
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(res.member)
+}
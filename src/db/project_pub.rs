@@ -21,6 +21,8 @@ pub struct PubProject {
   pub networks: Json<Networks>,
   pub feature_from: Option<NaiveDate>,
   pub feature_until: Option<NaiveDate>,
+  pub point_transfer: bool,
+  pub point_transfer_daily_limit: Option<i64>,
   pub created_at: DateTime<Utc>,
   pub updated_at: Option<DateTime<Utc>>,
   pub description: Option<String>,
@@ -41,6 +43,8 @@ pub async fn list(
       networks,
       feature_from,
       feature_until,
+      point_transfer,
+      point_transfer_daily_limit,
       created_at,
       updated_at,
       description
@@ -81,6 +85,8 @@ pub async fn get(db: &PgPool, project_id: Uuid) -> Result<PubProject, Error> {
       networks AS "networks: Json<Networks>",
       feature_from,
       feature_until,
+      point_transfer,
+      point_transfer_daily_limit,
       created_at,
       updated_at,
       description
@@ -0,0 +1,706 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::{prelude::FromRow, query, query_as, types::Json, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::{
+  handle_pg_error,
+  reward::{spend_points_in, PointPool, PointRewardUpdateResult},
+  voucher, CreateResult, Error,
+};
+
+/// A reward given away by draw among users who spent points on tickets. `seed` is only shown
+/// once drawn, until then `seed_hash`, its SHA-256, commits to it.
+#[derive(FromRow, Serialize, Debug)]
+pub struct Raffle {
+  pub id: Uuid,
+  pub org_id: Uuid,
+  pub project_id: Uuid,
+  pub reward_id: Uuid,
+  pub name: String,
+  pub ticket_point: i64,
+  pub user_tickets: Option<i64>,
+  pub winners: i64,
+  pub refund: bool,
+  pub seed: Option<String>,
+  pub seed_hash: String,
+  pub draw_at: DateTime<Utc>,
+  pub drawn_at: Option<DateTime<Utc>>,
+  pub tickets: i64,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// The tickets of a user in a raffle, in the order the draw walks them.
+#[derive(FromRow, Serialize, Debug)]
+pub struct Entrant {
+  pub user_id: String,
+  pub tickets: i64,
+  pub won: Option<bool>,
+}
+
+// list the raffles of a org or a project, latest draw first
+pub async fn list(
+  db: &PgPool,
+  org_id: Option<Uuid>,
+  project_id: Option<Uuid>,
+) -> Result<Vec<Raffle>, Error> {
+  query_as!(
+    Raffle,
+    r#"SELECT
+      id,
+      org_id,
+      project_id,
+      reward_id,
+      name,
+      ticket_point,
+      user_tickets,
+      winners,
+      refund,
+      CASE WHEN drawn_at IS NULL THEN NULL ELSE seed END AS seed,
+      seed_hash,
+      draw_at,
+      drawn_at,
+      (
+        SELECT COALESCE(SUM(tickets), 0)
+        FROM raffle_entry
+        WHERE raffle_id = r.id
+      )::BIGINT AS "tickets!",
+      created_at,
+      updated_at
+    FROM raffle r
+    WHERE ($1::UUID IS NULL OR org_id = $1)
+      AND ($2::UUID IS NULL OR project_id = $2)
+    ORDER BY draw_at DESC"#,
+    org_id,
+    project_id
+  )
+  .fetch_all(db)
+  .await
+  .map_err(handle_pg_error)
+}
+
+// get a raffle of a org or a project
+pub async fn get(
+  db: &PgPool,
+  org_id: Option<Uuid>,
+  project_id: Option<Uuid>,
+  raffle_id: Uuid,
+) -> Result<Raffle, Error> {
+  query_as!(
+    Raffle,
+    r#"SELECT
+      id,
+      org_id,
+      project_id,
+      reward_id,
+      name,
+      ticket_point,
+      user_tickets,
+      winners,
+      refund,
+      CASE WHEN drawn_at IS NULL THEN NULL ELSE seed END AS seed,
+      seed_hash,
+      draw_at,
+      drawn_at,
+      (
+        SELECT COALESCE(SUM(tickets), 0)
+        FROM raffle_entry
+        WHERE raffle_id = r.id
+      )::BIGINT AS "tickets!",
+      created_at,
+      updated_at
+    FROM raffle r
+    WHERE id = $3
+      AND ($1::UUID IS NULL OR org_id = $1)
+      AND ($2::UUID IS NULL OR project_id = $2)"#,
+    org_id,
+    project_id,
+    raffle_id
+  )
+  .fetch_one(db)
+  .await
+  .map_err(handle_pg_error)
+}
+
+pub struct CreateParam<'a> {
+  pub id: Uuid,
+  pub org_id: Uuid,
+  pub project_id: Uuid,
+  pub reward_id: Uuid,
+  pub name: &'a str,
+  pub ticket_point: i64,
+  pub user_tickets: Option<i64>,
+  pub winners: i64,
+  pub refund: bool,
+  pub seed: &'a str,
+  pub seed_hash: &'a str,
+  pub draw_at: DateTime<Utc>,
+}
+
+// create a raffle of a reward active and approved for the project, reserving a coupon for each
+// winner. refuses with LimitReached when there aren't enough left
+pub async fn create(db: &PgPool, p: CreateParam<'_>) -> Result<CreateResult, Error> {
+  let mut tx = db.begin().await?;
+  query!(
+    "SELECT reward_id
+    FROM project__reward
+    WHERE project_id = $1
+      AND reward_id = $2
+      AND active = true
+      AND approved = true
+    FOR SHARE",
+    p.project_id,
+    p.reward_id
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  let res = query_as!(
+    CreateResult,
+    "INSERT INTO raffle (
+      id,
+      org_id,
+      project_id,
+      reward_id,
+      name,
+      ticket_point,
+      user_tickets,
+      winners,
+      refund,
+      seed,
+      seed_hash,
+      draw_at
+    )
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+    RETURNING created_at",
+    p.id,
+    p.org_id,
+    p.project_id,
+    p.reward_id,
+    p.name,
+    p.ticket_point,
+    p.user_tickets,
+    p.winners,
+    p.refund,
+    p.seed,
+    p.seed_hash,
+    p.draw_at
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  let reserved = query!(
+    "UPDATE coupon
+    SET
+      raffle_id = $1,
+      updated_at = NOW()
+    WHERE reward_id = $2
+      AND number IN (
+        SELECT number
+        FROM coupon
+        WHERE reward_id = $2
+          AND user_id IS NULL
+          AND minted_at IS NULL
+          AND raffle_id IS NULL
+        ORDER BY number ASC
+        LIMIT $3
+        FOR UPDATE
+      )",
+    p.id,
+    p.reward_id,
+    p.winners
+  )
+  .execute(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+  if (reserved.rows_affected() as i64) < p.winners {
+    return Err(Error::LimitReached);
+  }
+  tx.commit().await?;
+
+  Ok(res)
+}
+
+#[derive(Serialize)]
+pub struct BuyResult {
+  pub id: i64,
+  pub tickets: i64,
+  pub point: i64,
+  pub vouchers: Vec<PointRewardUpdateResult>,
+  pub balance: i64,
+  pub created_at: DateTime<Utc>,
+}
+
+// buy tickets of a raffle still open, spending points like when minting a reward
+pub async fn buy(
+  db: &PgPool,
+  project_id: Uuid,
+  raffle_id: Uuid,
+  user_id: &str,
+  tickets: i64,
+) -> Result<BuyResult, Error> {
+  if tickets <= 0 {
+    return Err(Error::Validation);
+  }
+
+  let mut tx = db.begin().await?;
+  // shared with other buyers, the draw waits for them
+  let raffle = query!(
+    "SELECT
+      ticket_point,
+      user_tickets
+    FROM raffle
+    WHERE id = $1
+      AND project_id = $2
+      AND drawn_at IS NULL
+      AND draw_at > NOW()
+    FOR SHARE",
+    raffle_id,
+    project_id
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+  let point = raffle
+    .ticket_point
+    .checked_mul(tickets)
+    .ok_or(Error::Validation)?;
+
+  if voucher::settle_debt_in(&mut tx, project_id, user_id).await? > 0 {
+    tx.commit().await?;
+    return Err(Error::Debt);
+  }
+
+  // spending locks the user's vouchers, so concurrent purchases see each other in the limit
+  let (vouchers, balance) =
+    spend_points_in(&mut tx, PointPool::Project(project_id), user_id, point).await?;
+
+  if let Some(limit) = raffle.user_tickets {
+    let bought = query!(
+      r#"SELECT
+        COALESCE(SUM(tickets), 0)::BIGINT AS "bought!"
+      FROM raffle_entry
+      WHERE raffle_id = $1
+        AND user_id = $2"#,
+      raffle_id,
+      user_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(handle_pg_error)?
+    .bought;
+    if bought + tickets > limit {
+      return Err(Error::LimitReached);
+    }
+  }
+
+  let entry = query!(
+    "INSERT INTO raffle_entry (
+      raffle_id,
+      user_id,
+      tickets,
+      point,
+      spent
+    )
+    VALUES ($1, $2, $3, $4, $5)
+    RETURNING
+      id,
+      created_at",
+    raffle_id,
+    user_id,
+    tickets,
+    point,
+    Json(&vouchers) as _
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  query!(
+    "INSERT INTO voucher_event (
+      org_id,
+      project_id,
+      campaign_id,
+      chain_id,
+      signer_address,
+      user_id,
+      task_id,
+      kind,
+      amount
+    )
+    SELECT
+      v.org_id,
+      v.project_id,
+      v.campaign_id,
+      v.chain_id,
+      v.signer_address,
+      v.user_id,
+      v.task_id,
+      'raffle_ticket',
+      s.minted
+    FROM raffle_entry e
+    CROSS JOIN jsonb_to_recordset(e.spent)
+      AS s(campaign_id UUID, chain_id BIGINT, signer_address TEXT, task_id TEXT, minted BIGINT)
+    JOIN voucher v
+    ON v.campaign_id = s.campaign_id
+      AND v.chain_id = s.chain_id
+      AND v.signer_address = s.signer_address
+      AND v.task_id = s.task_id
+    WHERE e.id = $1",
+    entry.id
+  )
+  .execute(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  tx.commit().await?;
+
+  Ok(BuyResult {
+    id: entry.id,
+    tickets,
+    point,
+    vouchers,
+    balance,
+    created_at: entry.created_at,
+  })
+}
+
+// list who entered a raffle and how many tickets they hold, in draw order
+pub async fn list_entrants(
+  db: &PgPool,
+  project_id: Uuid,
+  raffle_id: Uuid,
+) -> Result<Vec<Entrant>, Error> {
+  query_as!(
+    Entrant,
+    r#"SELECT
+      e.user_id,
+      SUM(e.tickets)::BIGINT AS "tickets!",
+      BOOL_OR(e.won) AS won
+    FROM raffle_entry e
+    JOIN raffle r
+    ON r.id = e.raffle_id
+    WHERE e.raffle_id = $1
+      AND r.project_id = $2
+    GROUP BY e.user_id
+    ORDER BY MIN(e.id)"#,
+    raffle_id,
+    project_id
+  )
+  .fetch_all(db)
+  .await
+  .map_err(handle_pg_error)
+}
+
+/// Pick up to `count` winners among `entrants`, by index, each with a chance proportional to
+/// their tickets and winning at most once. Round `i` takes the first 8 bytes of the SHA-256 of
+/// `"{seed}:{i}"` as a big-endian integer, modulo the tickets left, and walks the entrants in
+/// order to find who holds that ticket. Anyone can replay it once the seed is revealed.
+fn pick_winners(seed: &str, entrants: &[Entrant], count: usize) -> Vec<usize> {
+  let mut left = entrants
+    .iter()
+    .map(|e| e.tickets.max(0) as u64)
+    .collect::<Vec<u64>>();
+  let mut winners = Vec::<usize>::new();
+  for i in 0..count {
+    let total = left.iter().sum::<u64>();
+    if total == 0 {
+      break;
+    }
+    let digest = Sha256::digest(format!("{}:{}", seed, i).as_bytes());
+    let mut ticket = u64::from_be_bytes(digest[..8].try_into().unwrap()) % total;
+    for (j, tickets) in left.iter_mut().enumerate() {
+      if ticket < *tickets {
+        winners.push(j);
+        *tickets = 0;
+        break;
+      }
+      ticket -= *tickets;
+    }
+  }
+
+  winners
+}
+
+// draw a raffle due, handing its reserved coupons to the winners and refunding the others when
+// the raffle says so, or when it can't give out all of its coupons. returns whether it was drawn
+pub async fn draw(db: &PgPool, raffle_id: Uuid) -> Result<bool, Error> {
+  let mut tx = db.begin().await?;
+  let Some(raffle) = query!(
+    "SELECT
+      project_id,
+      reward_id,
+      winners,
+      refund,
+      seed
+    FROM raffle
+    WHERE id = $1
+      AND drawn_at IS NULL
+      AND draw_at <= NOW()
+    FOR UPDATE",
+    raffle_id
+  )
+  .fetch_optional(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?
+  else {
+    return Ok(false);
+  };
+
+  let entrants = query_as!(
+    Entrant,
+    r#"SELECT
+      user_id,
+      SUM(tickets)::BIGINT AS "tickets!",
+      NULL::BOOL AS won
+    FROM raffle_entry
+    WHERE raffle_id = $1
+    GROUP BY user_id
+    ORDER BY MIN(id)"#,
+    raffle_id
+  )
+  .fetch_all(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  // the reward may have been unlinked or deactivated since the raffle was created
+  let available = query!(
+    r#"SELECT EXISTS (
+      SELECT 1
+      FROM project__reward
+      WHERE project_id = $1
+        AND reward_id = $2
+        AND active = true
+        AND approved = true
+    ) AS "available!""#,
+    raffle.project_id,
+    raffle.reward_id
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?
+  .available;
+  let coupons = if available {
+    query!(
+      "SELECT number
+      FROM coupon
+      WHERE raffle_id = $1
+        AND user_id IS NULL
+        AND minted_at IS NULL
+      ORDER BY number ASC
+      FOR UPDATE",
+      raffle_id
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(handle_pg_error)?
+  } else {
+    tracing::warn!(
+      "Raffle {} reward is no longer available, refunding every entry",
+      raffle_id
+    );
+    Vec::new()
+  };
+  // reserved coupons can still go missing, e.g. deleted by hand, then nobody loses out on them
+  let short = available && (coupons.len() as i64) < raffle.winners;
+  if short {
+    tracing::warn!(
+      "Raffle {} draws {} winners, only {} coupons are left, refunding every loser",
+      raffle_id,
+      raffle.winners,
+      coupons.len()
+    );
+  }
+
+  let winners = pick_winners(&raffle.seed, &entrants, coupons.len())
+    .into_iter()
+    .map(|i| entrants[i].user_id.clone())
+    .collect::<Vec<String>>();
+  let numbers = coupons
+    .iter()
+    .take(winners.len())
+    .map(|c| c.number)
+    .collect::<Vec<i64>>();
+
+  query!(
+    "WITH won AS (
+      SELECT *
+      FROM UNNEST($3::TEXT[], $4::BIGINT[]) WITH ORDINALITY AS t(user_id, number, rank)
+    ),
+    minted AS (
+      UPDATE coupon c
+      SET
+        user_id = won.user_id,
+        minted_by = won.user_id,
+        minted_at = NOW(),
+        updated_at = NOW()
+      FROM won
+      WHERE c.reward_id = $2
+        AND c.number = won.number
+    )
+    INSERT INTO raffle_winner (
+      raffle_id,
+      rank,
+      user_id,
+      reward_id,
+      number
+    )
+    SELECT $1, rank, user_id, $2, number
+    FROM won",
+    raffle_id,
+    raffle.reward_id,
+    &winners,
+    &numbers
+  )
+  .execute(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  query!(
+    "UPDATE raffle_entry
+    SET won = user_id = ANY($2)
+    WHERE raffle_id = $1",
+    raffle_id,
+    &winners
+  )
+  .execute(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  if raffle.refund || !available || short {
+    refund_in(&mut tx, raffle_id).await?;
+  }
+
+  // coupons left over when there are fewer entrants than winners go back to the mints
+  query!(
+    "UPDATE coupon
+    SET
+      raffle_id = NULL,
+      updated_at = NOW()
+    WHERE raffle_id = $1
+      AND minted_at IS NULL",
+    raffle_id
+  )
+  .execute(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  query!(
+    "UPDATE raffle
+    SET
+      drawn_at = NOW(),
+      updated_at = NOW()
+    WHERE id = $1",
+    raffle_id
+  )
+  .execute(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+  tx.commit().await?;
+
+  Ok(true)
+}
+
+// give the points of losing tickets back to the vouchers they were spent from
+async fn refund_in(tx: &mut Transaction<'_, Postgres>, raffle_id: Uuid) -> Result<(), Error> {
+  query!(
+    "WITH refunded AS (
+      UPDATE raffle_entry
+      SET refunded_at = NOW()
+      WHERE raffle_id = $1
+        AND won = false
+      RETURNING spent
+    ),
+    spent AS (
+      SELECT
+        s.campaign_id,
+        s.chain_id,
+        s.signer_address,
+        s.task_id,
+        SUM(s.minted)::BIGINT AS amount
+      FROM refunded e
+      CROSS JOIN jsonb_to_recordset(e.spent)
+        AS s(campaign_id UUID, chain_id BIGINT, signer_address TEXT, task_id TEXT, minted BIGINT)
+      GROUP BY s.campaign_id, s.chain_id, s.signer_address, s.task_id
+    ),
+    credited AS (
+      UPDATE voucher v
+      SET
+        balance = v.balance + s.amount,
+        updated_at = NOW()
+      FROM spent s
+      WHERE v.campaign_id = s.campaign_id
+        AND v.chain_id = s.chain_id
+        AND v.signer_address = s.signer_address
+        AND v.task_id = s.task_id
+      RETURNING
+        v.org_id,
+        v.project_id,
+        v.campaign_id,
+        v.chain_id,
+        v.signer_address,
+        v.user_id,
+        v.task_id,
+        s.amount
+    )
+    INSERT INTO voucher_event (
+      org_id,
+      project_id,
+      campaign_id,
+      chain_id,
+      signer_address,
+      user_id,
+      task_id,
+      kind,
+      amount
+    )
+    SELECT
+      org_id,
+      project_id,
+      campaign_id,
+      chain_id,
+      signer_address,
+      user_id,
+      task_id,
+      'raffle_refund',
+      amount
+    FROM credited",
+    raffle_id
+  )
+  .execute(&mut **tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(())
+}
+
+// draw the raffles that are due, returning how many were. one failing doesn't hold up the others
+pub async fn draw_due(db: &PgPool) -> Result<u64, Error> {
+  let due = query!(
+    "SELECT id
+    FROM raffle
+    WHERE drawn_at IS NULL
+      AND draw_at <= NOW()
+    ORDER BY draw_at ASC"
+  )
+  .fetch_all(db)
+  .await
+  .map_err(handle_pg_error)?;
+
+  let mut drawn = 0;
+  for r in due {
+    match draw(db, r.id).await {
+      Ok(true) => drawn += 1,
+      Ok(false) => {}
+      Err(err) => {
+        tracing::error!("Error drawing raffle {}: {}", r.id, err.to_string());
+      }
+    }
+  }
+
+  Ok(drawn)
+}
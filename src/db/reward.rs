@@ -2,12 +2,14 @@ use chrono::{DateTime, NaiveDate, Utc};
 use is_empty::IsEmpty;
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, NoneAsEmptyString};
-use sqlx::{prelude::FromRow, query, query_as, PgPool, Postgres, QueryBuilder};
+use sqlx::{prelude::FromRow, query, query_as, PgPool, Postgres, QueryBuilder, Transaction};
 use uuid::Uuid;
 
 use crate::db::sqlx_macro::{must_bind, maybe_bind, offset_limit};
 
-use super::{coupon::Coupon, handle_pg_error, maybe_order_by, CreateResult, Error, UpdateResult};
+use super::{
+  coupon::Coupon, handle_pg_error, maybe_order_by, voucher, CreateResult, Error, UpdateResult,
+};
 
 #[derive(FromRow, Serialize)]
 pub struct Reward {
@@ -298,6 +300,10 @@ pub async fn mint_project_reward(
   project_id: Uuid,
 ) -> Result<MintProjectRewardResult, Error> {
   let mut tx = db.begin().await?;
+  if voucher::settle_debt_in(&mut tx, project_id, user_id).await? > 0 {
+    tx.commit().await?;
+    return Err(Error::Debt);
+  }
 
   let coupon = query!(
     r#"SELECT
@@ -308,6 +314,7 @@ pub async fn mint_project_reward(
   FROM coupon
   WHERE user_id IS NULL
     AND minted_at IS NULL
+    AND raffle_id IS NULL
     AND reward_id = $1
   ORDER BY number ASC
   LIMIT 1
@@ -329,7 +336,9 @@ pub async fn mint_project_reward(
       AND active = true
       AND approved = true
       AND point IS NOT NULL
-      AND (max_mint IS NULL OR (SELECT COUNT(user_id) FROM voucher WHERE project_id = $1) < max_mint)
+      AND (max_mint IS NULL
+        OR max_mint = 0
+        OR (SELECT COUNT(user_id) FROM voucher WHERE project_id = $1) < max_mint)
       "#,
     project_id,
     reward_id
@@ -341,12 +350,10 @@ pub async fn mint_project_reward(
   if let Some(user_mint) = project_reward.user_mint {
     let c = query!(
       r#"SELECT
-        COUNT(user_id) AS "count!"
+        COUNT(*) AS "count!"
       FROM coupon
-      WHERE user_id = $1
+      WHERE minted_by = $1
         AND reward_id = $2
-        AND minted_at IS NULL
-      LIMIT 1
       "#,
       user_id,
       reward_id
@@ -359,151 +366,50 @@ pub async fn mint_project_reward(
     }
   }
 
-  let point = query!(
-    r#"SELECT
-      SUM(balance)::BIGINT
-    FROM voucher
-    WHERE project_id = $1
-      AND user_id = $2
-      AND valid_from <= NOW()
-      AND (valid_until IS NULL OR valid_until >= NOW())
-      "#,
-    project_id,
+  let (vouchers, balance) = spend_points_in(
+    &mut tx,
+    PointPool::Project(project_id),
     user_id,
+    project_reward.point,
+  )
+  .await?;
+
+  let res = query!(
+    r#"UPDATE coupon SET
+      user_id = $3,
+      minted_by = $3,
+      minted_at = NOW(),
+      updated_at = NOW()
+    WHERE user_id IS NULL
+      AND minted_at IS NULL
+      AND raffle_id IS NULL
+      AND reward_id = $1
+      AND number = $2
+    RETURNING minted_at AS "minted_at!"
+    "#,
+    reward_id,
+    coupon.number,
+    user_id
   )
   .fetch_one(&mut *tx)
   .await
   .map_err(handle_pg_error)?;
 
-  let point_total = point.sum.unwrap_or_default();
-
-  if point_total >= project_reward.point {
-    let vouchers = query!(
-      r#"SELECT
-      campaign_id,
-      chain_id,
-      signer_address,
-      task_id,
-      balance
-    FROM voucher
-    WHERE project_id = $1
-      AND user_id = $2
-      AND valid_from <= NOW()
-      AND (valid_until IS NULL OR valid_until >= NOW())
-    ORDER BY
-      valid_until ASC,
-      created_at ASC
-      "#,
-      project_id,
-      user_id,
-    )
-    .fetch_all(&mut *tx)
-    .await
-    .map_err(handle_pg_error)?;
-
-    let mut vouchers_update_results = Vec::<PointRewardUpdateResult>::new();
-    let mut total: i64 = 0;
-    for r in vouchers {
-      total += r.balance;
-      if total < project_reward.point {
-        let res = query_as!(
-          super::UpdateResult,
-          r#"UPDATE voucher SET
-            balance = 0,
-            updated_at = NOW()
-          WHERE campaign_id = $1
-            AND chain_id  = $2
-            AND signer_address  = $3
-            AND task_id  = $4
-          RETURNING updated_at AS "updated_at!"
-          "#,
-          r.campaign_id,
-          r.chain_id,
-          r.signer_address,
-          r.task_id
-        )
-        .fetch_one(&mut *tx)
-        .await
-        .map_err(handle_pg_error)?;
-        vouchers_update_results.push(PointRewardUpdateResult {
-          campaign_id: r.campaign_id,
-          chain_id: r.chain_id,
-          signer_address: r.signer_address,
-          task_id: r.task_id,
-          minted: r.balance,
-          updated_at: res.updated_at,
-        });
-      } else {
-        let remainder = total - project_reward.point;
-        let res = query_as!(
-          super::UpdateResult,
-          r#"UPDATE voucher SET
-            balance = $5,
-            updated_at = NOW()
-          WHERE campaign_id = $1
-            AND chain_id  = $2
-            AND signer_address  = $3
-            AND task_id  = $4
-          RETURNING updated_at AS "updated_at!"
-          "#,
-          r.campaign_id,
-          r.chain_id,
-          r.signer_address,
-          r.task_id,
-          remainder
-        )
-        .fetch_one(&mut *tx)
-        .await
-        .map_err(handle_pg_error)?;
-        vouchers_update_results.push(PointRewardUpdateResult {
-          campaign_id: r.campaign_id,
-          chain_id: r.chain_id,
-          signer_address: r.signer_address,
-          task_id: r.task_id,
-          minted: r.balance,
-          updated_at: res.updated_at,
-        });
-        break;
-      }
-    }
+  tx.commit().await.map_err(handle_pg_error)?;
 
-    let res = query!(
-      r#"UPDATE coupon SET
-        user_id = $3,
-        minted_at = NOW(),
-        updated_at = NOW()
-      WHERE user_id IS NULL
-        AND minted_at IS NULL
-        AND reward_id = $1
-        AND number = $2
-      RETURNING minted_at AS "minted_at!"
-      "#,
+  Ok(MintProjectRewardResult {
+    coupon: Coupon {
       reward_id,
-      coupon.number,
-      user_id
-    )
-    .fetch_one(&mut *tx)
-    .await
-    .map_err(handle_pg_error)?;
-
-    tx.commit().await.map_err(handle_pg_error)?;
-
-    Ok(MintProjectRewardResult {
-      coupon: Coupon {
-        reward_id,
-        number: coupon.number,
-        url: coupon.url,
-        user_id: Some(user_id.to_owned()),
-        minted_at: Some(res.minted_at),
-        created_at: coupon.created_at,
-        updated_at: Some(res.minted_at),
-      },
-      vouchers: vouchers_update_results,
-      balance: point_total - project_reward.point,
-    })
-  } else {
-    Err(Error::NotFound)
-  }
+      number: coupon.number,
+      url: coupon.url,
+      user_id: Some(user_id.to_owned()),
+      minted_at: Some(res.minted_at),
+      created_at: coupon.created_at,
+      updated_at: Some(res.minted_at),
+    },
+    vouchers,
+    balance,
+  })
 }
 
 #[derive(sqlx::FromRow, Serialize)]
@@ -521,6 +427,14 @@ pub async fn mint_campaign_reward(
   campaign_id: Uuid,
 ) -> Result<MintCampaignRewardResult, Error> {
   let mut tx = db.begin().await?;
+  let campaign = query!("SELECT project_id FROM campaign WHERE id = $1", campaign_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(handle_pg_error)?;
+  if voucher::settle_debt_in(&mut tx, campaign.project_id, user_id).await? > 0 {
+    tx.commit().await?;
+    return Err(Error::Debt);
+  }
 
   let coupon = query!(
     r#"SELECT
@@ -531,6 +445,7 @@ pub async fn mint_campaign_reward(
   FROM coupon
   WHERE user_id IS NULL
     AND minted_at IS NULL
+    AND raffle_id IS NULL
     AND reward_id = $1
   ORDER BY number ASC
   LIMIT 1
@@ -571,12 +486,10 @@ pub async fn mint_campaign_reward(
   if user_mint > 0 {
     let c = query!(
       r#"SELECT
-        COUNT(user_id) AS "count!"
+        COUNT(*) AS "count!"
       FROM coupon
-      WHERE user_id = $1
+      WHERE minted_by = $1
         AND reward_id = $2
-        AND minted_at IS NULL
-      LIMIT 1
       "#,
       user_id,
       reward_id
@@ -589,149 +502,287 @@ pub async fn mint_campaign_reward(
     }
   }
 
-  let point = query!(
-    r#"SELECT
-      SUM(balance)::BIGINT
-    FROM voucher
-    WHERE campaign_id = $1
-      AND user_id = $2
-      AND valid_from <= NOW()
-      AND (valid_until IS NULL OR valid_until >= NOW())
-      "#,
-    campaign_id,
+  let (vouchers, balance) = spend_points_in(
+    &mut tx,
+    PointPool::Campaign(campaign_id),
     user_id,
+    campaign_reward.point,
+  )
+  .await?;
+
+  let res = query!(
+    r#"UPDATE coupon SET
+      user_id = $3,
+      minted_by = $3,
+      minted_at = NOW(),
+      updated_at = NOW()
+    WHERE user_id IS NULL
+      AND minted_at IS NULL
+      AND raffle_id IS NULL
+      AND reward_id = $1
+      AND number = $2
+    RETURNING minted_at AS "minted_at!"
+    "#,
+    reward_id,
+    coupon.number,
+    user_id
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  tx.commit().await.map_err(handle_pg_error)?;
+
+  Ok(MintCampaignRewardResult {
+    coupon: Coupon {
+      reward_id,
+      number: coupon.number,
+      url: coupon.url,
+      user_id: Some(user_id.to_owned()),
+      minted_at: Some(res.minted_at),
+      created_at: coupon.created_at,
+      updated_at: Some(res.minted_at),
+    },
+    vouchers,
+    balance,
+  })
+}
+
+#[derive(sqlx::FromRow, Serialize)]
+pub struct MintOrgRewardResult {
+  pub coupon: Coupon,
+  pub vouchers: Vec<PointRewardUpdateResult>,
+  pub balance: i64,
+}
+
+// mint a reward of a org's catalog, spending points from any of the org's projects
+pub async fn mint_org_reward(
+  db: &PgPool,
+  user_id: &str,
+  reward_id: Uuid,
+  org_id: Uuid,
+) -> Result<MintOrgRewardResult, Error> {
+  let mut tx = db.begin().await?;
+  if voucher::settle_org_debt_in(&mut tx, org_id, user_id).await? > 0 {
+    tx.commit().await?;
+    return Err(Error::Debt);
+  }
+
+  let coupon = query!(
+    r#"SELECT
+    reward_id,
+    number,
+    url,
+    created_at
+  FROM coupon
+  WHERE user_id IS NULL
+    AND minted_at IS NULL
+    AND raffle_id IS NULL
+    AND reward_id = $1
+  ORDER BY number ASC
+  LIMIT 1
+  FOR UPDATE
+  "#,
+    reward_id
   )
   .fetch_one(&mut *tx)
   .await
   .map_err(handle_pg_error)?;
 
-  let point_total = point.sum.unwrap_or_default();
+  let org_reward = query!(
+    r#"SELECT
+      point,
+      user_mint
+    FROM org__reward
+    WHERE org_id = $1
+      AND reward_id = $2
+      AND active = true
+      AND approved = true
+      AND (max_mint IS NULL
+        OR max_mint = 0
+        OR (
+          SELECT COUNT(user_id)
+          FROM coupon
+          WHERE reward_id = $2
+        ) < max_mint)
+      "#,
+    org_id,
+    reward_id
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
 
-  if point_total >= campaign_reward.point {
-    let vouchers = query!(
+  let user_mint = org_reward.user_mint.unwrap_or_default();
+  if user_mint > 0 {
+    let c = query!(
       r#"SELECT
+        COUNT(*) AS "count!"
+      FROM coupon
+      WHERE minted_by = $1
+        AND reward_id = $2
+      "#,
+      user_id,
+      reward_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(handle_pg_error)?;
+    if c.count >= user_mint {
+      return Err(Error::LimitReached);
+    }
+  }
+
+  let (vouchers, balance) =
+    spend_points_in(&mut tx, PointPool::Org(org_id), user_id, org_reward.point).await?;
+
+  let res = query!(
+    r#"UPDATE coupon SET
+      user_id = $3,
+      minted_by = $3,
+      minted_at = NOW(),
+      updated_at = NOW()
+    WHERE user_id IS NULL
+      AND minted_at IS NULL
+      AND raffle_id IS NULL
+      AND reward_id = $1
+      AND number = $2
+    RETURNING minted_at AS "minted_at!"
+    "#,
+    reward_id,
+    coupon.number,
+    user_id
+  )
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  tx.commit().await.map_err(handle_pg_error)?;
+
+  Ok(MintOrgRewardResult {
+    coupon: Coupon {
+      reward_id,
+      number: coupon.number,
+      url: coupon.url,
+      user_id: Some(user_id.to_owned()),
+      minted_at: Some(res.minted_at),
+      created_at: coupon.created_at,
+      updated_at: Some(res.minted_at),
+    },
+    vouchers,
+    balance,
+  })
+}
+
+/// Where points can be spent from.
+#[derive(Clone, Copy, Debug)]
+pub enum PointPool {
+  Org(Uuid),
+  Project(Uuid),
+  Campaign(Uuid),
+}
+
+impl PointPool {
+  fn org_id(&self) -> Option<Uuid> {
+    match self {
+      PointPool::Org(id) => Some(*id),
+      _ => None,
+    }
+  }
+
+  fn project_id(&self) -> Option<Uuid> {
+    match self {
+      PointPool::Project(id) => Some(*id),
+      _ => None,
+    }
+  }
+
+  fn campaign_id(&self) -> Option<Uuid> {
+    match self {
+      PointPool::Campaign(id) => Some(*id),
+      _ => None,
+    }
+  }
+}
+
+// spend a user's valid points, soonest to expire first, returning the vouchers spent from and
+// what is left. refuses with NotFound when there aren't enough, like the mints do
+pub(super) async fn spend_points_in(
+  tx: &mut Transaction<'_, Postgres>,
+  pool: PointPool,
+  user_id: &str,
+  point: i64,
+) -> Result<(Vec<PointRewardUpdateResult>, i64), Error> {
+  let vouchers = query!(
+    r#"SELECT
       campaign_id,
       chain_id,
       signer_address,
       task_id,
       balance
     FROM voucher
-    WHERE campaign_id = $1
-      AND user_id = $2
+    WHERE ($1::UUID IS NULL OR org_id = $1)
+      AND ($2::UUID IS NULL OR project_id = $2)
+      AND ($4::UUID IS NULL OR campaign_id = $4)
+      AND user_id = $3
+      AND balance > 0
       AND valid_from <= NOW()
       AND (valid_until IS NULL OR valid_until >= NOW())
     ORDER BY
       valid_until ASC,
       created_at ASC
-      "#,
-      campaign_id,
-      user_id,
-    )
-    .fetch_all(&mut *tx)
-    .await
-    .map_err(handle_pg_error)?;
+    FOR UPDATE"#,
+    pool.org_id(),
+    pool.project_id(),
+    user_id,
+    pool.campaign_id(),
+  )
+  .fetch_all(&mut **tx)
+  .await
+  .map_err(handle_pg_error)?;
 
-    let mut vouchers_update_results = Vec::<PointRewardUpdateResult>::new();
-    let mut total: i64 = 0;
-    for r in vouchers {
-      total += r.balance;
-      if total < campaign_reward.point {
-        let res = query_as!(
-          super::UpdateResult,
-          r#"UPDATE voucher SET
-            balance = 0,
-            updated_at = NOW()
-          WHERE campaign_id = $1
-            AND chain_id  = $2
-            AND signer_address  = $3
-            AND task_id  = $4
-          RETURNING updated_at AS "updated_at!"
-          "#,
-          r.campaign_id,
-          r.chain_id,
-          r.signer_address,
-          r.task_id
-        )
-        .fetch_one(&mut *tx)
-        .await
-        .map_err(handle_pg_error)?;
-        vouchers_update_results.push(PointRewardUpdateResult {
-          campaign_id: r.campaign_id,
-          chain_id: r.chain_id,
-          signer_address: r.signer_address,
-          task_id: r.task_id,
-          minted: r.balance,
-          updated_at: res.updated_at,
-        });
-      } else {
-        let remainder = total - campaign_reward.point;
-        let res = query_as!(
-          super::UpdateResult,
-          r#"UPDATE voucher SET
-            balance = $5,
-            updated_at = NOW()
-          WHERE campaign_id = $1
-            AND chain_id  = $2
-            AND signer_address  = $3
-            AND task_id  = $4
-          RETURNING updated_at AS "updated_at!"
-          "#,
-          r.campaign_id,
-          r.chain_id,
-          r.signer_address,
-          r.task_id,
-          remainder
-        )
-        .fetch_one(&mut *tx)
-        .await
-        .map_err(handle_pg_error)?;
-        vouchers_update_results.push(PointRewardUpdateResult {
-          campaign_id: r.campaign_id,
-          chain_id: r.chain_id,
-          signer_address: r.signer_address,
-          task_id: r.task_id,
-          minted: r.balance,
-          updated_at: res.updated_at,
-        });
-        break;
-      }
-    }
+  let point_total = vouchers.iter().map(|v| v.balance).sum::<i64>();
+  if point_total < point {
+    return Err(Error::NotFound);
+  }
 
-    let res = query!(
-      r#"UPDATE coupon SET
-        user_id = $3,
-        minted_at = NOW(),
+  let mut spent = Vec::<PointRewardUpdateResult>::new();
+  let mut left = point;
+  for r in vouchers {
+    if left == 0 {
+      break;
+    }
+    let minted = r.balance.min(left);
+    left -= minted;
+    let res = query_as!(
+      super::UpdateResult,
+      r#"UPDATE voucher SET
+        balance = balance - $5,
         updated_at = NOW()
-      WHERE user_id IS NULL
-        AND minted_at IS NULL
-        AND reward_id = $1
-        AND number = $2
-      RETURNING minted_at AS "minted_at!"
+      WHERE campaign_id = $1
+        AND chain_id = $2
+        AND signer_address = $3
+        AND task_id = $4
+      RETURNING updated_at AS "updated_at!"
       "#,
-      reward_id,
-      coupon.number,
-      user_id
+      r.campaign_id,
+      r.chain_id,
+      r.signer_address,
+      r.task_id,
+      minted
     )
-    .fetch_one(&mut *tx)
+    .fetch_one(&mut **tx)
     .await
     .map_err(handle_pg_error)?;
-
-    tx.commit().await.map_err(handle_pg_error)?;
-
-    Ok(MintCampaignRewardResult {
-      coupon: Coupon {
-        reward_id,
-        number: coupon.number,
-        url: coupon.url,
-        user_id: Some(user_id.to_owned()),
-        minted_at: Some(res.minted_at),
-        created_at: coupon.created_at,
-        updated_at: Some(res.minted_at),
-      },
-      vouchers: vouchers_update_results,
-      balance: point_total - campaign_reward.point,
-    })
-  } else {
-    Err(Error::NotFound)
+    spent.push(PointRewardUpdateResult {
+      campaign_id: r.campaign_id,
+      chain_id: r.chain_id,
+      signer_address: r.signer_address,
+      task_id: r.task_id,
+      minted,
+      updated_at: res.updated_at,
+    });
   }
+
+  Ok((spent, point_total - point))
 }
@@ -2,7 +2,7 @@ use chrono::{DateTime, NaiveDate, Utc};
 use is_empty::IsEmpty;
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, NoneAsEmptyString};
-use sqlx::{prelude::FromRow, query, query_as, PgPool, Postgres, QueryBuilder};
+use sqlx::{prelude::FromRow, query, query_as, PgPool, Postgres, QueryBuilder, Transaction};
 use uuid::Uuid;
 
 use crate::db::sqlx_macro::{must_bind, maybe_bind, offset_limit};
@@ -19,11 +19,17 @@ pub struct Voucher {
   pub user_id: String,
   pub task_id: String,
   pub value: i64,
+  pub base: i64,
+  pub bonus: i64,
+  pub multiplier_id: Option<Uuid>,
+  pub transfer_id: Option<Uuid>,
   pub balance: i64,
   pub valid_from: Option<NaiveDate>,
   pub valid_until: Option<NaiveDate>,
   pub created_at: DateTime<Utc>,
   pub updated_at: Option<DateTime<Utc>>,
+  pub voided_at: Option<DateTime<Utc>>,
+  pub expired_at: Option<DateTime<Utc>>,
 }
 
 pub async fn get_project_point(
@@ -89,11 +95,17 @@ pub async fn list(
     user_id,
     task_id,
     value,
+    base,
+    bonus,
+    multiplier_id,
+    transfer_id,
     balance,
     valid_from,
     valid_until,
     created_at,
-    updated_at
+    updated_at,
+    voided_at,
+    expired_at
     FROM voucher"#,
   );
 
@@ -142,11 +154,17 @@ pub async fn get(
       user_id,
       task_id,
       value,
+      base,
+      bonus,
+      multiplier_id,
+      transfer_id,
       balance,
       valid_from,
       valid_until,
       created_at,
-      updated_at
+      updated_at,
+      voided_at,
+      expired_at
     FROM voucher
     WHERE campaign_id = $1
       AND chain_id = $2
@@ -161,3 +179,253 @@ pub async fn get(
   .await
   .map_err(handle_pg_error)
 }
+
+// mark the vouchers past their validity as expired, logging the points lost with them
+pub async fn expire(db: &PgPool) -> Result<u64, Error> {
+  let res = query!(
+    "WITH expired AS (
+      UPDATE voucher
+      SET
+        expired_at = NOW(),
+        updated_at = NOW()
+      WHERE expired_at IS NULL
+        AND voided_at IS NULL
+        AND valid_until < NOW()
+      RETURNING
+        org_id,
+        project_id,
+        campaign_id,
+        chain_id,
+        signer_address,
+        user_id,
+        task_id,
+        balance
+    )
+    INSERT INTO voucher_event (
+      org_id,
+      project_id,
+      campaign_id,
+      chain_id,
+      signer_address,
+      user_id,
+      task_id,
+      kind,
+      amount
+    )
+    SELECT
+      org_id,
+      project_id,
+      campaign_id,
+      chain_id,
+      signer_address,
+      user_id,
+      task_id,
+      'expired',
+      balance
+    FROM expired"
+  )
+  .execute(db)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(res.rows_affected())
+}
+
+struct Balance {
+  campaign_id: Uuid,
+  chain_id: i64,
+  signer_address: String,
+  task_id: String,
+  balance: i64,
+}
+
+// void the vouchers of revoked tasks, or of every task when `task_ids` is None. unspent vouchers
+// are removed, spent ones are kept with the spent points as a negative balance
+pub(super) async fn revoke_in(
+  tx: &mut Transaction<'_, Postgres>,
+  campaign_id: Uuid,
+  chain_id: i64,
+  signer_address: &str,
+  task_ids: Option<&[String]>,
+) -> Result<(), Error> {
+  query!(
+    "DELETE
+    FROM voucher
+    WHERE campaign_id = $1
+      AND chain_id = $2
+      AND signer_address = $3
+      AND ($4::TEXT[] IS NULL OR task_id = ANY($4))
+      AND voided_at IS NULL
+      AND transfer_id IS NULL
+      AND balance >= value",
+    campaign_id,
+    chain_id,
+    signer_address,
+    task_ids as _
+  )
+  .execute(&mut **tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  let debtors = query!(
+    "UPDATE voucher
+    SET
+      balance = balance - value,
+      valid_from = COALESCE(valid_from, CURRENT_DATE),
+      valid_until = NULL,
+      voided_at = NOW(),
+      updated_at = NOW()
+    WHERE campaign_id = $1
+      AND chain_id = $2
+      AND signer_address = $3
+      AND ($4::TEXT[] IS NULL OR task_id = ANY($4))
+      AND voided_at IS NULL
+      AND transfer_id IS NULL
+    RETURNING
+      project_id,
+      user_id",
+    campaign_id,
+    chain_id,
+    signer_address,
+    task_ids as _
+  )
+  .fetch_all(&mut **tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  if let Some(d) = debtors.first() {
+    settle_debt_in(tx, d.project_id, &d.user_id).await?;
+  }
+
+  Ok(())
+}
+
+// pay off the debts of a user in a project with their available points, oldest debts and
+// soonest to expire points first, returning what is still owed
+pub(super) async fn settle_debt_in(
+  tx: &mut Transaction<'_, Postgres>,
+  project_id: Uuid,
+  user_id: &str,
+) -> Result<i64, Error> {
+  let mut debts = query_as!(
+    Balance,
+    "SELECT
+      campaign_id,
+      chain_id,
+      signer_address,
+      task_id,
+      balance
+    FROM voucher
+    WHERE project_id = $1
+      AND user_id = $2
+      AND balance < 0
+    ORDER BY created_at ASC
+    FOR UPDATE",
+    project_id,
+    user_id
+  )
+  .fetch_all(&mut **tx)
+  .await
+  .map_err(handle_pg_error)?;
+  if debts.is_empty() {
+    return Ok(0);
+  }
+
+  let mut credits = query_as!(
+    Balance,
+    "SELECT
+      campaign_id,
+      chain_id,
+      signer_address,
+      task_id,
+      balance
+    FROM voucher
+    WHERE project_id = $1
+      AND user_id = $2
+      AND balance > 0
+      AND valid_from <= NOW()
+      AND (valid_until IS NULL OR valid_until >= NOW())
+    ORDER BY
+      valid_until ASC,
+      created_at ASC
+    FOR UPDATE",
+    project_id,
+    user_id
+  )
+  .fetch_all(&mut **tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  let mut credit = credits.iter_mut().peekable();
+  for debt in debts.iter_mut() {
+    while debt.balance < 0 {
+      let Some(c) = credit.peek_mut() else {
+        break;
+      };
+      let paid = c.balance.min(-debt.balance);
+      c.balance -= paid;
+      debt.balance += paid;
+      if c.balance == 0 {
+        credit.next();
+      }
+    }
+  }
+
+  let vouchers = debts.iter().chain(credits.iter());
+  query!(
+    "UPDATE voucher v
+    SET
+      balance = t.balance,
+      updated_at = NOW()
+    FROM UNNEST($1::UUID[], $2::BIGINT[], $3::TEXT[], $4::TEXT[], $5::BIGINT[])
+      AS t(campaign_id, chain_id, signer_address, task_id, balance)
+    WHERE v.campaign_id = t.campaign_id
+      AND v.chain_id = t.chain_id
+      AND v.signer_address = t.signer_address
+      AND v.task_id = t.task_id
+      AND v.balance <> t.balance",
+    &vouchers.clone().map(|v| v.campaign_id).collect::<Vec<_>>(),
+    &vouchers.clone().map(|v| v.chain_id).collect::<Vec<_>>(),
+    &vouchers
+      .clone()
+      .map(|v| v.signer_address.clone())
+      .collect::<Vec<_>>(),
+    &vouchers
+      .clone()
+      .map(|v| v.task_id.clone())
+      .collect::<Vec<_>>(),
+    &vouchers.map(|v| v.balance).collect::<Vec<_>>()
+  )
+  .execute(&mut **tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  Ok(debts.iter().map(|d| -d.balance).sum())
+}
+
+// pay off a user's debts in each project of a org, returning what is still owed
+pub(super) async fn settle_org_debt_in(
+  tx: &mut Transaction<'_, Postgres>,
+  org_id: Uuid,
+  user_id: &str,
+) -> Result<i64, Error> {
+  let projects = query!(
+    "SELECT DISTINCT project_id
+    FROM voucher
+    WHERE org_id = $1
+      AND user_id = $2
+      AND balance < 0",
+    org_id,
+    user_id
+  )
+  .fetch_all(&mut **tx)
+  .await
+  .map_err(handle_pg_error)?;
+
+  let mut owed = 0;
+  for p in projects {
+    owed += settle_debt_in(tx, p.project_id, user_id).await?;
+  }
+
+  Ok(owed)
+}
@@ -0,0 +1,82 @@
+use sqlx::PgPool;
+
+use crate::{
+  db::{
+    self,
+    campaign::{NftHoldConfig, Rule, RuleCheck},
+  },
+  subscan,
+};
+
+/// Who eligibility is being checked for.
+pub struct Context<'a> {
+  pub db: &'a PgPool,
+  pub subscan_client: &'a subscan::Client,
+  pub chain_id: i64,
+  /// The campaign contract, used when an NFT rule doesn't name its own.
+  pub contract_address: &'a str,
+  /// Unknown when checking ahead of joining without a wallet.
+  pub signer_address: Option<&'a str>,
+  pub user_id: &'a str,
+  pub country_id: Option<i16>,
+}
+
+/// Evaluate each rule for a participant, who is eligible when all of them pass.
+pub async fn check(ctx: &Context<'_>, rules: &[Rule]) -> Result<Vec<RuleCheck>, db::Error> {
+  let mut res = Vec::<RuleCheck>::new();
+  for rule in rules {
+    let (passed, detail) = match rule {
+      Rule::ClubMember { project_id } => (
+        db::project_membership::is_member(ctx.db, *project_id, ctx.user_id).await?,
+        None,
+      ),
+      Rule::NftHolder(c) => check_nft_holder(ctx, c).await,
+      Rule::CompletedCampaign { campaign_id } => (
+        db::engage_pub::has_completed(ctx.db, *campaign_id, ctx.user_id).await?,
+        None,
+      ),
+      Rule::Country { country_ids } => match ctx.country_id {
+        Some(id) => (country_ids.contains(&id), None),
+        None => (false, Some(String::from("country required"))),
+      },
+      Rule::MinXp { xp } => {
+        let current = db::me::get_xp(ctx.db, ctx.user_id).await?;
+        (current >= *xp, Some(format!("{} of {} xp", current, xp)))
+      }
+    };
+
+    res.push(RuleCheck {
+      rule: rule.clone(),
+      passed,
+      detail,
+    });
+  }
+
+  Ok(res)
+}
+
+async fn check_nft_holder(ctx: &Context<'_>, c: &NftHoldConfig) -> (bool, Option<String>) {
+  let signer_address = match ctx.signer_address {
+    Some(signer_address) => signer_address,
+    None => return (false, Some(String::from("wallet required"))),
+  };
+  let contract = c
+    .contract_address
+    .as_deref()
+    .unwrap_or(ctx.contract_address);
+
+  match ctx
+    .subscan_client
+    .count_nfts(ctx.chain_id, signer_address, contract)
+    .await
+  {
+    Ok(count) => (
+      count >= c.min_count as u64,
+      Some(format!("holds {} of {} required", count, c.min_count)),
+    ),
+    Err(err) => {
+      tracing::warn!("Error checking NFTs of {}: {}", signer_address, err);
+      (false, Some(format!("unable to check holdings: {}", err)))
+    }
+  }
+}
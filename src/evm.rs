@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+use ethers::{
+  providers::{Http, Middleware, Provider, ProviderError},
+  types::{Block, Transaction, TransactionReceipt, TxHash, H256},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+  #[error("Unsupported chain")]
+  UnsupportedChain,
+  #[error("Invalid RPC config: {0}")]
+  InvalidConfig(String),
+  #[error("Provider error: {0}")]
+  Provider(#[from] ProviderError),
+}
+
+/// A mined transaction along with its receipt and block.
+pub struct MinedTransaction {
+  pub tx: Transaction,
+  pub receipt: TransactionReceipt,
+  pub block: Block<H256>,
+}
+
+/// JSON-RPC providers keyed by chain id.
+#[derive(Clone, Debug, Default)]
+pub struct Client {
+  providers: HashMap<i64, Provider<Http>>,
+}
+
+impl Client {
+  /// Build from a comma separated list of `chain_id=url` pairs.
+  pub fn new(rpc_urls: &str) -> Result<Self, Error> {
+    let mut providers = HashMap::new();
+    for pair in rpc_urls.split(',').filter(|p| !p.trim().is_empty()) {
+      let (chain_id, url) = pair
+        .split_once('=')
+        .ok_or_else(|| Error::InvalidConfig(pair.to_owned()))?;
+      let chain_id = chain_id
+        .trim()
+        .parse::<i64>()
+        .map_err(|_| Error::InvalidConfig(pair.to_owned()))?;
+      let provider = Provider::<Http>::try_from(url.trim())
+        .map_err(|_| Error::InvalidConfig(pair.to_owned()))?;
+      providers.insert(chain_id, provider);
+    }
+
+    Ok(Self { providers })
+  }
+
+  /// Look up a transaction, returning None until it's mined.
+  pub async fn get_mined_transaction(
+    &self,
+    chain_id: i64,
+    hash: TxHash,
+  ) -> Result<Option<MinedTransaction>, Error> {
+    let provider = self
+      .providers
+      .get(&chain_id)
+      .ok_or(Error::UnsupportedChain)?;
+
+    let Some(tx) = provider.get_transaction(hash).await? else {
+      return Ok(None);
+    };
+    let Some(block_hash) = tx.block_hash else {
+      return Ok(None);
+    };
+    let receipt = match provider.get_transaction_receipt(hash).await? {
+      Some(receipt) => receipt,
+      None => return Ok(None),
+    };
+    let block = match provider.get_block(block_hash).await? {
+      Some(block) => block,
+      None => return Ok(None),
+    };
+
+    Ok(Some(MinedTransaction { tx, receipt, block }))
+  }
+}
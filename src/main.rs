@@ -1,4 +1,4 @@
-use std::{env, fs::File, path::Path, str::FromStr};
+use std::{env, fs, fs::File, path::Path, str::FromStr, time::Duration};
 
 use firebase_auth::FirebaseAuth;
 use http::HeaderValue;
@@ -14,17 +14,29 @@ use tracing_subscriber::{
 };
 
 use crate::{
-  api::rs::engage::{start_listening, EngageEvent},
-  auth::{user::UserService, ServiceAccount},
+  api::rs::{
+    engage::{start_listening, EngageEvent},
+    raffle::start_drawing,
+    voucher::start_expiring,
+  },
+  auth::{
+    local::{LocalAuth, LocalKey},
+    provider::{AuthProvider, ClaimsStore},
+    user::UserService,
+    ServiceAccount,
+  },
 };
 use tokio::sync::broadcast::channel;
 
 mod api;
 mod auth;
 mod db;
+mod eligibility;
+mod evm;
 mod mezzofy;
 mod rec_http;
 mod subscan;
+mod verify;
 
 static MIGRATOR: Migrator = sqlx::migrate!();
 
@@ -51,18 +63,53 @@ async fn run<'a>() {
     .init();
   tracing::info!("Log level: {}", log_level);
 
-  tracing::info!("Initialising Firebase client...");
-  let sa_path = env::var("FIREBASE_SERVICE_ACCOUNT_PATH")
-    .expect("env var FIREBASE_SERVICE_ACCOUNT_PATH should be set");
-  let sa_reader = File::open(Path::new(&sa_path))
-    .expect("file at FIREBASE_SERVICE_ACCOUNT_PATH should be readable");
-  let firebase_sa: ServiceAccount = serde_json::from_reader(sa_reader)
-    .expect("json in FIREBASE_SERVICE_ACCOUNT_PATH should be valid");
-  let firebase_auth = FirebaseAuth::new(&firebase_sa.project_id).await;
-  let claims_service = UserService::new(
-    &env::var("FIREBASE_API_KEY").expect("env var FIREBASE_API_KEY should be set"),
-    firebase_sa,
-  );
+  let (auth_provider, claims_service) = match env::var("AUTH_PROVIDER")
+    .unwrap_or(String::from("firebase"))
+    .as_str()
+  {
+    "local" => {
+      tracing::info!("Initialising local JWT auth...");
+      let key = match (
+        non_empty_var("LOCAL_JWT_SECRET"),
+        non_empty_var("LOCAL_JWT_PUBLIC_KEY_PATH"),
+      ) {
+        (Some(secret), _) => LocalKey::Hs256(secret),
+        (None, Some(path)) => LocalKey::Rs256(
+          fs::read_to_string(path).expect("file at LOCAL_JWT_PUBLIC_KEY_PATH should be readable"),
+        ),
+        (None, None) => {
+          panic!("env var LOCAL_JWT_SECRET or LOCAL_JWT_PUBLIC_KEY_PATH should be set")
+        }
+      };
+      let local_auth = LocalAuth::new(
+        key,
+        non_empty_var("LOCAL_JWT_ISSUER"),
+        non_empty_var("LOCAL_JWT_AUDIENCE"),
+      )
+      .expect("local JWT key should be valid");
+      let claims_service = ClaimsStore::Local(local_auth.store());
+      (AuthProvider::Local(Box::new(local_auth)), claims_service)
+    }
+    "firebase" => {
+      tracing::info!("Initialising Firebase client...");
+      let sa_path = env::var("FIREBASE_SERVICE_ACCOUNT_PATH")
+        .expect("env var FIREBASE_SERVICE_ACCOUNT_PATH should be set");
+      let sa_reader = File::open(Path::new(&sa_path))
+        .expect("file at FIREBASE_SERVICE_ACCOUNT_PATH should be readable");
+      let firebase_sa: ServiceAccount = serde_json::from_reader(sa_reader)
+        .expect("json in FIREBASE_SERVICE_ACCOUNT_PATH should be valid");
+      let firebase_auth = FirebaseAuth::new(&firebase_sa.project_id).await;
+      let claims_service = UserService::new(
+        &env::var("FIREBASE_API_KEY").expect("env var FIREBASE_API_KEY should be set"),
+        firebase_sa,
+      );
+      (
+        AuthProvider::Firebase(firebase_auth),
+        ClaimsStore::Firebase(Box::new(claims_service)),
+      )
+    }
+    other => panic!("AUTH_PROVIDER should be firebase or local, got {}", other),
+  };
 
   tracing::info!("Preparing DB connection...");
   let db_url = &env::var("DATABASE_URL").expect("env var DATABASE_URL should be set");
@@ -89,15 +136,36 @@ async fn run<'a>() {
     env::var("SUBSCAN_API_KEY").expect("SUBSCAN_API_KEY is missing from env"),
     reqwest::Client::new(),
   );
+  let evm_client = evm::Client::new(&env::var("EVM_RPC_URLS").unwrap_or_default())
+    .expect("EVM_RPC_URLS should be a list of chain_id=url pairs");
+
+  let voucher_expiry_period = env::var("VOUCHER_EXPIRY_INTERVAL_SECS")
+    .map(|secs| {
+      secs
+        .parse()
+        .expect("VOUCHER_EXPIRY_INTERVAL_SECS should be a number")
+    })
+    .unwrap_or(3600);
+  let expiring_db = sqlx_pool.clone();
+
+  let raffle_draw_period = env::var("RAFFLE_DRAW_INTERVAL_SECS")
+    .map(|secs| {
+      secs
+        .parse()
+        .expect("RAFFLE_DRAW_INTERVAL_SECS should be a number")
+    })
+    .unwrap_or(60);
+  let drawing_db = sqlx_pool.clone();
 
   tracing::info!("Crating service...");
   let server = api::Server::new(
     sqlx_pool,
-    firebase_auth,
+    auth_provider,
     claims_service,
     tx.clone(),
     mezzofy_client,
     subscan_client,
+    evm_client,
   );
 
   tracing::info!("Spawning PG => SSE worker...");
@@ -112,6 +180,18 @@ async fn run<'a>() {
     };
   });
 
+  tracing::info!("Spawning voucher expiry worker...");
+  tokio::spawn(start_expiring(
+    expiring_db,
+    Duration::from_secs(voucher_expiry_period),
+  ));
+
+  tracing::info!("Spawning raffle draw worker...");
+  tokio::spawn(start_drawing(
+    drawing_db,
+    Duration::from_secs(raffle_draw_period),
+  ));
+
   let cors = CorsLayer::new()
     .allow_methods(Any)
     .allow_origin(Any)
@@ -152,3 +232,8 @@ async fn run<'a>() {
   .await
   .unwrap();
 }
+
+/// Read an env var, treating an empty or blank value as unset.
+fn non_empty_var(key: &str) -> Option<String> {
+  env::var(key).ok().filter(|v| !v.trim().is_empty())
+}
@@ -60,4 +60,18 @@ impl Client {
       _ => Err(Error::Subscan { status, text }),
     }
   }
+
+  /// Sum the balances an owner holds of a given NFT contract.
+  pub async fn count_nfts(&self, chain_id: i64, owner: &str, contract: &str) -> Result<u64, Error> {
+    let contract = contract.to_lowercase();
+    let tokens = self.get_nfts_for_owner(chain_id, owner).await?;
+
+    Ok(
+      tokens
+        .iter()
+        .filter(|t| t.contract.to_lowercase() == contract)
+        .map(|t| t.balance.parse::<u64>().unwrap_or_default())
+        .sum(),
+    )
+  }
 }
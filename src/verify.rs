@@ -0,0 +1,375 @@
+use std::{collections::HashMap, str::FromStr};
+
+use chrono::{NaiveDate, Utc};
+use ethers::types::{Address, H256, U256};
+use regex::Regex;
+use reqwest::Url;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+  db::{
+    self,
+    campaign::{
+      ApprovalRule, NftHoldConfig, OnchainTxConfig, QuizConfig, ReferralConfig, Task, TaskKind,
+      TaskLock,
+    },
+    engage::{Accepted, Submission, Submissions, Verification},
+  },
+  evm, subscan,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+  #[error("Unknown task {0}")]
+  UnknownTask(String),
+  #[error("Invalid submission for task {0}")]
+  InvalidSubmission(String),
+  #[error("No attempts left for task {0}")]
+  AttemptsExhausted(String),
+  #[error("Task {0} is locked, {1}")]
+  TaskLocked(String, TaskLock),
+  #[error("Proof for task {0} was already submitted by another participant")]
+  DuplicateProof(String),
+  #[error(transparent)]
+  Db(#[from] db::Error),
+}
+
+/// Who a submission is being checked for.
+pub struct Context<'a> {
+  pub db: &'a PgPool,
+  pub subscan_client: &'a subscan::Client,
+  pub evm_client: &'a evm::Client,
+  pub project_id: Uuid,
+  pub campaign_id: Uuid,
+  pub chain_id: i64,
+  /// The campaign contract, used when a task doesn't name its own.
+  pub contract_address: &'a str,
+  pub start_at: Option<NaiveDate>,
+  pub signer_address: &'a str,
+  pub user_id: &'a str,
+  /// Refuse proofs other participants of the campaign already submitted.
+  pub block_fraud: bool,
+}
+
+/// Validate submissions against their tasks and run the checks the server can do itself,
+/// recording the outcome in each submission.
+///
+/// Returns the tasks that passed and can be accepted without review.
+pub async fn verify(
+  ctx: &Context<'_>,
+  tasks: &[Task],
+  submissions: &mut Submissions,
+) -> Result<Accepted, Error> {
+  let mut accepted = Accepted::new();
+  let previous = previous_engage(ctx).await?;
+  let completions = if tasks.iter().any(|t| t.max_completions.is_some()) {
+    db::engage_pub::count_completions(ctx.db, ctx.campaign_id).await?
+  } else {
+    HashMap::new()
+  };
+
+  for (task_id, sub) in submissions.iter_mut() {
+    let task = tasks
+      .iter()
+      .find(|t| &t.id == task_id)
+      .ok_or_else(|| Error::UnknownTask(task_id.to_owned()))?;
+    if let Some(lock) = task.lock(
+      &previous.accepted,
+      completions.get(task_id).copied().unwrap_or_default(),
+    ) {
+      return Err(Error::TaskLocked(task_id.to_owned(), lock));
+    }
+    if !task.kind.accepts(sub) {
+      return Err(Error::InvalidSubmission(task_id.to_owned()));
+    }
+    let urls = sub.proof_urls();
+    if ctx.block_fraud && !urls.is_empty() {
+      let duplicates =
+        db::engage_pub::find_duplicate_proofs(ctx.db, ctx.campaign_id, ctx.user_id, &urls).await?;
+      if !duplicates.is_empty() {
+        return Err(Error::DuplicateProof(task_id.to_owned()));
+      }
+    }
+
+    sub.attempts = None;
+    if let TaskKind::Quiz(c) = &task.kind {
+      let attempts = previous
+        .submissions
+        .get(task_id)
+        .and_then(|s| s.attempts)
+        .unwrap_or_default()
+        + 1;
+      if c.max_attempts.is_some_and(|max| attempts > max) {
+        return Err(Error::AttemptsExhausted(task_id.to_owned()));
+      }
+      sub.attempts = Some(attempts);
+    }
+
+    sub.verification = check(ctx, task_id, &task.kind, sub).await?;
+    if let Some(Verification { passed: true, .. }) = sub.verification {
+      accepted.insert(task_id.to_owned(), true);
+    }
+  }
+
+  Ok(accepted)
+}
+
+async fn check(
+  ctx: &Context<'_>,
+  task_id: &str,
+  kind: &TaskKind,
+  sub: &Submission,
+) -> Result<Option<Verification>, Error> {
+  let res = match kind {
+    TaskKind::CheckIn => (true, None),
+    TaskKind::Referral(c) => check_referral(ctx, c).await?,
+    TaskKind::NftHold(c) => check_nft_hold(ctx, c).await,
+    TaskKind::OnchainTx(c) => check_onchain_tx(ctx, task_id, c, sub).await?,
+    TaskKind::Quiz(c) => check_quiz(c, sub),
+    // Everything else still goes through a reviewer, including visited links since the url is
+    // public and a matching submission proves nothing
+    _ => return Ok(None),
+  };
+
+  Ok(Some(Verification {
+    passed: res.0,
+    detail: res.1,
+    checked_at: Utc::now(),
+  }))
+}
+
+/// Match the submissions the server couldn't check itself against their task's auto-approval
+/// rules.
+///
+/// Returns the tasks meeting all of their rules.
+pub async fn match_rules(
+  ctx: &Context<'_>,
+  tasks: &[Task],
+  submissions: &Submissions,
+) -> Result<Accepted, Error> {
+  let mut accepted = Accepted::new();
+  let mut is_member = None;
+  for (task_id, sub) in submissions.iter().filter(|(_, s)| s.verification.is_none()) {
+    let rules = match tasks.iter().find(|t| &t.id == task_id) {
+      Some(Task {
+        auto_approve: Some(rules),
+        ..
+      }) => rules,
+      _ => continue,
+    };
+
+    let mut passed = true;
+    for rule in rules {
+      passed = match rule {
+        ApprovalRule::ClubMember => match is_member {
+          Some(m) => m,
+          None => {
+            let m = db::project_membership::is_member(ctx.db, ctx.project_id, ctx.user_id).await?;
+            *is_member.insert(m)
+          }
+        },
+        _ => matches_rule(rule, sub),
+      };
+      if !passed {
+        break;
+      }
+    }
+    if passed {
+      accepted.insert(task_id.to_owned(), true);
+    }
+  }
+
+  Ok(accepted)
+}
+
+fn matches_rule(rule: &ApprovalRule, sub: &Submission) -> bool {
+  match rule {
+    ApprovalRule::LinkDomain { domains } => sub
+      .link
+      .as_deref()
+      .and_then(|l| Url::parse(l).ok())
+      .and_then(|u| u.host_str().map(|h| h.to_lowercase()))
+      .is_some_and(|host| {
+        domains.iter().any(|d| {
+          let d = d.to_lowercase();
+          host == d || host.ends_with(&format!(".{}", d))
+        })
+      }),
+    ApprovalRule::LinkPattern { pattern } => match (&sub.link, Regex::new(pattern)) {
+      (Some(link), Ok(re)) => re.is_match(link),
+      _ => false,
+    },
+    ApprovalRule::MinImages { count } => sub.images.as_ref().map_or(0, |i| i.len()) >= *count,
+    ApprovalRule::MinMessageLength { length } => {
+      sub
+        .message
+        .as_deref()
+        .map_or(0, |m| m.trim().chars().count())
+        >= *length
+    }
+    ApprovalRule::ClubMember => false,
+  }
+}
+
+/// What the participant submitted and had accepted so far.
+#[derive(Default)]
+struct Previous {
+  submissions: Submissions,
+  accepted: Accepted,
+}
+
+async fn previous_engage(ctx: &Context<'_>) -> Result<Previous, Error> {
+  match db::engage_pub::get(ctx.db, ctx.campaign_id, ctx.chain_id, ctx.signer_address).await {
+    Ok(engage) => Ok(Previous {
+      submissions: engage.submissions.0,
+      accepted: engage.accepted.0,
+    }),
+    Err(db::Error::NotFound) => Ok(Previous::default()),
+    Err(err) => Err(Error::Db(err)),
+  }
+}
+
+fn check_quiz(c: &QuizConfig, sub: &Submission) -> (bool, Option<String>) {
+  let correct = c
+    .questions
+    .iter()
+    .filter(|q| {
+      sub
+        .answers
+        .as_ref()
+        .and_then(|a| a.get(&q.id))
+        .is_some_and(|a| q.is_correct(a))
+    })
+    .count();
+
+  (
+    correct >= c.pass_score as usize,
+    Some(format!("{} of {} correct", correct, c.questions.len())),
+  )
+}
+
+async fn check_referral(
+  ctx: &Context<'_>,
+  c: &ReferralConfig,
+) -> Result<(bool, Option<String>), Error> {
+  let count =
+    db::engage_pub::count_referrals(ctx.db, ctx.campaign_id, ctx.signer_address, ctx.user_id)
+      .await?;
+
+  Ok((
+    count >= c.min_referrals as i64,
+    Some(format!("{} of {} referrals", count, c.min_referrals)),
+  ))
+}
+
+async fn check_nft_hold(ctx: &Context<'_>, c: &NftHoldConfig) -> (bool, Option<String>) {
+  let contract = c
+    .contract_address
+    .as_deref()
+    .unwrap_or(ctx.contract_address);
+
+  match ctx
+    .subscan_client
+    .count_nfts(ctx.chain_id, ctx.signer_address, contract)
+    .await
+  {
+    Ok(count) => (
+      count >= c.min_count as u64,
+      Some(format!("holds {} of {} required", count, c.min_count)),
+    ),
+    Err(err) => {
+      tracing::warn!("Error checking NFTs of {}: {}", ctx.signer_address, err);
+      (false, Some(format!("unable to check holdings: {}", err)))
+    }
+  }
+}
+
+async fn check_onchain_tx(
+  ctx: &Context<'_>,
+  task_id: &str,
+  c: &OnchainTxConfig,
+  sub: &Submission,
+) -> Result<(bool, Option<String>), Error> {
+  let hash = match sub.tx_hash.as_deref().map(H256::from_str) {
+    Some(Ok(hash)) => hash,
+    _ => return Ok((false, Some(String::from("invalid tx hash")))),
+  };
+
+  let res = match ctx
+    .evm_client
+    .get_mined_transaction(ctx.chain_id, hash)
+    .await
+  {
+    Ok(Some(mined)) => match tx_mismatch(ctx, c, &mined) {
+      Some(reason) => (false, Some(String::from(reason))),
+      // a transaction counts once, for the task and participant it was first accepted for
+      None => match db::engage_pub::use_tx(
+        ctx.db,
+        ctx.chain_id,
+        &format!("{:?}", hash),
+        ctx.campaign_id,
+        task_id,
+        ctx.user_id,
+      )
+      .await?
+      {
+        true => (
+          true,
+          Some(format!("block {}", mined.block.number.unwrap_or_default())),
+        ),
+        false => (false, Some(String::from("transaction already used"))),
+      },
+    },
+    Ok(None) => (
+      false,
+      Some(String::from("transaction not found or not yet mined")),
+    ),
+    Err(err) => {
+      tracing::warn!("Error checking tx {:?}: {}", hash, err);
+      (false, Some(format!("unable to check transaction: {}", err)))
+    }
+  };
+
+  Ok(res)
+}
+
+/// Find the first requirement the transaction doesn't meet.
+fn tx_mismatch(
+  ctx: &Context<'_>,
+  c: &OnchainTxConfig,
+  mined: &evm::MinedTransaction,
+) -> Option<&'static str> {
+  let contract = Address::from_str(
+    c.contract_address
+      .as_deref()
+      .unwrap_or(ctx.contract_address),
+  );
+
+  if mined.receipt.status != Some(1.into()) {
+    return Some("transaction reverted");
+  }
+  if Address::from_str(ctx.signer_address).ok() != Some(mined.tx.from) {
+    return Some("sender does not match");
+  }
+  if contract.is_err() || mined.tx.to != contract.ok() {
+    return Some("recipient does not match");
+  }
+  if let Some(selector) = c.method_selector() {
+    if !mined.tx.input.starts_with(&selector) {
+      return Some("method does not match");
+    }
+  }
+  if let Some(min_value) = c.min_value() {
+    if mined.tx.value < min_value {
+      return Some("value below minimum");
+    }
+  }
+  if let Some(start_at) = ctx.start_at {
+    let start = start_at.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
+    if mined.block.timestamp < U256::from(start) {
+      return Some("sent before the campaign started");
+    }
+  }
+  None
+}